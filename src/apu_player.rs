@@ -0,0 +1,102 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::sound::AudioPlayer;
+
+// Samples are moved from the shared buffer to the source in chunks, to avoid locking per sample
+const CHUNK_LEN: usize = 512;
+
+/// Feeds the output of the emulated APU to a rodio mixer.
+#[derive(Clone)]
+pub struct ApuPlayer {
+    buffer: Arc<Mutex<VecDeque<f32>>>,
+    sample_rate: u32,
+}
+
+impl ApuPlayer {
+    /// Creates a player along with the source that plays its output.
+    pub fn new(sample_rate: u32) -> (ApuPlayer, ApuSource) {
+        let buffer = Arc::new(Mutex::new(VecDeque::new()));
+
+        let source = ApuSource {
+            buffer: buffer.clone(),
+            pending: VecDeque::with_capacity(CHUNK_LEN),
+            sample_rate,
+        };
+
+        let player = ApuPlayer {
+            buffer,
+            sample_rate,
+        };
+
+        (player, source)
+    }
+}
+
+impl AudioPlayer for ApuPlayer {
+    fn play(&mut self, left_channel: &[f32], right_channel: &[f32]) {
+        debug_assert_eq!(left_channel.len(), right_channel.len());
+
+        let mut buffer = self.buffer.lock().unwrap();
+
+        for (l, r) in left_channel.iter().zip(right_channel) {
+            // Don't buffer more than a second, so that we catch up quickly after fast forwarding
+            if buffer.len() > self.sample_rate as usize * 2 {
+                return;
+            }
+
+            buffer.push_back(*l);
+            buffer.push_back(*r);
+        }
+    }
+
+    fn samples_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn underflowed(&self) -> bool {
+        self.buffer.lock().unwrap().is_empty()
+    }
+}
+
+/// Never ending stereo source that plays whatever the `ApuPlayer` has produced, or silence.
+pub struct ApuSource {
+    buffer: Arc<Mutex<VecDeque<f32>>>,
+    pending: VecDeque<f32>,
+    sample_rate: u32,
+}
+
+impl Iterator for ApuSource {
+    type Item = rodio::Sample;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pending.is_empty() {
+            let mut buffer = self.buffer.lock().unwrap();
+            let len = usize::min(buffer.len(), CHUNK_LEN);
+            self.pending.extend(buffer.drain(..len));
+        }
+
+        Some(self.pending.pop_front().unwrap_or(0.0))
+    }
+}
+
+impl rodio::Source for ApuSource {
+    fn current_span_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> rodio::ChannelCount {
+        2
+    }
+
+    fn sample_rate(&self) -> rodio::SampleRate {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
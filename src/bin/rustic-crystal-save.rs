@@ -0,0 +1,10 @@
+fn main() {
+    env_logger::init();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    if let Err(error) = rustic_crystal::save_editor::run(&args) {
+        eprintln!("{error}");
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,297 @@
+//! Turns the RGB555 colours of the CGB palettes into the RGB888 colours that are shown.
+//!
+//! The colours of a real Game Boy Color screen look nothing like the raw palette values, so a
+//! profile is picked to correct them. Every profile is precomputed into a table with one entry
+//! for each of the 32768 colours, which the GPU looks up while drawing.
+
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use serde::{Deserialize, Serialize};
+
+const COLORS: usize = 0x8000;
+
+type Table = Arc<Vec<[u8; 3]>>;
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorProfile {
+    /// The palette values scaled up as they are, very saturated.
+    Raw,
+    /// The correction from the Gambatte emulator.
+    #[default]
+    Gambatte,
+    /// Mimics the colours and contrast of the GBC screen, for displays with sRGB gamma.
+    GbcLcd,
+    /// A user-supplied 3D LUT in the `.cube` format.
+    Lut,
+}
+
+impl ColorProfile {
+    const ALL: [ColorProfile; 4] = [
+        ColorProfile::Raw,
+        ColorProfile::Gambatte,
+        ColorProfile::GbcLcd,
+        ColorProfile::Lut,
+    ];
+
+    /// The next profile, for cycling through them with a hotkey.
+    pub fn next(self) -> ColorProfile {
+        let index = ColorProfile::ALL
+            .iter()
+            .position(|p| *p == self)
+            .unwrap_or(0);
+        ColorProfile::ALL[(index + 1) % ColorProfile::ALL.len()]
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ColorSettings {
+    pub profile: ColorProfile,
+    /// The `.cube` file used by the `lut` profile.
+    pub lut: Option<PathBuf>,
+}
+
+/// The colour profile shared between the frontend, which picks it, and the GPU, which uses it.
+pub struct ColorCorrection {
+    table: Mutex<Table>,
+    generation: AtomicU32,
+}
+
+impl ColorCorrection {
+    pub fn new(settings: &ColorSettings) -> Arc<ColorCorrection> {
+        Arc::new(ColorCorrection {
+            table: Mutex::new(build_table(settings)),
+            generation: AtomicU32::new(0),
+        })
+    }
+
+    /// Switches to the profile in `settings`, from the next frame on.
+    pub fn update(&self, settings: &ColorSettings) {
+        let table = build_table(settings);
+        *self.table.lock().unwrap() = table;
+        self.generation.fetch_add(1, Ordering::Release);
+    }
+}
+
+/// The GPU's copy of the current table, so that it doesn't have to lock for every pixel.
+pub struct ColorTable {
+    table: Table,
+    shared: Option<Arc<ColorCorrection>>,
+    generation: u32,
+}
+
+impl ColorTable {
+    pub fn new() -> ColorTable {
+        ColorTable {
+            table: Arc::new(table_from_fn(gambatte)),
+            shared: None,
+            generation: 0,
+        }
+    }
+
+    pub fn set_shared(&mut self, shared: Arc<ColorCorrection>) {
+        self.table = shared.table.lock().unwrap().clone();
+        self.generation = shared.generation.load(Ordering::Acquire);
+        self.shared = Some(shared);
+    }
+
+    /// Picks up a profile change, called between frames.
+    pub fn refresh(&mut self) {
+        let Some(shared) = &self.shared else {
+            return;
+        };
+
+        let generation = shared.generation.load(Ordering::Acquire);
+
+        if generation != self.generation {
+            self.table = shared.table.lock().unwrap().clone();
+            self.generation = generation;
+        }
+    }
+
+    /// Looks up a colour, with `r`, `g` and `b` between 0 and 1F.
+    pub fn get(&self, r: u8, g: u8, b: u8) -> [u8; 3] {
+        self.table[r as usize | (g as usize) << 5 | (b as usize) << 10]
+    }
+}
+
+fn build_table(settings: &ColorSettings) -> Table {
+    let table = match settings.profile {
+        ColorProfile::Raw => table_from_fn(raw),
+        ColorProfile::Gambatte => table_from_fn(gambatte),
+        ColorProfile::GbcLcd => table_from_fn(gbc_lcd),
+        ColorProfile::Lut => match load_lut(settings.lut.as_deref()) {
+            Ok(lut) => table_from_fn(|r, g, b| lut.apply(r, g, b)),
+            Err(error) => {
+                log::error!("Failed to load colour LUT, using the Gambatte profile: {error}");
+                table_from_fn(gambatte)
+            }
+        },
+    };
+
+    Arc::new(table)
+}
+
+fn table_from_fn(f: impl Fn(u8, u8, u8) -> [u8; 3]) -> Vec<[u8; 3]> {
+    (0..COLORS)
+        .map(|i| f((i & 0x1f) as u8, ((i >> 5) & 0x1f) as u8, (i >> 10) as u8))
+        .collect()
+}
+
+fn expand(v: u8) -> u8 {
+    (v << 3) | (v >> 2)
+}
+
+fn raw(r: u8, g: u8, b: u8) -> [u8; 3] {
+    [expand(r), expand(g), expand(b)]
+}
+
+fn gambatte(r: u8, g: u8, b: u8) -> [u8; 3] {
+    // Taken from the Gambatte emulator
+    let r = r as u32;
+    let g = g as u32;
+    let b = b as u32;
+
+    [
+        ((r * 13 + g * 2 + b) >> 1) as u8,
+        ((g * 3 + b) << 1) as u8,
+        ((r * 3 + g * 2 + b * 11) >> 1) as u8,
+    ]
+}
+
+fn gbc_lcd(r: u8, g: u8, b: u8) -> [u8; 3] {
+    // Colour matrix and luminance of the GBC screen, as measured by Pokefan531
+    const MATRIX: [[f32; 3]; 3] = [
+        [0.82, 0.125, 0.195],
+        [0.24, 0.665, 0.075],
+        [-0.06, 0.21, 0.73],
+    ];
+    const LUMINANCE: f32 = 0.94;
+    const GAMMA: f32 = 2.2;
+
+    let linear = [r, g, b].map(|v| (v as f32 / 31.0).powf(GAMMA));
+
+    MATRIX.map(|row| {
+        let v = row.iter().zip(&linear).map(|(m, c)| m * c).sum::<f32>() * LUMINANCE;
+        (v.clamp(0.0, 1.0).powf(1.0 / GAMMA) * 255.0).round() as u8
+    })
+}
+
+fn load_lut(path: Option<&Path>) -> Result<Lut, Box<dyn Error>> {
+    let path = path.ok_or("no `lut` file is set in the colour settings")?;
+    let text = std::fs::read_to_string(path)?;
+
+    Ok(Lut::parse(&text)?)
+}
+
+/// A 3D LUT, read from the `.cube` format used by most colour grading tools.
+struct Lut {
+    size: usize,
+    // Red changes fastest, then green, then blue
+    entries: Vec<[f32; 3]>,
+}
+
+impl Lut {
+    fn parse(text: &str) -> Result<Lut, String> {
+        let mut size = None;
+        let mut entries = Vec::new();
+
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(value) = line.strip_prefix("LUT_3D_SIZE") {
+                let value = value.trim().parse::<usize>().ok();
+                size = Some(value.filter(|s| *s >= 2).ok_or("invalid LUT_3D_SIZE")?);
+                continue;
+            }
+
+            if line.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                // TITLE, DOMAIN_MIN and the like, the domain is assumed to be 0 to 1
+                continue;
+            }
+
+            let values: Vec<f32> = line
+                .split_whitespace()
+                .map(str::parse)
+                .collect::<Result<_, _>>()
+                .map_err(|_| format!("invalid line: {line}"))?;
+
+            match values[..] {
+                [r, g, b] => entries.push([r, g, b]),
+                _ => return Err(format!("invalid line: {line}")),
+            }
+        }
+
+        let size = size.ok_or("missing LUT_3D_SIZE, only 3D LUTs are supported")?;
+
+        if entries.len() != size * size * size {
+            return Err(format!(
+                "expected {} entries, found {}",
+                size * size * size,
+                entries.len()
+            ));
+        }
+
+        Ok(Lut { size, entries })
+    }
+
+    fn entry(&self, r: usize, g: usize, b: usize) -> [f32; 3] {
+        self.entries[r + g * self.size + b * self.size * self.size]
+    }
+
+    /// Looks up a colour with trilinear interpolation.
+    fn apply(&self, r: u8, g: u8, b: u8) -> [u8; 3] {
+        let max = (self.size - 1) as f32;
+
+        let [(r0, r1, rt), (g0, g1, gt), (b0, b1, bt)] = [r, g, b].map(|v| {
+            let pos = v as f32 / 31.0 * max;
+            let low = pos.floor() as usize;
+            (low, usize::min(low + 1, self.size - 1), pos - low as f32)
+        });
+
+        let lerp = |a: [f32; 3], b: [f32; 3], t: f32| -> [f32; 3] {
+            std::array::from_fn(|i| a[i] + (b[i] - a[i]) * t)
+        };
+
+        let c00 = lerp(self.entry(r0, g0, b0), self.entry(r1, g0, b0), rt);
+        let c10 = lerp(self.entry(r0, g1, b0), self.entry(r1, g1, b0), rt);
+        let c01 = lerp(self.entry(r0, g0, b1), self.entry(r1, g0, b1), rt);
+        let c11 = lerp(self.entry(r0, g1, b1), self.entry(r1, g1, b1), rt);
+
+        let c = lerp(lerp(c00, c10, gt), lerp(c01, c11, gt), bt);
+
+        c.map(|v| (v.clamp(0.0, 1.0) * 255.0).round() as u8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lut() {
+        let lut = Lut::parse(
+            "TITLE \"identity\"\n\
+             LUT_3D_SIZE 2\n\
+             0 0 0\n1 0 0\n0 1 0\n1 1 0\n\
+             0 0 1\n1 0 1\n0 1 1\n1 1 1\n",
+        )
+        .unwrap();
+
+        for (r, g, b) in [(0, 0, 0), (31, 0, 15), (15, 31, 31)] {
+            assert_eq!(lut.apply(r, g, b), raw(r, g, b));
+        }
+
+        assert!(Lut::parse("LUT_3D_SIZE 2\n0 0 0\n").is_err());
+    }
+}
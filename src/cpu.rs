@@ -1,12 +1,22 @@
+use std::panic::{self, AssertUnwindSafe};
 use std::path::PathBuf;
-use std::sync::mpsc::{Receiver, SyncSender};
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
 
+use crate::color::ColorCorrection;
+use crate::crash_report::{CallHistory, CrashReport};
+use crate::error::{self, Error};
+use crate::frame_buffer::FrameWriter;
 use crate::game_state::GameState;
 use crate::keypad::KeypadEvent;
+use crate::mixer::{Mixer, TransitionSettings};
 use crate::mmu::Mmu;
 use crate::save_state::SaveState;
 use crate::serial::SerialCallback;
-use crate::sound2::Sfx;
+use crate::sound2::{AudioMode, Sfx};
+use crate::texture_pack::TexturePack;
+use crate::trace;
+use crate::vram_viewer::DebugView;
 use crate::StrResult;
 
 #[derive(Copy, Clone)]
@@ -36,12 +46,13 @@ pub struct Cpu<'a> {
     pub ime: bool,
     setdi: u32,
     setei: u32,
+    calls: CallHistory,
 }
 
 impl<'a> Cpu<'a> {
     pub fn new_cgb(
         serial_callback: Option<SerialCallback<'a>>,
-        update_screen: SyncSender<Vec<u8>>,
+        screen: FrameWriter,
         keypad_events: Receiver<KeypadEvent>,
     ) -> StrResult<Cpu<'a>> {
         Ok(Cpu {
@@ -60,7 +71,49 @@ impl<'a> Cpu<'a> {
             ime: true,
             setdi: 0,
             setei: 0,
-            mmu: Mmu::new_cgb(serial_callback, update_screen, keypad_events)?,
+            calls: CallHistory::new(),
+            mmu: Mmu::new_cgb(serial_callback, screen, keypad_events)?,
+        })
+    }
+
+    /// Runs the game from the entry point until it stops, either because the frontend went away
+    /// or because of an error.
+    pub fn run(&mut self) -> Result<(), Error> {
+        panic::catch_unwind(AssertUnwindSafe(|| self.call(0x0100))).map_err(Error::from_panic)
+    }
+
+    /// Collects the state of the machine, for when `run` returned an error.
+    pub fn crash_report(&self, error: &Error) -> CrashReport {
+        let registers = format!(
+            "AF={:04x} BC={:04x} DE={:04x} HL={:04x} SP={:04x} PC={:04x} IME={}",
+            self.af(),
+            self.bc(),
+            self.de(),
+            self.hl(),
+            self.sp,
+            self.pc,
+            self.ime as u8,
+        );
+
+        CrashReport::new(
+            error,
+            &self.calls,
+            registers,
+            self.mmu.snapshot(),
+            self.borrow_sram().clone(),
+        )
+    }
+
+    fn hook<F: FnOnce(&mut Self)>(&mut self, f: F) {
+        let _span = trace::hook_span::<F>();
+        f(self);
+    }
+
+    fn rust_only(&self, name: &'static str) -> ! {
+        error::raise(Error::RustOnlyRoutine {
+            name,
+            bank: self.bank(),
+            pc: self.pc,
         })
     }
 
@@ -68,6 +121,8 @@ impl<'a> Cpu<'a> {
     pub fn call(&mut self, pc: u16) {
         assert_ne!(pc, 0x0000);
 
+        self.calls.record(self.bank(), pc);
+
         self.stack_push(0x0000);
         self.pc = pc;
 
@@ -75,107 +130,107 @@ impl<'a> Cpu<'a> {
             match (self.bank(), self.pc) {
                 (_, 0x0000) => break,
 
-                (_, 0x0100) => crate::game::home::init::start(self),
-                (_, 0x017d) => panic!("init should only be called from Rust"),
-                (_, 0x0245) => panic!("clear_vram should only be called from Rust"),
-                (_, 0x025a) => panic!("clear_wram should only be called from Rust"),
-                (_, 0x0270) => panic!("clears_scratch should only be called from Rust"),
-                (_, 0x3dfe) => crate::game::home::audio::terminate_exp_bar_sound(self),
-
-                (0x03, 0x5b3f) => crate::game::engine::pokemon::move_mon::send_get_mon_into_from_box(self),
-                (0x03, 0x5cb6) => crate::game::engine::pokemon::move_mon::restore_pp_of_deposited_pokemon(self),
-                (0x03, 0x5e6e) => crate::game::engine::pokemon::move_mon::send_mon_into_box(self),
-                (0x03, 0x5f47) => panic!("shift_box_mon should only be called from Rust"),
-                (0x03, 0x6039) => crate::game::engine::pokemon::move_mon::remove_mon_from_party_or_box(self),
-                (0x03, 0x68a2) => crate::game::engine::items::item_effects::poke_ball_effect(self),
-                (0x03, 0x6c29) => panic!("ultra_ball_multiplier should only be called from Rust"),
-                (0x03, 0x6c2f) => panic!("great_ball_multiplier should only be called from Rust"),
-                (0x03, 0x6ccc) => panic!("lure_ball_multiplier should only be called from Rust"),
-                (0x03, 0x6cdd) => panic!("moon_ball_multiplier should only be called from Rust"),
-                (0x03, 0x6d68) => panic!("fast_ball_multiplier should only be called from Rust"),
-                (0x03, 0x6d8c) => panic!("level_ball_multiplier should only be called from Rust"),
-                (0x03, 0x6dfa) => panic!("return_to_battle_use_ball should only be called from Rust"),
-
-                (0x05, 0x4a83) => crate::game::engine::menus::save::change_box_save_game(self),
-                (0x05, 0x4b5f) => crate::game::engine::menus::save::add_hall_of_fame_entry(self),
-                (0x05, 0x4b89) => crate::game::engine::menus::save::ask_overwrite_save_file(self),
-                (0x05, 0x4c10) => crate::game::engine::menus::save::save_game_data(self),
-                (0x05, 0x4e0c) => crate::game::engine::menus::save::save_box(self),
-                (0x05, 0x4f1c) => crate::game::engine::menus::save::try_load_save_data(self),
-                (0x05, 0x4f84) => panic!("check_primary_save_file should only be called from Rust"),
-                (0x05, 0x5021) => crate::game::engine::menus::save::load_box(self),
-                (0x05, 0x50d8) => panic!("get_box_address should only be called from Rust"),
-                (0x05, 0x50f9) => panic!("save_box_address should only be called from Rust"),
-                (0x05, 0x517d) => panic!("load_box_address should only be called from Rust"),
-                (0x05, 0x5a45) => crate::game::engine::items::mart::open_mart_dialog(self),
-                (0x05, 0x5a61) => panic!("mart_dialog should only be called from Rust"),
-                (0x05, 0x5a6e) => panic!("herb_shop should only be called from Rust"),
-                (0x05, 0x5a84) => panic!("bargain_shop should only be called from Rust"),
-                (0x05, 0x5aae) => panic!("pharmacist should only be called from Rust"),
-                (0x05, 0x5ac4) => panic!("rooftop_sale should only be called from Rust"),
-                (0x05, 0x5b10) => panic!("load_mart_pointer should only be called from Rust"),
-                (0x05, 0x5b31) => panic!("get_mart should only be called from Rust"),
-                (0x05, 0x5b47) => panic!("standard_mart should only be called from Rust"),
-                (0x05, 0x5bbb) => panic!("far_read_mart should only be called from Rust"),
-                (0x05, 0x5c25) => panic!("read_mart should only be called from Rust"),
-                (0x05, 0x5dbc) => crate::game::engine::items::mart::bargain_shop_ask_purchase_quantity_get_price(self),
-                (0x05, 0x5df9) => crate::game::engine::items::mart::rooftop_sale_ask_purchase_quantity_get_sale_price(self),
-                (0x05, 0x70e4) => crate::game::engine::pokemon::breeding::get_egg_move(self),
-                (0x05, 0x720b) => panic!("get_breedmon_move_pointer should only be called from Rust"),
-
-                (0x0a, 0x5ce8) => crate::game::engine::link::link::set_bits_for_link_trade_request(self),
-                (0x0a, 0x5d11) => crate::game::engine::link::link::wait_for_linked_friend(self),
-                (0x0a, 0x5ff8) => crate::game::engine::overworld::wildmons::load_wild_mon_data(self),
-                (0x0a, 0x601f) => crate::game::engine::overworld::wildmons::find_nest(self),
-                (0x0a, 0x614f) => crate::game::engine::overworld::wildmons::choose_wild_encounter(self),
-                (0x0a, 0x6200) => panic!("load_wild_mon_data_pointer should only be called from Rust"),
-                (0x0a, 0x6205) => panic!("grass_wildmon_lookup should only be called from Rust"),
-                (0x0a, 0x621d) => panic!("water_wildmon_lookup should only be called from Rust"),
-                (0x0a, 0x6235) => panic!("johto_wildmon_check should only be called from Rust"),
-                (0x0a, 0x623d) => panic!("swarm_wildmon_check should only be called from Rust"),
-                (0x0a, 0x627a) => panic!("normal_wildmon_ok should only be called from Rust"),
-                (0x0a, 0x627f) => panic!("copy_curr_map_de should only be called from Rust"),
-                (0x0a, 0x6288) => panic!("look_up_wildmons_for_map_de should only be called from Rust"),
-                (0x0a, 0x62ce) => panic!("check_encounter_roam_mon should only be called from Rust"),
-                (0x0a, 0x64a0) => panic!("validate_temp_wild_mon_species should only be called from Rust"),
-                (0x0a, 0x64ab) => crate::game::engine::overworld::wildmons::random_unseen_wild_mon(self),
-                (0x0a, 0x651f) => crate::game::engine::overworld::wildmons::random_phone_wild_mon(self),
-
-                (0x0b, 0x49e2) => crate::game::engine::items::tmhm::tm_hm_display_pocket_items(self),
-                (0x0b, 0x4b0c) => crate::game::engine::items::tmhm::consume_tm(self),
-
-                (0x0e, 0x5771) => crate::game::engine::battle::read_trainer_party::read_trainer_party(self),
-                (0x0e, 0x594c) => crate::game::engine::battle::read_trainer_party::get_trainer_name(self),
-
-                (0x0f, 0x5618) => crate::game::engine::battle::core::is_the_player_mon_types_effective_against_ot_mon(self),
-
-                (0x10, 0x61e6) => crate::game::engine::pokemon::evolve::evolve_after_battle(self),
-                (0x10, 0x61f5) => panic!("evolve_after_battle_master_loop should only be called from Rust"),
-                (0x10, 0x6454) => panic!("cancel_evolution should only be called from Rust"),
-                (0x10, 0x6461) => panic!("is_mon_holding_everstone should only be called from Rust"),
-                (0x10, 0x6487) => crate::game::engine::pokemon::evolve::learn_level_moves(self),
-                (0x10, 0x64e1) => crate::game::engine::pokemon::evolve::fill_moves(self),
-                (0x10, 0x656e) => panic!("shift_moves should only be called from Rust"),
-                (0x10, 0x6581) => crate::game::engine::pokemon::evolve::get_pre_evolution(self),
-
-                (0x12, 0x5cdc) => crate::game::engine::menus::main_menu::main_menu(self),
-                (0x12, 0x5ed0) => panic!("clear_tilemap_etc should only be called from Rust"),
-                (0x12, 0x5da4) => panic!("main_menu_get_which_menu should only be called from Rust"),
-                (0x12, 0x5de4) => panic!("main_menu_joypad_loop should only be called from Rust"),
-                (0x12, 0x5e09) => panic!("main_menu_print_current_time_and_day should only be called from Rust"),
-
-                (0x14, 0x422f) => crate::game::engine::pokemon::party_menu::place_party_mon_evo_stone_compatibility(self),
-                (0x14, 0x493a) => crate::game::engine::pokemon::types::print_move_type(self),
-                (0x14, 0x5322) => crate::game::engine::pokemon::move_mon_wo_mail::insert_pokemon_into_box(self),
-
-                (0x2e, 0x4762) => crate::game::engine::pokegear::radio::oaks_pkmn_talk_4(self),
-
-                (0x38, 0x76f9) => crate::game::engine::pokemon::bills_pc::bills_pc_change_box_submenu(self),
-
-                (0x3a, 0x4000) => crate::game::audio::engine::init_sound(self),
-                (0x3a, 0x4b30) => crate::game::audio::engine::play_music(self),
-                (0x3a, 0x4b79) => crate::game::audio::engine::play_cry(self),
-                (0x3a, 0x4c04) => crate::game::audio::engine::play_sfx(self),
+                (_, 0x0100) => self.hook(crate::game::home::init::start),
+                (_, 0x017d) => self.rust_only("init"),
+                (_, 0x0245) => self.rust_only("clear_vram"),
+                (_, 0x025a) => self.rust_only("clear_wram"),
+                (_, 0x0270) => self.rust_only("clears_scratch"),
+                (_, 0x3dfe) => self.hook(crate::game::home::audio::terminate_exp_bar_sound),
+
+                (0x03, 0x5b3f) => self.hook(crate::game::engine::pokemon::move_mon::send_get_mon_into_from_box),
+                (0x03, 0x5cb6) => self.hook(crate::game::engine::pokemon::move_mon::restore_pp_of_deposited_pokemon),
+                (0x03, 0x5e6e) => self.hook(crate::game::engine::pokemon::move_mon::send_mon_into_box),
+                (0x03, 0x5f47) => self.rust_only("shift_box_mon"),
+                (0x03, 0x6039) => self.hook(crate::game::engine::pokemon::move_mon::remove_mon_from_party_or_box),
+                (0x03, 0x68a2) => self.hook(crate::game::engine::items::item_effects::poke_ball_effect),
+                (0x03, 0x6c29) => self.rust_only("ultra_ball_multiplier"),
+                (0x03, 0x6c2f) => self.rust_only("great_ball_multiplier"),
+                (0x03, 0x6ccc) => self.rust_only("lure_ball_multiplier"),
+                (0x03, 0x6cdd) => self.rust_only("moon_ball_multiplier"),
+                (0x03, 0x6d68) => self.rust_only("fast_ball_multiplier"),
+                (0x03, 0x6d8c) => self.rust_only("level_ball_multiplier"),
+                (0x03, 0x6dfa) => self.rust_only("return_to_battle_use_ball"),
+
+                (0x05, 0x4a83) => self.hook(crate::game::engine::menus::save::change_box_save_game),
+                (0x05, 0x4b5f) => self.hook(crate::game::engine::menus::save::add_hall_of_fame_entry),
+                (0x05, 0x4b89) => self.hook(crate::game::engine::menus::save::ask_overwrite_save_file),
+                (0x05, 0x4c10) => self.hook(crate::game::engine::menus::save::save_game_data),
+                (0x05, 0x4e0c) => self.hook(crate::game::engine::menus::save::save_box),
+                (0x05, 0x4f1c) => self.hook(crate::game::engine::menus::save::try_load_save_data),
+                (0x05, 0x4f84) => self.rust_only("check_primary_save_file"),
+                (0x05, 0x5021) => self.hook(crate::game::engine::menus::save::load_box),
+                (0x05, 0x50d8) => self.rust_only("get_box_address"),
+                (0x05, 0x50f9) => self.rust_only("save_box_address"),
+                (0x05, 0x517d) => self.rust_only("load_box_address"),
+                (0x05, 0x5a45) => self.hook(crate::game::engine::items::mart::open_mart_dialog),
+                (0x05, 0x5a61) => self.rust_only("mart_dialog"),
+                (0x05, 0x5a6e) => self.rust_only("herb_shop"),
+                (0x05, 0x5a84) => self.rust_only("bargain_shop"),
+                (0x05, 0x5aae) => self.rust_only("pharmacist"),
+                (0x05, 0x5ac4) => self.rust_only("rooftop_sale"),
+                (0x05, 0x5b10) => self.rust_only("load_mart_pointer"),
+                (0x05, 0x5b31) => self.rust_only("get_mart"),
+                (0x05, 0x5b47) => self.rust_only("standard_mart"),
+                (0x05, 0x5bbb) => self.rust_only("far_read_mart"),
+                (0x05, 0x5c25) => self.rust_only("read_mart"),
+                (0x05, 0x5dbc) => self.hook(crate::game::engine::items::mart::bargain_shop_ask_purchase_quantity_get_price),
+                (0x05, 0x5df9) => self.hook(crate::game::engine::items::mart::rooftop_sale_ask_purchase_quantity_get_sale_price),
+                (0x05, 0x70e4) => self.hook(crate::game::engine::pokemon::breeding::get_egg_move),
+                (0x05, 0x720b) => self.rust_only("get_breedmon_move_pointer"),
+
+                (0x0a, 0x5ce8) => self.hook(crate::game::engine::link::link::set_bits_for_link_trade_request),
+                (0x0a, 0x5d11) => self.hook(crate::game::engine::link::link::wait_for_linked_friend),
+                (0x0a, 0x5ff8) => self.hook(crate::game::engine::overworld::wildmons::load_wild_mon_data),
+                (0x0a, 0x601f) => self.hook(crate::game::engine::overworld::wildmons::find_nest),
+                (0x0a, 0x614f) => self.hook(crate::game::engine::overworld::wildmons::choose_wild_encounter),
+                (0x0a, 0x6200) => self.rust_only("load_wild_mon_data_pointer"),
+                (0x0a, 0x6205) => self.rust_only("grass_wildmon_lookup"),
+                (0x0a, 0x621d) => self.rust_only("water_wildmon_lookup"),
+                (0x0a, 0x6235) => self.rust_only("johto_wildmon_check"),
+                (0x0a, 0x623d) => self.rust_only("swarm_wildmon_check"),
+                (0x0a, 0x627a) => self.rust_only("normal_wildmon_ok"),
+                (0x0a, 0x627f) => self.rust_only("copy_curr_map_de"),
+                (0x0a, 0x6288) => self.rust_only("look_up_wildmons_for_map_de"),
+                (0x0a, 0x62ce) => self.rust_only("check_encounter_roam_mon"),
+                (0x0a, 0x64a0) => self.rust_only("validate_temp_wild_mon_species"),
+                (0x0a, 0x64ab) => self.hook(crate::game::engine::overworld::wildmons::random_unseen_wild_mon),
+                (0x0a, 0x651f) => self.hook(crate::game::engine::overworld::wildmons::random_phone_wild_mon),
+
+                (0x0b, 0x49e2) => self.hook(crate::game::engine::items::tmhm::tm_hm_display_pocket_items),
+                (0x0b, 0x4b0c) => self.hook(crate::game::engine::items::tmhm::consume_tm),
+
+                (0x0e, 0x5771) => self.hook(crate::game::engine::battle::read_trainer_party::read_trainer_party),
+                (0x0e, 0x594c) => self.hook(crate::game::engine::battle::read_trainer_party::get_trainer_name),
+
+                (0x0f, 0x5618) => self.hook(crate::game::engine::battle::core::is_the_player_mon_types_effective_against_ot_mon),
+
+                (0x10, 0x61e6) => self.hook(crate::game::engine::pokemon::evolve::evolve_after_battle),
+                (0x10, 0x61f5) => self.rust_only("evolve_after_battle_master_loop"),
+                (0x10, 0x6454) => self.rust_only("cancel_evolution"),
+                (0x10, 0x6461) => self.rust_only("is_mon_holding_everstone"),
+                (0x10, 0x6487) => self.hook(crate::game::engine::pokemon::evolve::learn_level_moves),
+                (0x10, 0x64e1) => self.hook(crate::game::engine::pokemon::evolve::fill_moves),
+                (0x10, 0x656e) => self.rust_only("shift_moves"),
+                (0x10, 0x6581) => self.hook(crate::game::engine::pokemon::evolve::get_pre_evolution),
+
+                (0x12, 0x5cdc) => self.hook(crate::game::engine::menus::main_menu::main_menu),
+                (0x12, 0x5ed0) => self.rust_only("clear_tilemap_etc"),
+                (0x12, 0x5da4) => self.rust_only("main_menu_get_which_menu"),
+                (0x12, 0x5de4) => self.rust_only("main_menu_joypad_loop"),
+                (0x12, 0x5e09) => self.rust_only("main_menu_print_current_time_and_day"),
+
+                (0x14, 0x422f) => self.hook(crate::game::engine::pokemon::party_menu::place_party_mon_evo_stone_compatibility),
+                (0x14, 0x493a) => self.hook(crate::game::engine::pokemon::types::print_move_type),
+                (0x14, 0x5322) => self.hook(crate::game::engine::pokemon::move_mon_wo_mail::insert_pokemon_into_box),
+
+                (0x2e, 0x4762) => self.hook(crate::game::engine::pokegear::radio::oaks_pkmn_talk_4),
+
+                (0x38, 0x76f9) => self.hook(crate::game::engine::pokemon::bills_pc::bills_pc_change_box_submenu),
+
+                (0x3a, 0x4000) => self.hook(crate::game::audio::engine::init_sound),
+                (0x3a, 0x4b30) => self.hook(crate::game::audio::engine::play_music),
+                (0x3a, 0x4b79) => self.hook(crate::game::audio::engine::play_cry),
+                (0x3a, 0x4c04) => self.hook(crate::game::audio::engine::play_sfx),
 
                 _ => {
                     let ticks = if self.halted { 4 } else { self.step() * 4 };
@@ -236,6 +291,11 @@ impl<'a> Cpu<'a> {
         self.mmu.borrow_wram_mut()
     }
 
+    pub fn set_audio_mode(&mut self, mode: AudioMode) {
+        log::info!("Using {mode:?} audio");
+        self.mmu.set_audio_mode(mode);
+    }
+
     pub fn play_sfx<T, TSource>(&mut self, sfx: T)
     where
         T: Sfx<TSource>,
@@ -245,6 +305,46 @@ impl<'a> Cpu<'a> {
         self.mmu.sound2.play_sfx(sfx)
     }
 
+    pub fn play_cry<T, TSource>(&mut self, cry: T)
+    where
+        T: Sfx<TSource>,
+        TSource: rodio::Source + Send + 'static,
+        f32: cpal::FromSample<TSource::Item>,
+    {
+        self.mmu.sound2.play_cry(cry)
+    }
+
+    pub fn set_music_transition(&mut self, transition: TransitionSettings) {
+        self.mmu.sound2.set_transition(transition);
+    }
+
+    /// Lifts the limit of ten sprites per line, which is kept by default for accuracy.
+    pub fn set_unlimited_sprites(&mut self, unlimited: bool) {
+        self.mmu.gpu.set_unlimited_sprites(unlimited);
+    }
+
+    /// Draws the replacement graphics from `pack` into an upscaled copy of the screen, which the
+    /// frame buffer must have room for after the normal screen.
+    pub fn set_texture_pack(&mut self, pack: TexturePack) {
+        self.mmu.gpu.set_texture_pack(pack);
+    }
+
+    /// Follows the layer toggles and dump requests of `debug_view`.
+    pub fn set_debug_view(&mut self, debug_view: Arc<DebugView>) {
+        self.mmu.gpu.set_debug_view(debug_view);
+    }
+
+    /// Uses the colour profile in `color_correction`, following any changes to it.
+    pub fn set_color_correction(&mut self, color_correction: Arc<ColorCorrection>) {
+        self.mmu.gpu.set_color_correction(color_correction);
+    }
+
+    /// Applies the volume settings of `mixer` to the game audio, must be called before
+    /// `set_audio_mode`.
+    pub fn set_mixer(&mut self, mixer: Arc<Mixer>) {
+        self.mmu.sound2.set_mixer(mixer);
+    }
+
     fn fetch_byte(&mut self) -> u8 {
         let b = self.mmu.rb(self.pc);
         self.pc = self.pc.wrapping_add(1);
@@ -0,0 +1,192 @@
+use std::{
+    collections::VecDeque,
+    fmt::Write as _,
+    fs,
+    io::Result,
+    path::PathBuf,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+use crate::{error::Error, save_state::SaveState, saves};
+
+const CALLS_LEN: usize = 64;
+const LOG_LINES_LEN: usize = 200;
+
+static LOG_LINES: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// Prints log messages like `env_logger`, and keeps the most recent ones for crash reports.
+///
+/// Messages at `info` and above are kept even when `RUST_LOG` doesn't print them.
+struct Logger {
+    inner: env_logger::Logger,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= LevelFilter::Info || self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if let Ok(mut lines) = LOG_LINES.lock() {
+            if lines.len() == LOG_LINES_LEN {
+                lines.pop_front();
+            }
+
+            lines.push_back(format!(
+                "{} {}: {}",
+                record.level(),
+                record.target(),
+                record.args()
+            ));
+        }
+
+        if self.inner.matches(record) {
+            self.inner.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Sets up logging from `RUST_LOG`, like `env_logger::init`, and starts keeping log lines for
+/// crash reports.
+pub fn init_logger() {
+    let inner = env_logger::Builder::from_default_env().build();
+    let max_level = inner.filter().max(LevelFilter::Info);
+
+    if log::set_logger(Box::leak(Box::new(Logger { inner }))).is_ok() {
+        log::set_max_level(max_level);
+    }
+}
+
+/// Keeps track of the most recent calls made through `Cpu::call`.
+pub struct CallHistory {
+    calls: VecDeque<(usize, u16)>,
+}
+
+impl CallHistory {
+    pub fn new() -> CallHistory {
+        CallHistory {
+            calls: VecDeque::with_capacity(CALLS_LEN),
+        }
+    }
+
+    pub fn record(&mut self, bank: usize, pc: u16) {
+        if self.calls.len() == CALLS_LEN {
+            self.calls.pop_front();
+        }
+
+        self.calls.push_back((bank, pc));
+    }
+
+    fn lines(&self) -> impl Iterator<Item = String> + '_ {
+        self.calls
+            .iter()
+            .map(|(bank, pc)| format!("call {bank:02x}:{pc:04x}"))
+    }
+}
+
+/// Raw copies of the emulated memory at the time of the crash.
+pub struct Snapshot {
+    pub wram: Vec<u8>,
+    pub vram: Vec<u8>,
+    pub oam: Vec<u8>,
+    pub hram: Vec<u8>,
+    pub rombank: usize,
+    pub wrambank: usize,
+    pub vrambank: usize,
+}
+
+pub struct CrashReport {
+    error: String,
+    log_lines: Vec<String>,
+    calls: Vec<String>,
+    registers: String,
+    snapshot: Snapshot,
+    save: SaveState,
+}
+
+impl CrashReport {
+    pub(crate) fn new(
+        error: &Error,
+        calls: &CallHistory,
+        registers: String,
+        snapshot: Snapshot,
+        save: SaveState,
+    ) -> CrashReport {
+        CrashReport {
+            error: error.to_string(),
+            log_lines: LOG_LINES
+                .lock()
+                .map(|lines| lines.iter().cloned().collect())
+                .unwrap_or_default(),
+            calls: calls.lines().collect(),
+            registers,
+            snapshot,
+            save,
+        }
+    }
+
+    pub fn error(&self) -> &str {
+        &self.error
+    }
+
+    /// Writes the report to a new directory in the crash reports folder, and returns its path.
+    pub fn write(&self) -> Result<PathBuf> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        let dir = saves::get_crash_reports_dir().join(format!("crash-{timestamp}"));
+        fs::create_dir_all(&dir)?;
+
+        fs::write(dir.join("report.txt"), self.summary())?;
+        fs::write(dir.join("wram.bin"), &self.snapshot.wram)?;
+        fs::write(dir.join("vram.bin"), &self.snapshot.vram)?;
+        fs::write(dir.join("oam.bin"), &self.snapshot.oam)?;
+        fs::write(dir.join("hram.bin"), &self.snapshot.hram)?;
+        self.save.write_to_file(&dir.join("save.sav"))?;
+
+        Ok(dir)
+    }
+
+    fn summary(&self) -> String {
+        let mut out = String::new();
+
+        let _ = writeln!(out, "Rustic Crystal {}", env!("CARGO_PKG_VERSION"));
+        let _ = writeln!(out);
+        let _ = writeln!(out, "Error: {}", self.error);
+        let _ = writeln!(out);
+        let _ = writeln!(out, "{}", self.registers);
+        let _ = writeln!(
+            out,
+            "ROM bank={:02x} WRAM bank={} VRAM bank={}",
+            self.snapshot.rombank, self.snapshot.wrambank, self.snapshot.vrambank
+        );
+        let _ = writeln!(out);
+        let _ = writeln!(
+            out,
+            "Last {} log lines (oldest first):",
+            self.log_lines.len()
+        );
+
+        for line in &self.log_lines {
+            let _ = writeln!(out, "  {line}");
+        }
+
+        let _ = writeln!(out);
+        let _ = writeln!(out, "Last {} calls (oldest first):", self.calls.len());
+
+        for line in &self.calls {
+            let _ = writeln!(out, "  {line}");
+        }
+
+        out
+    }
+}
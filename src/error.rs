@@ -0,0 +1,98 @@
+use std::{any::Any, fmt, io, path::PathBuf};
+
+/// An unrecoverable error that stopped the emulator.
+#[derive(Debug)]
+pub enum Error {
+    /// A routine that has been reimplemented in Rust was reached from game code.
+    RustOnlyRoutine {
+        name: &'static str,
+        bank: usize,
+        pc: u16,
+    },
+    IllegalHdmaSource(u16),
+    UnknownTrainer(u8, u8),
+    UnknownMartType(u8),
+    Save {
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// The frontend stopped listening for frames, this is how a normal shutdown looks.
+    ScreenDisconnected,
+    /// Any other panic, carrying its message.
+    Panic(String),
+}
+
+impl Error {
+    pub(crate) fn from_panic(payload: Box<dyn Any + Send>) -> Error {
+        let payload = match payload.downcast::<Error>() {
+            Ok(error) => return *error,
+            Err(payload) => payload,
+        };
+
+        if let Some(message) = payload.downcast_ref::<&'static str>() {
+            return Error::Panic(message.to_string());
+        }
+
+        match payload.downcast::<String>() {
+            Ok(message) => Error::Panic(*message),
+            Err(_) => Error::Panic("Unknown panic payload".to_owned()),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::RustOnlyRoutine { name, bank, pc } => {
+                write!(
+                    f,
+                    "{name} ({bank:02x}:{pc:04x}) should only be called from Rust"
+                )
+            }
+            Error::IllegalHdmaSource(src) => {
+                write!(f, "HDMA transfer with illegal start address {src:04X}")
+            }
+            Error::UnknownTrainer(x, y) => write!(f, "No party defined for trainer {x}:{y}"),
+            Error::UnknownMartType(n) => write!(f, "Invalid mart type: {n}"),
+            Error::Save { path, source } => {
+                write!(f, "Failed to access save {}: {source}", path.display())
+            }
+            Error::ScreenDisconnected => write!(f, "Screen disconnected"),
+            Error::Panic(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Save { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Stops the emulator with `error`, which is returned from `Cpu::run`.
+///
+/// Game code is implemented as plain functions that call each other through the CPU, so this
+/// unwinds the stack instead of threading a `Result` through every routine.
+pub fn raise(error: Error) -> ! {
+    std::panic::resume_unwind(Box::new(error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_panic() {
+        let payload = std::panic::catch_unwind(|| raise(Error::UnknownMartType(9))).unwrap_err();
+        assert!(matches!(
+            Error::from_panic(payload),
+            Error::UnknownMartType(9)
+        ));
+
+        let payload = std::panic::catch_unwind(|| panic!("Oh no {}", 1)).unwrap_err();
+        assert!(matches!(Error::from_panic(payload), Error::Panic(m) if m == "Oh no 1"));
+    }
+}
@@ -0,0 +1,319 @@
+//! CPU-side post-processing of finished frames, before they are shown or saved.
+//!
+//! Frames are RGB with 3 bytes per pixel, row by row, like the ones produced by the GPU.
+
+use serde::{Deserialize, Serialize};
+
+type Rgb = [u8; 3];
+
+/// How a frame is scaled up before it's shown.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Filter {
+    #[default]
+    None,
+    Scale2x,
+    Scale3x,
+    /// Scale2x that compares colours by how similar they look, and blends the edges it finds,
+    /// in the spirit of xBR.
+    Smooth2x,
+    /// Every pixel is drawn as three subpixel columns with a dark gap below, like on an LCD.
+    LcdGrid,
+}
+
+impl Filter {
+    const ALL: [Filter; 5] = [
+        Filter::None,
+        Filter::Scale2x,
+        Filter::Scale3x,
+        Filter::Smooth2x,
+        Filter::LcdGrid,
+    ];
+
+    pub fn scale(self) -> usize {
+        match self {
+            Filter::None => 1,
+            Filter::Scale2x | Filter::Smooth2x => 2,
+            Filter::Scale3x | Filter::LcdGrid => 3,
+        }
+    }
+
+    /// The next filter, for cycling through them with a hotkey.
+    pub fn next(self) -> Filter {
+        let index = Filter::ALL.iter().position(|f| *f == self).unwrap_or(0);
+        Filter::ALL[(index + 1) % Filter::ALL.len()]
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FilterSettings {
+    pub filter: Filter,
+    /// Mixes in the previous frame, like the slow LCD of the Game Boy Color.
+    pub frame_blending: bool,
+}
+
+/// A filtered frame.
+pub struct Image<'a> {
+    pub data: &'a [u8],
+    pub width: usize,
+    pub height: usize,
+}
+
+/// Applies the filters, keeping the buffers that they need between frames.
+pub struct FilterChain {
+    pub settings: FilterSettings,
+    blended: Vec<u8>,
+    output: Vec<u8>,
+}
+
+impl FilterChain {
+    pub fn new(settings: FilterSettings) -> FilterChain {
+        FilterChain {
+            settings,
+            blended: Vec::new(),
+            output: Vec::new(),
+        }
+    }
+
+    /// Filters `frame`, blending it with `previous`, the frame that was emulated before it, if
+    /// frame blending is on.
+    pub fn apply<'a>(
+        &'a mut self,
+        frame: &'a [u8],
+        previous: &[u8],
+        width: usize,
+        height: usize,
+    ) -> Image<'a> {
+        let frame = if self.settings.frame_blending {
+            self.blended.clear();
+            self.blended.extend(
+                frame
+                    .iter()
+                    .zip(previous)
+                    .map(|(&a, &b)| ((a as u16 + b as u16) / 2) as u8),
+            );
+
+            &self.blended[..]
+        } else {
+            frame
+        };
+
+        let filter = self.settings.filter;
+
+        if filter == Filter::None {
+            return Image {
+                data: frame,
+                width,
+                height,
+            };
+        }
+
+        let scale = filter.scale();
+        self.output.resize(width * scale * height * scale * 3, 0);
+
+        let src = Source {
+            data: frame,
+            width,
+            height,
+        };
+
+        let output = &mut self.output[..];
+
+        for y in 0..height {
+            for x in 0..width {
+                let mut put = |block: &[Rgb]| put_block(output, width * scale, scale, x, y, block);
+
+                match filter {
+                    Filter::None => unreachable!(),
+                    Filter::Scale2x => put(&scale2x(&src.neighbours(x, y))),
+                    Filter::Scale3x => put(&scale3x(&src.neighbours(x, y))),
+                    Filter::Smooth2x => put(&smooth2x(&src.neighbours(x, y))),
+                    Filter::LcdGrid => put(&lcd_grid(src.pixel(x, y))),
+                }
+            }
+        }
+
+        Image {
+            data: &self.output,
+            width: width * scale,
+            height: height * scale,
+        }
+    }
+}
+
+/// Writes the `scale`×`scale` block, row by row, for the source pixel at `x`, `y`.
+fn put_block(output: &mut [u8], out_width: usize, scale: usize, x: usize, y: usize, block: &[Rgb]) {
+    for (i, rgb) in block.iter().enumerate() {
+        let ox = x * scale + i % scale;
+        let oy = y * scale + i / scale;
+        let idx = (oy * out_width + ox) * 3;
+        output[idx..idx + 3].copy_from_slice(rgb);
+    }
+}
+
+struct Source<'a> {
+    data: &'a [u8],
+    width: usize,
+    height: usize,
+}
+
+impl Source<'_> {
+    fn pixel(&self, x: usize, y: usize) -> Rgb {
+        let idx = (y * self.width + x) * 3;
+        [self.data[idx], self.data[idx + 1], self.data[idx + 2]]
+    }
+
+    /// The 3×3 block around a pixel, clamped at the edges, row by row.
+    fn neighbours(&self, x: usize, y: usize) -> [Rgb; 9] {
+        let xs = [x.saturating_sub(1), x, usize::min(x + 1, self.width - 1)];
+        let ys = [y.saturating_sub(1), y, usize::min(y + 1, self.height - 1)];
+
+        std::array::from_fn(|i| self.pixel(xs[i % 3], ys[i / 3]))
+    }
+}
+
+// Neighbours are named like in the Scale2x/Scale3x descriptions:
+//
+//   A B C
+//   D E F
+//   G H I
+
+fn scale2x(n: &[Rgb; 9]) -> [Rgb; 4] {
+    let [_, b, _, d, e, f, _, h, _] = *n;
+
+    if b == h || d == f {
+        return [e; 4];
+    }
+
+    [
+        if d == b { d } else { e },
+        if b == f { f } else { e },
+        if d == h { d } else { e },
+        if h == f { f } else { e },
+    ]
+}
+
+fn scale3x(n: &[Rgb; 9]) -> [Rgb; 9] {
+    let [a, b, c, d, e, f, g, h, i] = *n;
+
+    if b == h || d == f {
+        return [e; 9];
+    }
+
+    [
+        if d == b { d } else { e },
+        if (d == b && e != c) || (b == f && e != a) {
+            b
+        } else {
+            e
+        },
+        if b == f { f } else { e },
+        if (d == b && e != g) || (d == h && e != a) {
+            d
+        } else {
+            e
+        },
+        e,
+        if (b == f && e != i) || (h == f && e != c) {
+            f
+        } else {
+            e
+        },
+        if d == h { d } else { e },
+        if (d == h && e != i) || (h == f && e != g) {
+            h
+        } else {
+            e
+        },
+        if h == f { f } else { e },
+    ]
+}
+
+fn smooth2x(n: &[Rgb; 9]) -> [Rgb; 4] {
+    let [_, b, _, d, e, f, _, h, _] = *n;
+
+    let corner = |p: Rgb, q: Rgb, r: Rgb, s: Rgb| {
+        // Blend towards p when the edge p/q runs through this corner
+        if similar(p, q) && !similar(p, r) && !similar(q, s) && !similar(e, p) {
+            mix(e, p)
+        } else {
+            e
+        }
+    };
+
+    [
+        corner(d, b, h, f),
+        corner(b, f, d, h),
+        corner(d, h, b, f),
+        corner(h, f, d, b),
+    ]
+}
+
+fn lcd_grid(p: Rgb) -> [Rgb; 9] {
+    let dim = |v: u8, amount: u16| (v as u16 * amount / 256) as u8;
+    let [r, g, b] = p;
+
+    let columns = [
+        [r, dim(g, 128), dim(b, 128)],
+        [dim(r, 128), g, dim(b, 128)],
+        [dim(r, 128), dim(g, 128), b],
+    ];
+
+    // The bottom row is darker, as the gap between lines
+    std::array::from_fn(|i| match i / 3 {
+        2 => columns[i % 3].map(|v| dim(v, 160)),
+        _ => columns[i % 3],
+    })
+}
+
+fn mix(a: Rgb, b: Rgb) -> Rgb {
+    std::array::from_fn(|i| ((a[i] as u16 + b[i] as u16) / 2) as u8)
+}
+
+/// Compares colours in YUV, where differences in brightness count the most.
+fn similar(a: Rgb, b: Rgb) -> bool {
+    let yuv = |[r, g, b]: Rgb| {
+        let (r, g, b) = (r as i32, g as i32, b as i32);
+        let y = (r * 299 + g * 587 + b * 114) / 1000;
+        (y, (b - y) * 492 / 1000, (r - y) * 877 / 1000)
+    };
+
+    let (y1, u1, v1) = yuv(a);
+    let (y2, u2, v2) = yuv(b);
+
+    (y1 - y2).abs() <= 48 && (u1 - u2).abs() <= 7 && (v1 - v2).abs() <= 6
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scale2x() {
+        const W: Rgb = [255, 255, 255];
+        const K: Rgb = [0, 0, 0];
+
+        // A lone pixel in the corner doesn't change anything
+        let n = [W, K, K, K, K, K, K, K, K];
+        assert_eq!(scale2x(&n), [K; 4]);
+
+        // A diagonal edge, where the top left corner gets rounded
+        let n = [W, W, W, W, K, K, W, K, K];
+        assert_eq!(scale2x(&n), [W, K, K, K]);
+    }
+
+    #[test]
+    fn test_apply_size() {
+        let frame = vec![0; 4 * 2 * 3];
+
+        let mut chain = FilterChain::new(FilterSettings {
+            filter: Filter::LcdGrid,
+            frame_blending: true,
+        });
+
+        let image = chain.apply(&frame, &frame, 4, 2);
+        assert_eq!((image.width, image.height), (12, 6));
+        assert_eq!(image.data.len(), 12 * 6 * 3);
+    }
+}
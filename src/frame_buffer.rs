@@ -0,0 +1,243 @@
+use std::{
+    sync::{mpsc::Receiver, Arc, Condvar, Mutex},
+    time::Duration,
+};
+
+struct Shared {
+    middle: Mutex<Middle>,
+    published: Condvar,
+}
+
+struct Middle {
+    buffer: Vec<u8>,
+    previous: Vec<u8>,
+    fresh: bool,
+    writer_alive: bool,
+    reader_alive: bool,
+}
+
+/// Creates a triple buffered frame buffer where each buffer holds `len` bytes.
+///
+/// The writer always owns one buffer (the one being drawn), the reader owns another (the one
+/// being presented) and the third sits in between. Publishing and taking a frame only swaps
+/// buffers, so no allocation happens once the buffers have been created.
+///
+/// Each frame comes with a copy of the frame that was published before it, so that the reader can
+/// blend consecutive frames even when it skips some.
+pub fn new(len: usize) -> (FrameWriter, FrameReader) {
+    let shared = Arc::new(Shared {
+        middle: Mutex::new(Middle {
+            buffer: vec![0; len],
+            previous: vec![0; len],
+            fresh: false,
+            writer_alive: true,
+            reader_alive: true,
+        }),
+        published: Condvar::new(),
+    });
+
+    let writer = FrameWriter {
+        back: vec![0; len],
+        // Nothing was published before the first frame, which is blended with itself
+        previous: None,
+        shared: shared.clone(),
+        pacer: None,
+    };
+
+    let reader = FrameReader {
+        front: vec![0; len],
+        front_previous: vec![0; len],
+        shared,
+    };
+
+    (writer, reader)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Disconnected;
+
+/// A published frame, along with the one that was published right before it.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Frame<'a> {
+    pub data: &'a [u8],
+    pub previous: &'a [u8],
+}
+
+pub struct FrameWriter {
+    back: Vec<u8>,
+    // A copy of the last published frame
+    previous: Option<Vec<u8>>,
+    shared: Arc<Shared>,
+    pacer: Option<Receiver<()>>,
+}
+
+impl FrameWriter {
+    /// Makes `publish` wait for a tick from `pacer` after each frame, so that emulation runs at
+    /// the pace of the ticks instead of as fast as possible.
+    pub fn with_pacer(mut self, pacer: Receiver<()>) -> Self {
+        self.pacer = Some(pacer);
+        self
+    }
+
+    pub fn buffer(&self) -> &[u8] {
+        &self.back
+    }
+
+    pub fn buffer_mut(&mut self) -> &mut [u8] {
+        &mut self.back
+    }
+
+    /// Hands the completed frame over to the reader, replacing any frame it hasn't taken yet.
+    pub fn publish(&mut self) -> Result<(), Disconnected> {
+        {
+            let mut middle = self.shared.middle.lock().unwrap();
+
+            if !middle.reader_alive {
+                return Err(Disconnected);
+            }
+
+            let previous = self.previous.get_or_insert_with(|| self.back.clone());
+            std::mem::swap(&mut middle.previous, previous);
+            previous.copy_from_slice(&self.back);
+
+            std::mem::swap(&mut middle.buffer, &mut self.back);
+            middle.fresh = true;
+        }
+
+        self.shared.published.notify_one();
+
+        if let Some(ref pacer) = self.pacer {
+            if pacer.recv().is_err() {
+                return Err(Disconnected);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for FrameWriter {
+    fn drop(&mut self) {
+        if let Ok(mut middle) = self.shared.middle.lock() {
+            middle.writer_alive = false;
+        }
+
+        self.shared.published.notify_all();
+    }
+}
+
+pub struct FrameReader {
+    front: Vec<u8>,
+    front_previous: Vec<u8>,
+    shared: Arc<Shared>,
+}
+
+impl FrameReader {
+    /// Returns the most recently published frame, if one was published since the last call.
+    pub fn take_latest(&mut self) -> Result<Option<Frame<'_>>, Disconnected> {
+        let mut middle = self.shared.middle.lock().unwrap();
+        swap_if_fresh(&mut middle, &mut self.front, &mut self.front_previous)
+    }
+
+    /// Like `take_latest`, but waits up to `timeout` for a new frame to be published.
+    pub fn wait_latest(&mut self, timeout: Duration) -> Result<Option<Frame<'_>>, Disconnected> {
+        let middle = self.shared.middle.lock().unwrap();
+
+        let (mut middle, _) = self
+            .shared
+            .published
+            .wait_timeout_while(middle, timeout, |m| !m.fresh && m.writer_alive)
+            .unwrap();
+
+        swap_if_fresh(&mut middle, &mut self.front, &mut self.front_previous)
+    }
+}
+
+fn swap_if_fresh<'a>(
+    middle: &mut Middle,
+    front: &'a mut Vec<u8>,
+    front_previous: &'a mut Vec<u8>,
+) -> Result<Option<Frame<'a>>, Disconnected> {
+    if middle.fresh {
+        std::mem::swap(&mut middle.buffer, front);
+        std::mem::swap(&mut middle.previous, front_previous);
+        middle.fresh = false;
+
+        return Ok(Some(Frame {
+            data: front,
+            previous: front_previous,
+        }));
+    }
+
+    if !middle.writer_alive {
+        return Err(Disconnected);
+    }
+
+    Ok(None)
+}
+
+impl Drop for FrameReader {
+    fn drop(&mut self) {
+        if let Ok(mut middle) = self.shared.middle.lock() {
+            middle.reader_alive = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reader_gets_latest_frame() {
+        let (mut writer, mut reader) = new(2);
+
+        assert_eq!(reader.take_latest(), Ok(None));
+
+        writer.buffer_mut().copy_from_slice(&[1, 1]);
+        writer.publish().unwrap();
+        writer.buffer_mut().copy_from_slice(&[2, 2]);
+        writer.publish().unwrap();
+
+        let frame = reader.take_latest().unwrap().unwrap();
+        assert_eq!(frame.data, [2, 2]);
+        assert_eq!(reader.take_latest(), Ok(None));
+    }
+
+    #[test]
+    fn test_disconnect() {
+        let (mut writer, mut reader) = new(1);
+
+        writer.publish().unwrap();
+        drop(writer);
+
+        // The last frame is still delivered before reporting the disconnect
+        assert_eq!(reader.take_latest().unwrap().unwrap().data, [0]);
+        assert_eq!(reader.take_latest(), Err(Disconnected));
+
+        let (mut writer, reader) = new(1);
+        drop(reader);
+        assert_eq!(writer.publish(), Err(Disconnected));
+    }
+
+    #[test]
+    fn test_previous_frame() {
+        let (mut writer, mut reader) = new(1);
+
+        // The first frame has nothing before it
+        writer.buffer_mut()[0] = 1;
+        writer.publish().unwrap();
+
+        let frame = reader.take_latest().unwrap().unwrap();
+        assert_eq!((frame.data, frame.previous), (&[1][..], &[1][..]));
+
+        // Frames that the reader skips are still the previous frame of the next one
+        for i in 2..=4 {
+            writer.buffer_mut()[0] = i;
+            writer.publish().unwrap();
+        }
+
+        let frame = reader.take_latest().unwrap().unwrap();
+        assert_eq!((frame.data, frame.previous), (&[4][..], &[3][..]));
+    }
+}
@@ -1,12 +1,14 @@
 use crate::{
     cpu::Cpu,
     game::audio::{cry_pointers::CRIES, music::Music, sfx::Sfx},
+    trace,
 };
 
 pub fn init_sound(cpu: &mut Cpu) {
-    log::debug!("init_sound()");
+    log::debug!(target: trace::AUDIO, "init_sound()");
 
     cpu.mmu.sound2.stop_music();
+    cpu.mmu.sound2.end_jingle();
 
     // Run GameBoy code as well so that everything works like normally
     // push hl
@@ -16,10 +18,11 @@ pub fn init_sound(cpu: &mut Cpu) {
 }
 
 pub fn play_music(cpu: &mut Cpu) {
-    log::debug!("play_music(0x{:02x})", cpu.e);
+    log::debug!(target: trace::AUDIO, "play_music(0x{:02x})", cpu.e);
 
-    if let Some(music) = Music::from_id(cpu.e) {
-        cpu.mmu.sound2.start_music(music);
+    match Music::from_id(cpu.e) {
+        Some(music) => cpu.mmu.sound2.start_music(music),
+        None => cpu.mmu.sound2.stop_music(), // MUSIC_NONE
     }
 
     // Run GameBoy code as well so that everything works like normally
@@ -33,10 +36,10 @@ pub fn play_cry(cpu: &mut Cpu) {
     let pitch = cpu.mmu.borrow_wram().cry_pitch();
     let length = cpu.mmu.borrow_wram().cry_length();
 
-    log::debug!("play_cry({}, pitch = {pitch}, length = {length})", cpu.e);
+    log::debug!(target: trace::AUDIO, "play_cry({}, pitch = {pitch}, length = {length})", cpu.e);
 
-    let sfx = CRIES[cpu.e as usize].tweaked(pitch, length);
-    cpu.play_sfx(sfx);
+    let cry = CRIES[cpu.e as usize].tweaked(pitch, length);
+    cpu.play_cry(cry);
 
     // Run GameBoy code as well so that everything works like normally
     // call MusicOff
@@ -46,10 +49,11 @@ pub fn play_cry(cpu: &mut Cpu) {
 }
 
 pub fn play_sfx(cpu: &mut Cpu) {
-    log::debug!("play_sfx(0x{:02x})", cpu.e);
+    log::debug!(target: trace::AUDIO, "play_sfx(0x{:02x})", cpu.e);
 
-    if let Some(sfx) = Sfx::from_sfx_id(cpu.e) {
-        cpu.play_sfx(sfx);
+    match Sfx::from_sfx_id(cpu.e) {
+        Some(sfx) => cpu.play_sfx(sfx),
+        None => cpu.mmu.sound2.start_jingle(Sfx::is_jingle(cpu.e)),
     }
 
     // Run GameBoy code as well so that everything works like normally
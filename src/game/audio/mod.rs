@@ -1,4 +1,7 @@
 pub mod cry_pointers;
 pub mod engine;
 pub mod music;
+pub mod music_loop;
+pub mod music_timing;
 pub mod sfx;
+pub mod soundtrack;
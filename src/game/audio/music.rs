@@ -1,39 +1,6 @@
-use std::fs::File;
-use std::io::BufReader;
-use std::path::PathBuf;
-
-use rodio::decoder::LoopedDecoder;
-use rodio::Decoder;
-
+use crate::game::audio::{music_loop::MusicSource, soundtrack};
 use crate::sound2::{Music as MusicTrait, Sfx as SfxTrait};
 
-type MusicDecoder = LoopedDecoder<BufReader<File>>;
-
-fn resources_root() -> Option<PathBuf> {
-    if std::env::var_os("CARGO").is_some() {
-        return Some(PathBuf::from(std::env::var_os("CARGO_MANIFEST_DIR")?));
-    }
-
-    // TODO: support for other platforms
-    #[cfg(target_os = "macos")]
-    {
-        let bundle = core_foundation::bundle::CFBundle::main_bundle();
-        let bundle_path = bundle.path()?;
-        let resources_path = bundle.resources_path()?;
-        Some(bundle_path.join(resources_path))
-    }
-    #[cfg(not(any(target_os = "macos")))]
-    None
-}
-
-
-fn open_music(name: &str) -> MusicDecoder {
-    let root = resources_root().unwrap_or(std::env::current_dir().unwrap());
-    let file = File::open(root.join("music").join(name)).unwrap();
-
-    Decoder::new_looped(BufReader::new(file)).unwrap()
-}
-
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[repr(u8)]
 pub enum Music {
@@ -253,121 +220,134 @@ impl Music {
             _ => None,
         }
     }
-}
 
-impl SfxTrait<MusicDecoder> for Music {
+    /// Looks up a track by its name in this enum, e.g. `Route12`.
+    pub fn from_name(name: &str) -> Option<Music> {
+        (0..=u8::MAX)
+            .filter_map(Music::from_id)
+            .find(|music| format!("{music:?}") == name)
+    }
+
+    /// The file from the "Pokémon Gold & Pokémon Silver: Super Music Collection" album that is
+    /// played when the soundtrack manifest doesn't say otherwise.
     #[rustfmt::skip]
-    fn open(self) -> MusicDecoder {
+    pub fn album_file(self) -> &'static str {
         match self {
-            Music::Title => open_music("2-02. Title Screen (Crystal).flac"),
-            Music::Route1 => open_music("1-85. Route 1.flac"),
-            Music::Route3 => open_music("1-76. Route 3.flac"),
-            Music::Route12 => open_music("1-72. Route 11.flac"),
-            Music::MagnetTrain => open_music("1-75. Magnet Train.flac"),
-            Music::KantoGymLeaderBattle => open_music("1-79. Battle! (Gym Leader - Kanto).flac"),
-            Music::KantoTrainerBattle => open_music("1-77. Battle! (Trainer - Kanto).flac"),
-            Music::KantoWildBattle => open_music("1-73. Battle! (Wild Pokémon - Kanto).flac"),
-            Music::PokemonCenter => open_music("1-14. Pokémon Center.flac"),
-            Music::HikerEncounter => open_music("1-31. Trainers' Eyes Meet (Hiker).flac"),
-            Music::LassEncounter => open_music("1-27. Trainers' Eyes Meet (Lass).flac"),
-            Music::OfficerEncounter => open_music("1-39. Trainers' Eyes Meet (Officer).flac"),
-            Music::Heal => open_music("1-15. Pokémon Healed.flac"),
-            Music::LavenderTown => open_music("1-74. Lavender Town.flac"),
-            Music::Route2 => open_music("1-84. Viridian Forest.flac"),
-            Music::MtMoon => open_music("1-82. Mt. Moon.flac"),
-            Music::ShowMeAround => open_music("1-13. Hurry Along 2.flac"),
-            Music::GameCorner => open_music("1-42. Game Corner.flac"),
-            Music::Bicycle => open_music("1-43. Cycling.flac"),
-            Music::HallOfFame => open_music("1-90. Hall of Fame.flac"),
-            Music::ViridianCity => open_music("1-78. Pewter City.flac"),
-            Music::CeladonCity => open_music("1-80. Celadon City.flac"),
-            Music::TrainerVictory => open_music("1-21. Victory! (Trainer).flac"),
-            Music::WildVictory => open_music("1-11. Victory! (Wild Pokémon).flac"),
-            Music::GymVictory => open_music("1-35. Victory! (Gym Leader).flac"),
-            Music::MtMoonSquare => open_music("1-83. Mt. Moon Square.flac"),
-            Music::Gym => open_music("1-33. Pokémon Gym.flac"),
-            Music::PalletTown => open_music("1-86. Pallet Town.flac"),
-            Music::PokemonTalk => open_music("1-67. Radio Prof. Oak's Pokémon Talk.flac"),
-            Music::ProfOak => open_music("1-18. Professor Oak.flac"),
-            Music::RivalEncounter => open_music("1-37. Rival.flac"),
-            Music::RivalAfter => open_music("1-65. Rival 2.flac"),
-            Music::Surf => open_music("1-57. Surf.flac"),
-            Music::Evolution => open_music("1-36. Evolution.flac"),
-            Music::NationalPark => open_music("1-45. National Park.flac"),
-            Music::Credits => open_music("1-91. Ending.flac"),
-            Music::AzaleaTown => open_music("1-32. Azalea Town.flac"),
-            Music::CherrygroveCity => open_music("1-12. Cherrygrove City.flac"),
-            Music::KimonoEncounter => open_music("1-50. Trainers' Eyes Meet (Kimono Girl).flac"),
-            Music::UnionCave => open_music("1-28. Union Cave.flac"),
-            Music::JohtoWildBattle => open_music("1-09. Battle! (Wild Pokémon - Johto) (Day).flac"),
-            Music::JohtoTrainerBattle => open_music("1-20. Battle! (Trainer - Johto).flac"),
-            Music::Route30 => open_music("1-16. Route 30.flac"),
-            Music::EcruteakCity => open_music("1-48. Ecruteak City.flac"),
-            Music::VioletCity => open_music("1-23. Violet City.flac"),
-            Music::JohtoGymLeaderBattle => open_music("1-34. Battle! (Gym Leader - Johto).flac"),
-            Music::ChampionBattle => open_music("1-89. Battle! (Champion).flac"),
-            Music::RivalBattle => open_music("1-38. Battle! (Rival).flac"),
-            Music::RocketBattle => open_music("1-64. Battle! (Team Rocket).flac"),
-            Music::ProfElm => open_music("1-07. Elm Pokémon Lab.flac"),
-            Music::DarkCave => open_music("1-22. Dark Cave.flac"),
-            Music::Route29 => open_music("1-08. Route 29.flac"),
-            Music::Route36 => open_music("1-26. Route 32.flac"),
-            Music::SsAqua => open_music("1-70. S.S. Aqua.flac"),
-            Music::YoungsterEncounter => open_music("1-19. Trainers' Eyes Meet (Youngster).flac"),
-            Music::BeautyEncounter => open_music("1-54. Trainers' Eyes Meet (Beauty).flac"),
-            Music::RocketEncounter => open_music("1-63. Trainers' Eyes Meet (Team Rocket).flac"),
-            Music::PokemaniacEncounter => open_music("1-60. Trainers' Eyes Meet (PokéManiac).flac"),
-            Music::SageEncounter => open_music("1-25. Trainers' Eyes Meet (Sage).flac"),
-            Music::NewBarkTown => open_music("1-05. New Bark Town.flac"),
-            Music::GoldenrodCity => open_music("1-40. Goldenrod City.flac"),
-            Music::VermilionCity => open_music("1-71. Vermilion City.flac"),
-            Music::PokemonChannel => open_music("1-41. Radio Pokémon Channel.flac"),
-            Music::PokeFluteChannel => open_music("1-81. Radio Poké Flute.flac"),
-            Music::TinTower => open_music("1-52. Tin Tower.flac"),
-            Music::SproutTower => open_music("1-24. Sprout Tower.flac"),
-            Music::BurnedTower => open_music("1-51. Burned Tower.flac"),
-            Music::Lighthouse => open_music("1-56. Olivine Lighthouse.flac"),
-            Music::LakeOfRage => open_music("1-59. Route 42.flac"),
-            Music::IndigoPlateau => open_music("1-88. Pokémon League.flac"),
-            Music::Route37 => open_music("1-53. Route 38.flac"),
-            Music::RocketHideout => open_music("1-62. Team Rocket HQ.flac"),
-            Music::DragonsDen => open_music("1-68. Dragon's Den.flac"),
-            Music::JohtoWildBattleNight => open_music("1-10. Battle! (Wild Pokémon - Johto) (Night).flac"),
-            Music::RuinsOfAlphRadio => open_music("1-30. Radio Unown.flac"),
-            Music::Capture => open_music("1-17. Pokémon Caught.flac"),
-            Music::Route26 => open_music("1-69. Route 26.flac"),
-            Music::Mom => open_music("1-06. Hurry Along.flac"),
-            Music::VictoryRoad => open_music("1-87. Victory Road.flac"),
-            Music::PokemonLullaby => open_music("1-55. Pokémon Lullaby.flac"),
-            Music::PokemonMarch => open_music("1-44. Radio Pokémon March.flac"),
-            Music::GsOpening => open_music("1-01. Opening Movie .flac"),
-            Music::GsOpening2 => open_music("1-02. Opening Movie 2.flac"),
-            Music::MainMenu => open_music("1-04. An Adventure Begins!.flac"),
-            Music::RuinsOfAlphInterior => open_music("1-29. Ruins of Alph.flac"),
-            Music::RocketOverture => open_music("1-66. Radio Tower Occupied!.flac"),
-            Music::DancingHall => open_music("1-49. Ecruteak Dance Theater.flac"),
-            Music::BugCatchingContestRanking => open_music("1-47. Bug-Catching Contest.flac"),
-            Music::BugCatchingContest => open_music("1-46. Bug-Catching Contest (Preparing).flac"),
-            Music::LakeOfRageRocketRadio => open_music("1-61. Radio Transmission.flac"),
-            Music::Printer => open_music("1-58. Game Boy Printer.flac"),
-            Music::PostCredits => open_music("1-92. The End.flac"),
+            Music::Title => "2-02. Title Screen (Crystal).flac",
+            Music::Route1 => "1-85. Route 1.flac",
+            Music::Route3 => "1-76. Route 3.flac",
+            Music::Route12 => "1-72. Route 11.flac",
+            Music::MagnetTrain => "1-75. Magnet Train.flac",
+            Music::KantoGymLeaderBattle => "1-79. Battle! (Gym Leader - Kanto).flac",
+            Music::KantoTrainerBattle => "1-77. Battle! (Trainer - Kanto).flac",
+            Music::KantoWildBattle => "1-73. Battle! (Wild Pokémon - Kanto).flac",
+            Music::PokemonCenter => "1-14. Pokémon Center.flac",
+            Music::HikerEncounter => "1-31. Trainers' Eyes Meet (Hiker).flac",
+            Music::LassEncounter => "1-27. Trainers' Eyes Meet (Lass).flac",
+            Music::OfficerEncounter => "1-39. Trainers' Eyes Meet (Officer).flac",
+            Music::Heal => "1-15. Pokémon Healed.flac",
+            Music::LavenderTown => "1-74. Lavender Town.flac",
+            Music::Route2 => "1-84. Viridian Forest.flac",
+            Music::MtMoon => "1-82. Mt. Moon.flac",
+            Music::ShowMeAround => "1-13. Hurry Along 2.flac",
+            Music::GameCorner => "1-42. Game Corner.flac",
+            Music::Bicycle => "1-43. Cycling.flac",
+            Music::HallOfFame => "1-90. Hall of Fame.flac",
+            Music::ViridianCity => "1-78. Pewter City.flac",
+            Music::CeladonCity => "1-80. Celadon City.flac",
+            Music::TrainerVictory => "1-21. Victory! (Trainer).flac",
+            Music::WildVictory => "1-11. Victory! (Wild Pokémon).flac",
+            Music::GymVictory => "1-35. Victory! (Gym Leader).flac",
+            Music::MtMoonSquare => "1-83. Mt. Moon Square.flac",
+            Music::Gym => "1-33. Pokémon Gym.flac",
+            Music::PalletTown => "1-86. Pallet Town.flac",
+            Music::PokemonTalk => "1-67. Radio Prof. Oak's Pokémon Talk.flac",
+            Music::ProfOak => "1-18. Professor Oak.flac",
+            Music::RivalEncounter => "1-37. Rival.flac",
+            Music::RivalAfter => "1-65. Rival 2.flac",
+            Music::Surf => "1-57. Surf.flac",
+            Music::Evolution => "1-36. Evolution.flac",
+            Music::NationalPark => "1-45. National Park.flac",
+            Music::Credits => "1-91. Ending.flac",
+            Music::AzaleaTown => "1-32. Azalea Town.flac",
+            Music::CherrygroveCity => "1-12. Cherrygrove City.flac",
+            Music::KimonoEncounter => "1-50. Trainers' Eyes Meet (Kimono Girl).flac",
+            Music::UnionCave => "1-28. Union Cave.flac",
+            Music::JohtoWildBattle => "1-09. Battle! (Wild Pokémon - Johto) (Day).flac",
+            Music::JohtoTrainerBattle => "1-20. Battle! (Trainer - Johto).flac",
+            Music::Route30 => "1-16. Route 30.flac",
+            Music::EcruteakCity => "1-48. Ecruteak City.flac",
+            Music::VioletCity => "1-23. Violet City.flac",
+            Music::JohtoGymLeaderBattle => "1-34. Battle! (Gym Leader - Johto).flac",
+            Music::ChampionBattle => "1-89. Battle! (Champion).flac",
+            Music::RivalBattle => "1-38. Battle! (Rival).flac",
+            Music::RocketBattle => "1-64. Battle! (Team Rocket).flac",
+            Music::ProfElm => "1-07. Elm Pokémon Lab.flac",
+            Music::DarkCave => "1-22. Dark Cave.flac",
+            Music::Route29 => "1-08. Route 29.flac",
+            Music::Route36 => "1-26. Route 32.flac",
+            Music::SsAqua => "1-70. S.S. Aqua.flac",
+            Music::YoungsterEncounter => "1-19. Trainers' Eyes Meet (Youngster).flac",
+            Music::BeautyEncounter => "1-54. Trainers' Eyes Meet (Beauty).flac",
+            Music::RocketEncounter => "1-63. Trainers' Eyes Meet (Team Rocket).flac",
+            Music::PokemaniacEncounter => "1-60. Trainers' Eyes Meet (PokéManiac).flac",
+            Music::SageEncounter => "1-25. Trainers' Eyes Meet (Sage).flac",
+            Music::NewBarkTown => "1-05. New Bark Town.flac",
+            Music::GoldenrodCity => "1-40. Goldenrod City.flac",
+            Music::VermilionCity => "1-71. Vermilion City.flac",
+            Music::PokemonChannel => "1-41. Radio Pokémon Channel.flac",
+            Music::PokeFluteChannel => "1-81. Radio Poké Flute.flac",
+            Music::TinTower => "1-52. Tin Tower.flac",
+            Music::SproutTower => "1-24. Sprout Tower.flac",
+            Music::BurnedTower => "1-51. Burned Tower.flac",
+            Music::Lighthouse => "1-56. Olivine Lighthouse.flac",
+            Music::LakeOfRage => "1-59. Route 42.flac",
+            Music::IndigoPlateau => "1-88. Pokémon League.flac",
+            Music::Route37 => "1-53. Route 38.flac",
+            Music::RocketHideout => "1-62. Team Rocket HQ.flac",
+            Music::DragonsDen => "1-68. Dragon's Den.flac",
+            Music::JohtoWildBattleNight => "1-10. Battle! (Wild Pokémon - Johto) (Night).flac",
+            Music::RuinsOfAlphRadio => "1-30. Radio Unown.flac",
+            Music::Capture => "1-17. Pokémon Caught.flac",
+            Music::Route26 => "1-69. Route 26.flac",
+            Music::Mom => "1-06. Hurry Along.flac",
+            Music::VictoryRoad => "1-87. Victory Road.flac",
+            Music::PokemonLullaby => "1-55. Pokémon Lullaby.flac",
+            Music::PokemonMarch => "1-44. Radio Pokémon March.flac",
+            Music::GsOpening => "1-01. Opening Movie .flac",
+            Music::GsOpening2 => "1-02. Opening Movie 2.flac",
+            Music::MainMenu => "1-04. An Adventure Begins!.flac",
+            Music::RuinsOfAlphInterior => "1-29. Ruins of Alph.flac",
+            Music::RocketOverture => "1-66. Radio Tower Occupied!.flac",
+            Music::DancingHall => "1-49. Ecruteak Dance Theater.flac",
+            Music::BugCatchingContestRanking => "1-47. Bug-Catching Contest.flac",
+            Music::BugCatchingContest => "1-46. Bug-Catching Contest (Preparing).flac",
+            Music::LakeOfRageRocketRadio => "1-61. Radio Transmission.flac",
+            Music::Printer => "1-58. Game Boy Printer.flac",
+            Music::PostCredits => "1-92. The End.flac",
 
             // New to Crystal
-            Music::Clair => open_music("2-06. Clair.flac"),
-            Music::MobileAdapterMenu => open_music("2-11. Mobile Adapter Menu (JP).flac"),
-            Music::MobileAdapter => open_music("2-10. Mobile Adapter Screen (JP).flac"),
-            Music::BuenasPassword => open_music("2-03. Radio Buena's Password.flac"),
-            Music::MysticalmanEncounter => open_music("2-04. Eusine.flac"),
-            Music::CrystalOpening => open_music("2-01. Opening Movie (Crystal).flac"),
-            Music::BattleTowerTheme => open_music("2-07. Battle Tower.flac"),
-            Music::SuicuneBattle => open_music("2-05. Battle! (RaikouEnteiSuicune).flac"),
-            Music::BattleTowerLobby => open_music("2-08. Battle Tower Reception Desk.flac"),
-            Music::MobileCenter => open_music("2-09. Pokémon Communication Center (JP).flac"),
+            Music::Clair => "2-06. Clair.flac",
+            Music::MobileAdapterMenu => "2-11. Mobile Adapter Menu (JP).flac",
+            Music::MobileAdapter => "2-10. Mobile Adapter Screen (JP).flac",
+            Music::BuenasPassword => "2-03. Radio Buena's Password.flac",
+            Music::MysticalmanEncounter => "2-04. Eusine.flac",
+            Music::CrystalOpening => "2-01. Opening Movie (Crystal).flac",
+            Music::BattleTowerTheme => "2-07. Battle Tower.flac",
+            Music::SuicuneBattle => "2-05. Battle! (RaikouEnteiSuicune).flac",
+            Music::BattleTowerLobby => "2-08. Battle Tower Reception Desk.flac",
+            Music::MobileCenter => "2-09. Pokémon Communication Center (JP).flac",
         }
     }
 }
 
-impl MusicTrait<MusicDecoder> for Music {
+impl SfxTrait<MusicSource> for Music {
+    fn open(self) -> MusicSource {
+        soundtrack::open(self)
+    }
+}
+
+impl MusicTrait<MusicSource> for Music {
     fn id(&self) -> u32 {
         *self as u32
     }
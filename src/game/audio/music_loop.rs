@@ -0,0 +1,590 @@
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, BufReader, Read},
+    path::Path,
+    time::Duration,
+};
+
+use rodio::{decoder::LoopedDecoder, ChannelCount, Decoder, Sample, SampleRate, Source};
+
+use crate::game::audio::music_timing::{frames_to_samples, LoopFrames};
+
+// Samples quieter than this count as the silence before a recording starts
+const SILENCE: f32 = 1.0 / 256.0;
+// How far a recording's speed may be off from the Game Boy's, e.g. one made on a Super Game Boy,
+// which runs 2.4% faster
+const SPEED_TOLERANCE: f64 = 0.03;
+// How alike the audio right before and after the loop point has to be
+const MIN_CORRELATION: f32 = 0.95;
+// The loop is first looked for in averages of this many samples, and then sample by sample
+const COARSE_BLOCK: usize = 32;
+
+/// Where a track should loop, in samples per channel from the start of the file.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LoopPoints {
+    /// The first sample of the looping part, everything before it is only played once.
+    pub intro_end: u64,
+    /// The sample right after the looping part, everything from it onwards is never played.
+    pub loop_end: u64,
+}
+
+impl LoopPoints {
+    /// Reads loop points from the `LOOPSTART` and `LOOPEND` (or `LOOPLENGTH`) Vorbis comments of a
+    /// FLAC file, which is the convention used by most game music rips.
+    pub fn from_flac_tags(path: &Path) -> io::Result<Option<LoopPoints>> {
+        let mut file = BufReader::new(File::open(path)?);
+
+        let mut magic = [0; 4];
+        file.read_exact(&mut magic)?;
+
+        if &magic != b"fLaC" {
+            return Ok(None);
+        }
+
+        loop {
+            let mut header = [0; 4];
+            file.read_exact(&mut header)?;
+
+            let is_last = header[0] & 0x80 != 0;
+            let block_type = header[0] & 0x7f;
+            let len = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+
+            // VORBIS_COMMENT
+            if block_type == 4 {
+                let mut block = vec![0; len];
+                file.read_exact(&mut block)?;
+                return Ok(parse_vorbis_comment(&block));
+            }
+
+            file.seek_relative(len as i64)?;
+
+            if is_last {
+                return Ok(None);
+            }
+        }
+    }
+}
+
+impl LoopPoints {
+    /// Measures where the recording at `path` loops, given how long the intro and the loop take
+    /// in the game. `None` if the recording doesn't repeat itself around there.
+    pub fn measure(
+        path: &Path,
+        loop_frames: LoopFrames,
+    ) -> Result<Option<LoopPoints>, Box<dyn Error>> {
+        let decoder = Decoder::new(BufReader::new(File::open(path)?))?;
+        let channels = decoder.channels() as usize;
+        let sample_rate = decoder.sample_rate();
+
+        // Enough for the loop to play twice after the intro, with some silence before it
+        let needed = frames_to_samples(loop_frames.intro + 2 * loop_frames.body, sample_rate)
+            as f64
+            * (1.0 + SPEED_TOLERANCE)
+            + 10.0 * sample_rate as f64;
+
+        // Mixed down to mono as it's decoded, as the whole file would take a lot of memory
+        let mut samples = Vec::new();
+        let mut sum = 0.0;
+
+        for (i, sample) in decoder.enumerate() {
+            sum += sample;
+
+            if (i + 1) % channels == 0 {
+                samples.push(sum / channels as f32);
+                sum = 0.0;
+
+                if samples.len() as f64 >= needed {
+                    break;
+                }
+            }
+        }
+
+        Ok(find_loop_points(&samples, sample_rate, loop_frames))
+    }
+}
+
+/// Finds the loop in a mono recording: the audio a quarter into the first loop is compared with
+/// the audio about one loop later, to find exactly how far apart they repeat.
+fn find_loop_points(
+    samples: &[f32],
+    sample_rate: u32,
+    loop_frames: LoopFrames,
+) -> Option<LoopPoints> {
+    let start = samples.iter().position(|sample| sample.abs() > SILENCE)?;
+    let intro = frames_to_samples(loop_frames.intro, sample_rate) as f64;
+    let body = frames_to_samples(loop_frames.body, sample_rate) as f64;
+
+    let min_body = (body * (1.0 - SPEED_TOLERANCE)) as usize;
+    let max_body = (body * (1.0 + SPEED_TOLERANCE)) as usize;
+    let window = (sample_rate as usize).min(min_body / 4);
+
+    // Past the end of the intro, even if the recording is slower than the game
+    let at = start + (intro * (1.0 + SPEED_TOLERANCE) + body / 4.0) as usize;
+
+    if window < COARSE_BLOCK || at + max_body + window + COARSE_BLOCK > samples.len() {
+        return None;
+    }
+
+    let blocks: Vec<f32> = samples
+        .chunks(COARSE_BLOCK)
+        .map(|block| block.iter().sum::<f32>() / block.len() as f32)
+        .collect();
+
+    let coarse = |offset: usize| {
+        let (at, window) = (at / COARSE_BLOCK, window / COARSE_BLOCK);
+        correlation(
+            &blocks[at..at + window],
+            &blocks[at + offset..at + offset + window],
+        )
+    };
+    let (coarse_body, _) = best_offset(min_body / COARSE_BLOCK..=max_body / COARSE_BLOCK, coarse)?;
+
+    let fine = |offset: usize| {
+        correlation(
+            &samples[at..at + window],
+            &samples[at + offset..at + offset + window],
+        )
+    };
+    let around = coarse_body * COARSE_BLOCK;
+    let range = around.saturating_sub(2 * COARSE_BLOCK)..=around + 2 * COARSE_BLOCK;
+    let (body, correlation) = best_offset(range, fine)?;
+
+    if correlation < MIN_CORRELATION {
+        log::warn!("The recording doesn't repeat after its loop ({correlation:.2} alike)");
+        return None;
+    }
+
+    Some(LoopPoints {
+        intro_end: at as u64,
+        loop_end: (at + body) as u64,
+    })
+}
+
+/// The offset in `range` where `correlation` is the highest, and that correlation.
+fn best_offset(
+    range: std::ops::RangeInclusive<usize>,
+    correlation: impl Fn(usize) -> f32,
+) -> Option<(usize, f32)> {
+    range
+        .map(|offset| (offset, correlation(offset)))
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+}
+
+/// How alike `a` and `b` are, from -1 to 1, regardless of how loud they are.
+fn correlation(a: &[f32], b: &[f32]) -> f32 {
+    let (mut ab, mut aa, mut bb) = (0.0f64, 0.0f64, 0.0f64);
+
+    for (&a, &b) in a.iter().zip(b) {
+        ab += a as f64 * b as f64;
+        aa += a as f64 * a as f64;
+        bb += b as f64 * b as f64;
+    }
+
+    if aa == 0.0 || bb == 0.0 {
+        return 0.0;
+    }
+
+    (ab / (aa * bb).sqrt()) as f32
+}
+
+fn parse_vorbis_comment(block: &[u8]) -> Option<LoopPoints> {
+    fn read_u32(data: &[u8], pos: &mut usize) -> Option<usize> {
+        let bytes = data.get(*pos..*pos + 4)?;
+        *pos += 4;
+        Some(u32::from_le_bytes(bytes.try_into().unwrap()) as usize)
+    }
+
+    let mut pos = 0;
+
+    let vendor_len = read_u32(block, &mut pos)?;
+    pos += vendor_len;
+
+    let count = read_u32(block, &mut pos)?;
+
+    let mut start = None;
+    let mut end = None;
+    let mut length = None;
+
+    for _ in 0..count {
+        let len = read_u32(block, &mut pos)?;
+        let comment = std::str::from_utf8(block.get(pos..pos + len)?).ok()?;
+        pos += len;
+
+        if let Some((key, value)) = comment.split_once('=') {
+            let value = value.trim().parse::<u64>().ok();
+
+            match key.to_ascii_uppercase().as_str() {
+                "LOOPSTART" => start = value,
+                "LOOPEND" => end = value,
+                "LOOPLENGTH" => length = value,
+                _ => {}
+            }
+        }
+    }
+
+    let intro_end = start?;
+    let loop_end = end.or_else(|| Some(intro_end + length?))?;
+
+    (loop_end > intro_end).then_some(LoopPoints {
+        intro_end,
+        loop_end,
+    })
+}
+
+/// A music track that plays forever, looping at its loop points if it has any and from the
+/// beginning otherwise.
+pub struct MusicSource {
+    decoder: MusicDecoder,
+    volume: f32,
+}
+
+enum MusicDecoder {
+    Whole(LoopedDecoder<BufReader<File>>),
+    Looping(LoopingDecoder<Decoder<BufReader<File>>>),
+    /// Used when no file could be opened for a track.
+    Silence,
+}
+
+impl MusicSource {
+    /// Opens the track at `path`, looping at `loop_points` if given and from the beginning
+    /// otherwise.
+    pub fn open(
+        path: &Path,
+        loop_points: Option<LoopPoints>,
+        volume: f32,
+    ) -> Result<MusicSource, Box<dyn Error>> {
+        let Some(loop_points) = loop_points else {
+            let decoder = Decoder::new_looped(BufReader::new(File::open(path)?))?;
+
+            return Ok(MusicSource {
+                decoder: MusicDecoder::Whole(decoder),
+                volume,
+            });
+        };
+
+        // Opening the file directly lets the decoder know its length, which it needs to seek
+        let decoder = Decoder::try_from(File::open(path)?)?;
+
+        Ok(MusicSource {
+            decoder: MusicDecoder::Looping(LoopingDecoder::new(decoder, loop_points)),
+            volume,
+        })
+    }
+
+    pub fn silence() -> MusicSource {
+        MusicSource {
+            decoder: MusicDecoder::Silence,
+            volume: 0.0,
+        }
+    }
+}
+
+impl Iterator for MusicSource {
+    type Item = Sample;
+
+    fn next(&mut self) -> Option<Sample> {
+        let sample = match &mut self.decoder {
+            MusicDecoder::Whole(decoder) => decoder.next(),
+            MusicDecoder::Looping(decoder) => decoder.next(),
+            MusicDecoder::Silence => Some(0.0),
+        };
+
+        sample.map(|sample| sample * self.volume)
+    }
+}
+
+impl Source for MusicSource {
+    fn current_span_len(&self) -> Option<usize> {
+        match &self.decoder {
+            MusicDecoder::Whole(decoder) => decoder.current_span_len(),
+            MusicDecoder::Looping(decoder) => decoder.current_span_len(),
+            MusicDecoder::Silence => None,
+        }
+    }
+
+    fn channels(&self) -> ChannelCount {
+        match &self.decoder {
+            MusicDecoder::Whole(decoder) => decoder.channels(),
+            MusicDecoder::Looping(decoder) => decoder.channels(),
+            MusicDecoder::Silence => 2,
+        }
+    }
+
+    fn sample_rate(&self) -> SampleRate {
+        match &self.decoder {
+            MusicDecoder::Whole(decoder) => decoder.sample_rate(),
+            MusicDecoder::Looping(decoder) => decoder.sample_rate(),
+            MusicDecoder::Silence => 44100,
+        }
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// Plays the intro once, and then loops the body sample accurately by seeking the source back to
+/// the start of the loop.
+pub struct LoopingDecoder<S> {
+    source: S,
+    channels: ChannelCount,
+    sample_rate: SampleRate,
+    position: u64,
+    intro_end: u64,
+    loop_end: u64,
+}
+
+impl<S: Source> LoopingDecoder<S> {
+    fn new(source: S, loop_points: LoopPoints) -> LoopingDecoder<S> {
+        let channels = source.channels();
+        let sample_rate = source.sample_rate();
+
+        // Positions are counted in interleaved samples
+        let intro_end = loop_points.intro_end * channels as u64;
+        let loop_end = loop_points.loop_end * channels as u64;
+
+        LoopingDecoder {
+            source,
+            channels,
+            sample_rate,
+            position: 0,
+            intro_end,
+            loop_end,
+        }
+    }
+
+    fn seek_to_loop_start(&mut self) -> Option<()> {
+        // Rounded up, as seeking rounds down to the sample before a time
+        let sample = self.intro_end / self.channels as u64;
+        let nanos = (sample as u128 * 1_000_000_000).div_ceil(self.sample_rate as u128);
+
+        match self.source.try_seek(Duration::from_nanos(nanos as u64)) {
+            Ok(()) => {
+                self.position = self.intro_end;
+                Some(())
+            }
+            Err(error) => {
+                log::error!("Failed to seek to the loop start: {error}");
+                None
+            }
+        }
+    }
+}
+
+impl<S: Source> Iterator for LoopingDecoder<S> {
+    type Item = Sample;
+
+    fn next(&mut self) -> Option<Sample> {
+        if self.position >= self.loop_end {
+            self.seek_to_loop_start()?;
+        }
+
+        match self.source.next() {
+            Some(sample) => {
+                self.position += 1;
+                Some(sample)
+            }
+            // The loop end was past the end of the file, so loop from there instead
+            None if self.position > self.intro_end => {
+                self.loop_end = self.position;
+                self.seek_to_loop_start()?;
+
+                let sample = self.source.next()?;
+                self.position += 1;
+                Some(sample)
+            }
+            None => None,
+        }
+    }
+}
+
+impl<S: Source> Source for LoopingDecoder<S> {
+    fn current_span_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> ChannelCount {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> SampleRate {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rodio::source::SeekError;
+
+    use super::*;
+
+    fn comment_block(comments: &[&str]) -> Vec<u8> {
+        let mut block = Vec::new();
+        block.extend_from_slice(&4u32.to_le_bytes());
+        block.extend_from_slice(b"test");
+        block.extend_from_slice(&(comments.len() as u32).to_le_bytes());
+
+        for comment in comments {
+            block.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+            block.extend_from_slice(comment.as_bytes());
+        }
+
+        block
+    }
+
+    #[test]
+    fn test_parse_vorbis_comment() {
+        let block = comment_block(&["TITLE=Route 29", "LOOPSTART=1000", "LOOPEND=5000"]);
+        assert_eq!(
+            parse_vorbis_comment(&block),
+            Some(LoopPoints {
+                intro_end: 1000,
+                loop_end: 5000
+            })
+        );
+
+        let block = comment_block(&["loopstart=10", "LoopLength=20"]);
+        assert_eq!(
+            parse_vorbis_comment(&block),
+            Some(LoopPoints {
+                intro_end: 10,
+                loop_end: 30
+            })
+        );
+
+        let block = comment_block(&["TITLE=Pokémon Center"]);
+        assert_eq!(parse_vorbis_comment(&block), None);
+    }
+
+    /// A stereo source whose samples count up from 0, and which seeks like the FLAC decoder: to
+    /// the sample at or before the time.
+    struct Counter {
+        position: u64,
+        len: u64,
+    }
+
+    impl Iterator for Counter {
+        type Item = Sample;
+
+        fn next(&mut self) -> Option<Sample> {
+            (self.position < self.len).then(|| {
+                self.position += 1;
+                (self.position - 1) as Sample
+            })
+        }
+    }
+
+    impl Source for Counter {
+        fn current_span_len(&self) -> Option<usize> {
+            None
+        }
+
+        fn channels(&self) -> ChannelCount {
+            2
+        }
+
+        fn sample_rate(&self) -> SampleRate {
+            44100
+        }
+
+        fn total_duration(&self) -> Option<Duration> {
+            None
+        }
+
+        fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+            self.position = (pos.as_nanos() * 44100 / 1_000_000_000) as u64 * 2;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_find_loop_points() {
+        let sample_rate = 8000;
+        let loop_frames = LoopFrames {
+            intro: 60,
+            body: 600,
+        };
+
+        // A recording 1% slower than the game, with silence before it and the loop played twice
+        let intro_len = frames_to_samples(loop_frames.intro, sample_rate) * 101 / 100;
+        let body_len = frames_to_samples(loop_frames.body, sample_rate) * 101 / 100;
+
+        let mut seed = 1u32;
+        let mut noise = |len: u64| -> Vec<f32> {
+            (0..len)
+                .map(|_| {
+                    seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                    (seed >> 16) as f32 / 32768.0 - 1.0
+                })
+                .collect()
+        };
+
+        let intro = noise(intro_len);
+        let body = noise(body_len);
+        let samples: Vec<f32> = std::iter::repeat_n(0.0, 1000)
+            .chain(intro)
+            .chain(body.iter().copied())
+            .chain(body.iter().map(|sample| sample * 0.5))
+            .collect();
+
+        let points = find_loop_points(&samples, sample_rate, loop_frames).unwrap();
+        assert_eq!(points.loop_end - points.intro_end, body_len);
+        assert!(points.intro_end >= 1000 + intro_len);
+
+        // Audio that doesn't repeat has no loop
+        let samples = noise(samples.len() as u64);
+        assert_eq!(find_loop_points(&samples, sample_rate, loop_frames), None);
+    }
+
+    #[test]
+    fn test_looping_decoder() {
+        let intro_end = 100_003;
+        let loop_end = 100_010;
+
+        let source = Counter {
+            position: 0,
+            len: 1_000_000,
+        };
+        let decoder = LoopingDecoder::new(
+            source,
+            LoopPoints {
+                intro_end,
+                loop_end,
+            },
+        );
+
+        let body = (intro_end * 2..loop_end * 2).map(|sample| sample as Sample);
+        let expected: Vec<Sample> = (0..loop_end * 2)
+            .map(|sample| sample as Sample)
+            .chain(body.clone())
+            .chain(body.clone())
+            .chain(body)
+            .collect();
+
+        assert!(decoder.take(expected.len()).eq(expected));
+
+        // A loop end past the end of the file loops at the end of the file
+        let source = Counter {
+            position: 0,
+            len: 20,
+        };
+        let decoder = LoopingDecoder::new(
+            source,
+            LoopPoints {
+                intro_end: 4,
+                loop_end: 50,
+            },
+        );
+
+        let expected = (0..20)
+            .chain(8..20)
+            .chain(8..20)
+            .map(|sample| sample as Sample);
+        assert!(decoder.take(44).eq(expected));
+    }
+}
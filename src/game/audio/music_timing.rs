@@ -0,0 +1,409 @@
+//! How long the intro and the loop of each track take in the game, worked out from its music
+//! data.
+//!
+//! The album tracks were recorded from the game, so their intros and loops are about as long as
+//! the game's sound engine takes to play them. This follows the engine's timing over the music
+//! data in the ROM, without making a sound, until every channel has jumped back to the start of
+//! its loop. The result is only a guide for `music_loop::measure_loop`, which finds the exact
+//! loop in the recording.
+
+use std::collections::HashMap;
+
+use crate::{game::audio::music::Music, rom::ROM};
+
+const AUDIO_BANK: usize = 0x3a;
+const MUSIC: u16 = 0x506e; // Music
+
+// The sound engine is updated once per frame
+const CPU_FREQUENCY: u128 = 4_194_304;
+const CYCLES_PER_FRAME: u128 = 70_224;
+
+// Tracks that haven't looped after ten minutes are treated as not looping
+const MAX_FRAMES: u64 = 10 * 60 * 60;
+// Channels that read this many commands without a note are treated as stuck
+const MAX_COMMANDS: usize = 1000;
+
+/// The length of a track's intro and of the part that loops, in frames.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LoopFrames {
+    pub intro: u64,
+    pub body: u64,
+}
+
+/// Roughly how many samples `frames` take in a recording at `sample_rate`, if it was recorded at
+/// the Game Boy's speed.
+pub fn frames_to_samples(frames: u64, sample_rate: u32) -> u64 {
+    (frames as u128 * CYCLES_PER_FRAME * sample_rate as u128 / CPU_FREQUENCY) as u64
+}
+
+impl Music {
+    /// How long the intro and the loop of this track take in the game, or `None` if it doesn't
+    /// loop.
+    pub fn loop_frames(self) -> Option<LoopFrames> {
+        let entry = MUSIC.wrapping_add(3 * self as u16);
+
+        let bank = read_rom(AUDIO_BANK, entry) as usize;
+        let header = u16::from_le_bytes([
+            read_rom(AUDIO_BANK, entry.wrapping_add(1)),
+            read_rom(AUDIO_BANK, entry.wrapping_add(2)),
+        ]);
+
+        let read = |address: u16| read_rom(bank, address);
+        let count = (read(header) >> 6) + 1;
+
+        let channels: Vec<(u8, u16)> = (0..count as u16)
+            .map(|i| {
+                let at = header.wrapping_add(3 * i);
+                let address =
+                    u16::from_le_bytes([read(at.wrapping_add(1)), read(at.wrapping_add(2))]);
+
+                (read(at) & 0x07, address)
+            })
+            .collect();
+
+        find_loop(read, &channels)
+    }
+}
+
+fn read_rom(bank: usize, address: u16) -> u8 {
+    // Reads past the end of the ROM end the channel, like `sound_ret`
+    ROM.get((bank * 0x4000) | (address as usize & 0x3fff))
+        .copied()
+        .unwrap_or(0xff)
+}
+
+/// The state of a music channel in the sound engine, as far as timing goes.
+struct Channel {
+    id: u8,
+    address: u16,
+    duration: u8,
+    note_length: u8,
+    tempo: u16,
+    // The fraction of a frame that's carried over to the next note
+    carry: u8,
+    loop_count: Option<u8>,
+    return_address: Option<u16>,
+    condition: u8,
+    sfx: bool,
+    noise: bool,
+    // The frame at which each address outside of subroutines was first read
+    reached: HashMap<u16, u64>,
+    looped: Option<LoopFrames>,
+    ended: bool,
+}
+
+impl Channel {
+    fn new(id: u8, address: u16) -> Channel {
+        Channel {
+            id,
+            address,
+            duration: 0,
+            note_length: 1,
+            tempo: 0x100,
+            carry: 0,
+            loop_count: None,
+            return_address: None,
+            condition: 0,
+            sfx: false,
+            noise: false,
+            reached: HashMap::new(),
+            looped: None,
+            ended: false,
+        }
+    }
+
+    fn next_byte(&mut self, read: &impl Fn(u16) -> u8) -> u8 {
+        let byte = read(self.address);
+        self.address = self.address.wrapping_add(1);
+        byte
+    }
+
+    fn next_address(&mut self, read: &impl Fn(u16) -> u8) -> u16 {
+        u16::from_le_bytes([self.next_byte(read), self.next_byte(read)])
+    }
+
+    fn skip(&mut self, count: u16) {
+        self.address = self.address.wrapping_add(count);
+    }
+
+    /// Jumps to `target`, which is the start of the loop if it was already played.
+    fn jump(&mut self, target: u16, frame: u64) {
+        if self.return_address.is_none() && self.looped.is_none() {
+            if let Some(&start) = self.reached.get(&target) {
+                self.looped = Some(LoopFrames {
+                    intro: start,
+                    body: frame - start,
+                });
+            }
+        }
+
+        self.address = target;
+    }
+
+    /// SetNoteDuration
+    fn set_note_duration(&mut self, length: u8) {
+        let units = self.note_length.wrapping_mul(length.wrapping_add(1));
+        let result = (units as u16)
+            .wrapping_mul(self.tempo)
+            .wrapping_add(self.carry as u16);
+
+        self.carry = result as u8;
+        self.duration = (result >> 8) as u8;
+    }
+}
+
+/// Plays the channels at `channels` (their number and address) until they have all looped or
+/// ended. The track loops after the longest intro among them, and only repeats once every channel
+/// is back at the start of its loop, after the least common multiple of their loops.
+fn find_loop(read: impl Fn(u16) -> u8, channels: &[(u8, u16)]) -> Option<LoopFrames> {
+    let mut channels: Vec<Channel> = channels
+        .iter()
+        .map(|&(id, address)| Channel::new(id, address))
+        .collect();
+
+    for frame in 0..MAX_FRAMES {
+        if channels.iter().all(|ch| ch.ended || ch.looped.is_some()) {
+            break;
+        }
+
+        for i in 0..channels.len() {
+            if channels[i].ended {
+                continue;
+            }
+
+            if channels[i].duration >= 2 {
+                channels[i].duration -= 1;
+                continue;
+            }
+
+            parse_music(&mut channels, i, frame, &read);
+        }
+    }
+
+    if !channels.iter().all(|ch| ch.ended || ch.looped.is_some()) {
+        return None;
+    }
+
+    let mut loops = channels.iter().filter_map(|ch| ch.looped);
+
+    let intro = loops.clone().map(|l| l.intro).max()?;
+    let body = loops.try_fold(1, |body, l| {
+        let body = lcm(body, l.body);
+        (body <= MAX_FRAMES).then_some(body)
+    });
+
+    let Some(body) = body else {
+        log::warn!("The music channels only line up again after more than {MAX_FRAMES} frames");
+        return None;
+    };
+
+    Some(LoopFrames { intro, body })
+}
+
+fn lcm(a: u64, b: u64) -> u64 {
+    let gcd = |mut a: u64, mut b: u64| {
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        a
+    };
+
+    a / gcd(a, b) * b
+}
+
+/// ParseMusic: reads commands until the next note, which sets how long until the channel reads
+/// again.
+fn parse_music(channels: &mut [Channel], i: usize, frame: u64, read: &impl Fn(u16) -> u8) {
+    for _ in 0..MAX_COMMANDS {
+        let ch = &mut channels[i];
+
+        if ch.return_address.is_none() {
+            ch.reached.entry(ch.address).or_insert(frame);
+        }
+
+        match ch.next_byte(read) {
+            // Note or rest
+            note @ 0x00..=0xcf => {
+                if ch.sfx {
+                    ch.set_note_duration(note);
+                    ch.skip(if ch.id == 3 { 2 } else { 3 });
+                } else if !ch.noise || ch.id == 3 {
+                    ch.set_note_duration(note & 0x0f);
+                }
+
+                return;
+            }
+            // octave, sfx_priority_on, sfx_priority_off and unused commands
+            0xd0..=0xd7 | 0xec | 0xed | 0xf1..=0xf9 => {}
+            // note_type, or drum_speed on the noise channel
+            0xd8 => {
+                ch.note_length = ch.next_byte(read);
+
+                if ch.id != 3 {
+                    ch.skip(1);
+                }
+            }
+            // transpose, duty_cycle, volume_envelope, pitch_sweep, duty_cycle_pattern,
+            // force_stereo_panning, volume, stereo_panning and unused commands
+            0xd9 | 0xdb..=0xde | 0xe2 | 0xe4 | 0xe5 | 0xe7 | 0xe8 | 0xef => ch.skip(1),
+            // pitch_slide, vibrato, pitch_offset and a jump on a condition that's never set
+            0xe0 | 0xe1 | 0xe6 | 0xee => ch.skip(2),
+            // tempo
+            0xda => {
+                let tempo = u16::from_be_bytes([ch.next_byte(read), ch.next_byte(read)]);
+                set_global_tempo(channels, tempo);
+            }
+            // tempo_relative
+            0xe9 => {
+                let change = ch.next_byte(read) as i8;
+                let tempo = ch.tempo.wrapping_add_signed(change as i16);
+                set_global_tempo(channels, tempo);
+            }
+            // toggle_sfx
+            0xdf => ch.sfx = !ch.sfx,
+            // toggle_noise and sfx_toggle_noise, which only have a parameter when turned on
+            0xe3 | 0xf0 => {
+                ch.noise = !ch.noise;
+
+                if ch.noise {
+                    ch.skip(1);
+                }
+            }
+            // set_condition
+            0xfa => ch.condition = ch.next_byte(read),
+            // sound_jump_if
+            0xfb => {
+                let condition = ch.next_byte(read);
+                let target = ch.next_address(read);
+
+                if condition == ch.condition {
+                    ch.jump(target, frame);
+                }
+            }
+            // sound_jump
+            0xfc => {
+                let target = ch.next_address(read);
+                ch.jump(target, frame);
+            }
+            // sound_loop, which loops forever with a count of 0
+            0xfd => {
+                let count = ch.next_byte(read);
+
+                let remaining = match ch.loop_count {
+                    Some(remaining) => remaining,
+                    None if count == 0 => {
+                        let target = ch.next_address(read);
+                        ch.jump(target, frame);
+                        continue;
+                    }
+                    None => count - 1,
+                };
+
+                if remaining == 0 {
+                    ch.loop_count = None;
+                    ch.skip(2);
+                } else {
+                    ch.loop_count = Some(remaining - 1);
+                    ch.address = ch.next_address(read);
+                }
+            }
+            // sound_call
+            0xfe => {
+                let target = ch.next_address(read);
+                ch.return_address = Some(ch.address);
+                ch.address = target;
+            }
+            // sound_ret
+            0xff => match ch.return_address.take() {
+                Some(address) => ch.address = address,
+                None => {
+                    ch.ended = true;
+                    return;
+                }
+            },
+            // restart_channel and new_song
+            0xea | 0xeb => {
+                ch.ended = true;
+                return;
+            }
+        }
+    }
+
+    log::warn!("Music channel {} reads no notes", channels[i].id);
+    channels[i].ended = true;
+}
+
+/// SetGlobalTempo
+fn set_global_tempo(channels: &mut [Channel], tempo: u16) {
+    for ch in channels {
+        ch.tempo = tempo;
+        ch.carry = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_loop() {
+        #[rustfmt::skip]
+        let script = [
+            // Channel 1 at $4000
+            0xda, 0x01, 0x00,       // tempo $100
+            0xd8, 0x0c, 0xa7,       // note_type 12, 10, 7
+            0x13,                   // note C_, 4: 48 frames
+            0x21,                   // .mainloop: note C#, 2: 24 frames
+            0x10,                   // .inner: note C_, 1: 12 frames
+            0xfd, 0x02, 0x08, 0x40, // sound_loop 2, .inner
+            0xfd, 0x00, 0x07, 0x40, // sound_loop 0, .mainloop
+            // Channel 4 at $4011
+            0xe3, 0x03,             // toggle_noise 3
+            0xd8, 0x0c,             // drum_speed 12
+            0x23,                   // .mainloop: drum_note 3, 4: 48 frames
+            0xfc, 0x15, 0x40,       // sound_jump .mainloop
+            // Channel 2 at $4019
+            0xd8, 0x0c, 0xa7,       // note_type 12, 10, 7
+            0x1f,                   // note C_, 16: 192 frames
+            0xff,                   // sound_ret
+            // Another channel 4 at $401e
+            0xe3, 0x03,             // toggle_noise 3
+            0xd8, 0x0c,             // drum_speed 12
+            0x22,                   // .mainloop: drum_note 3, 3: 36 frames
+            0xfc, 0x22, 0x40,       // sound_jump .mainloop
+        ];
+
+        let read = |address: u16| {
+            script
+                .get(address as usize - 0x4000)
+                .copied()
+                .unwrap_or(0xff)
+        };
+
+        assert_eq!(
+            find_loop(read, &[(0, 0x4000), (3, 0x4011)]),
+            Some(LoopFrames {
+                intro: 48,
+                body: 48
+            })
+        );
+        assert_eq!(
+            find_loop(read, &[(0, 0x4000), (1, 0x4019)]),
+            Some(LoopFrames {
+                intro: 48,
+                body: 48
+            })
+        );
+        assert_eq!(find_loop(read, &[(1, 0x4019)]), None);
+
+        // Loops of 48 and 36 frames only line up every 144 frames
+        assert_eq!(
+            find_loop(read, &[(0, 0x4000), (3, 0x401e)]),
+            Some(LoopFrames {
+                intro: 48,
+                body: 144
+            })
+        );
+        assert_eq!(lcm(48, 36), 144);
+    }
+}
@@ -21,6 +21,40 @@ impl Sfx {
         }
     }
 
+    /// Whether the sound is a jingle, which the music is paused for.
+    #[rustfmt::skip]
+    pub fn is_jingle(id: u8) -> bool {
+        matches!(
+            id,
+            0x00 // Sfx_DexFanfare5079
+            | 0x01 // Sfx_Item
+            | 0x02 // Sfx_CaughtMon
+            | 0x09 // Sfx_DexFanfare2049
+            | 0x0a // Sfx_DexFanfare80109
+            | 0x2d // Sfx_Fanfare
+            | 0x90 // Sfx_LevelUp
+            | 0x91 // Sfx_KeyItem
+            | 0x92 // Sfx_Fanfare2
+            | 0x93 // Sfx_RegisterPhoneNumber
+            | 0x94 // Sfx_3rdPlace
+            | 0x95 // Sfx_GetEggFromDaycareMan
+            | 0x96 // Sfx_GetEggFromDaycareLady
+            | 0x97 // Sfx_MoveDeleted
+            | 0x98 // Sfx_2ndPlace
+            | 0x99 // Sfx_1stPlace
+            | 0x9b // Sfx_GetTm
+            | 0x9c // Sfx_GetBadge
+            | 0x9d // Sfx_QuitSlots
+            | 0x9f // Sfx_DexFanfareLessThan20
+            | 0xa0 // Sfx_DexFanfare140169
+            | 0xa1 // Sfx_DexFanfare170199
+            | 0xa2 // Sfx_DexFanfare200229
+            | 0xa3 // Sfx_DexFanfare230Plus
+        )
+    }
+
+    /// Returns `None` for sounds that the synthesizer can't play, such as jingles, which use music
+    /// channels. Those are played by the ROM's sound engine through the emulated APU instead.
     #[rustfmt::skip]
     pub fn from_sfx_id(id: u8) -> Option<Sfx> {
         match id {
@@ -173,8 +207,8 @@ impl Sfx {
             0x92 => None, // Sfx_Fanfare2
             0x93 => None, // Sfx_RegisterPhoneNumber
             0x94 => None, // Sfx_3rdPlace
-            0x95 => None, // Sfx_GetEgg
-            0x96 => None, // Sfx_GetEgg
+            0x95 => None, // Sfx_GetEggFromDaycareMan
+            0x96 => None, // Sfx_GetEggFromDaycareLady
             0x97 => None, // Sfx_MoveDeleted
             0x98 => None, // Sfx_2ndPlace
             0x99 => None, // Sfx_1stPlace
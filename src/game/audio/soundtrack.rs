@@ -0,0 +1,316 @@
+//! Picks the audio file for each `Music`, using the album file names by default and an optional
+//! `soundtrack.toml` manifest in the music directory to override them:
+//!
+//! ```toml
+//! [tracks.Route12]
+//! files = ["Remaster/Route 12.ogg", "Route 12.wav"]
+//! volume = 0.8
+//! loop_start = 88200
+//! loop_end = 2646000
+//! fallback = "Route3"
+//! ```
+//!
+//! For each track the listed `files` are tried in order, then the album file, and then the
+//! `fallback` track (with its own settings). The loop points are in samples per channel, and
+//! replace any loop tags in the file. Album files without either loop where the recording repeats
+//! itself, found around where the game's music data loops.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+};
+
+use serde::Deserialize;
+
+use crate::game::audio::{
+    music::Music,
+    music_loop::{LoopPoints, MusicSource},
+};
+
+const MANIFEST_NAME: &str = "soundtrack.toml";
+const AUDIO_EXTENSIONS: [&str; 3] = ["flac", "ogg", "wav"];
+
+static MANIFEST: OnceLock<Manifest> = OnceLock::new();
+static MEASURED_LOOPS: Mutex<BTreeMap<PathBuf, Option<LoopPoints>>> = Mutex::new(BTreeMap::new());
+
+fn resources_root() -> Option<PathBuf> {
+    if std::env::var_os("CARGO").is_some() {
+        return Some(PathBuf::from(std::env::var_os("CARGO_MANIFEST_DIR")?));
+    }
+
+    // TODO: support for other platforms
+    #[cfg(target_os = "macos")]
+    {
+        let bundle = core_foundation::bundle::CFBundle::main_bundle();
+        let bundle_path = bundle.path()?;
+        let resources_path = bundle.resources_path()?;
+        Some(bundle_path.join(resources_path))
+    }
+    #[cfg(not(any(target_os = "macos")))]
+    None
+}
+
+fn music_dir() -> PathBuf {
+    let root = resources_root().unwrap_or(std::env::current_dir().unwrap());
+    root.join("music")
+}
+
+/// Checks whether a soundtrack, or at least a manifest for one, has been put in the `music`
+/// directory.
+pub fn soundtrack_installed() -> bool {
+    std::fs::read_dir(music_dir()).is_ok_and(|mut entries| {
+        entries.any(|entry| {
+            entry.is_ok_and(|entry| {
+                let path = entry.path();
+
+                path.file_name().is_some_and(|name| name == MANIFEST_NAME)
+                    || path.extension().is_some_and(|ext| {
+                        AUDIO_EXTENSIONS
+                            .iter()
+                            .any(|known| ext.eq_ignore_ascii_case(known))
+                    })
+            })
+        })
+    })
+}
+
+fn manifest() -> &'static Manifest {
+    MANIFEST.get_or_init(|| Manifest::load(&music_dir().join(MANIFEST_NAME)))
+}
+
+/// Opens the music for `music`, or silence if none of its files could be found.
+pub fn open(music: Music) -> MusicSource {
+    let Some(track) = manifest().resolve(&music_dir(), music) else {
+        log::warn!("No music file found for {music:?}");
+        return MusicSource::silence();
+    };
+
+    let loop_points = track.loop_points.or_else(|| {
+        LoopPoints::from_flac_tags(&track.path).unwrap_or_else(|error| {
+            log::warn!(
+                "Failed to read loop points from {}: {error}",
+                track.path.display()
+            );
+            None
+        })
+    });
+
+    let loop_points = loop_points.or_else(|| measured_loop_points(&track));
+
+    MusicSource::open(&track.path, loop_points, track.volume).unwrap_or_else(|error| {
+        log::warn!("Failed to open {}: {error}", track.path.display());
+        MusicSource::silence()
+    })
+}
+
+/// Measures where an album track loops, once per file as it takes a while.
+fn measured_loop_points(track: &Track) -> Option<LoopPoints> {
+    let loop_frames = track.album?.loop_frames()?;
+
+    *MEASURED_LOOPS
+        .lock()
+        .unwrap()
+        .entry(track.path.clone())
+        .or_insert_with(|| {
+            LoopPoints::measure(&track.path, loop_frames).unwrap_or_else(|error| {
+                log::warn!(
+                    "Failed to measure the loop of {}: {error}",
+                    track.path.display()
+                );
+                None
+            })
+        })
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    #[serde(default)]
+    tracks: HashMap<String, TrackEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TrackEntry {
+    #[serde(default)]
+    files: Vec<PathBuf>,
+    #[serde(default = "full_volume")]
+    volume: f32,
+    loop_start: Option<u64>,
+    loop_end: Option<u64>,
+    fallback: Option<String>,
+}
+
+fn full_volume() -> f32 {
+    1.0
+}
+
+/// The file that was picked for a track, along with how to play it.
+#[derive(Debug, PartialEq)]
+pub struct Track {
+    pub path: PathBuf,
+    pub volume: f32,
+    pub loop_points: Option<LoopPoints>,
+    /// The track whose album file was picked, if it was one.
+    pub album: Option<Music>,
+}
+
+impl Manifest {
+    /// Reads the manifest at `path`, which is empty if the file doesn't exist or is invalid.
+    pub fn load(path: &Path) -> Manifest {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(_) => return Manifest::default(),
+        };
+
+        match Manifest::parse(&text) {
+            Ok(manifest) => manifest,
+            Err(error) => {
+                log::error!("Ignoring invalid {}: {error}", path.display());
+                Manifest::default()
+            }
+        }
+    }
+
+    fn parse(text: &str) -> Result<Manifest, toml::de::Error> {
+        let manifest: Manifest = toml::from_str(text)?;
+
+        for name in manifest.tracks.keys() {
+            if Music::from_name(name).is_none() {
+                log::warn!("Unknown track in {MANIFEST_NAME}: {name}");
+            }
+        }
+
+        Ok(manifest)
+    }
+
+    /// Finds the first existing file for `music` in `dir`, following the fallback chain.
+    pub fn resolve(&self, dir: &Path, music: Music) -> Option<Track> {
+        let mut visited = Vec::new();
+        let mut current = music;
+
+        loop {
+            if visited.contains(&current) {
+                log::warn!("Fallback loop in {MANIFEST_NAME} at {current:?}");
+                return None;
+            }
+
+            visited.push(current);
+
+            let entry = self.tracks.get(&format!("{current:?}"));
+
+            let files = entry.into_iter().flat_map(|entry| entry.files.iter());
+            let album_file = Path::new(current.album_file());
+
+            if let Some(path) = files
+                .map(|file| dir.join(file))
+                .chain(std::iter::once(dir.join(album_file)))
+                .find(|path| path.is_file())
+            {
+                let album = (path == dir.join(album_file)).then_some(current);
+
+                return Some(Track {
+                    path,
+                    volume: entry.map_or(1.0, |entry| entry.volume),
+                    loop_points: entry.and_then(TrackEntry::loop_points),
+                    album,
+                });
+            }
+
+            let fallback = entry?.fallback.as_deref()?;
+
+            current = match Music::from_name(fallback) {
+                Some(music) => music,
+                None => {
+                    log::warn!("Unknown fallback track in {MANIFEST_NAME}: {fallback}");
+                    return None;
+                }
+            };
+        }
+    }
+}
+
+impl TrackEntry {
+    fn loop_points(&self) -> Option<LoopPoints> {
+        let intro_end = self.loop_start?;
+        let loop_end = self.loop_end?;
+
+        (loop_end > intro_end).then_some(LoopPoints {
+            intro_end,
+            loop_end,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::audio::music_timing::frames_to_samples;
+
+    #[test]
+    fn test_resolve() {
+        let dir = std::env::temp_dir().join(format!("rustic-crystal-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("remaster.ogg"), []).unwrap();
+        std::fs::write(dir.join(Music::Route3.album_file()), []).unwrap();
+
+        let manifest = Manifest::parse(
+            r#"
+            [tracks.Route1]
+            files = ["missing.flac", "remaster.ogg"]
+            volume = 0.5
+            loop_start = 10
+            loop_end = 20
+
+            [tracks.Route12]
+            fallback = "Route3"
+
+            [tracks.Route2]
+            fallback = "Route2"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            manifest.resolve(&dir, Music::Route1),
+            Some(Track {
+                path: dir.join("remaster.ogg"),
+                volume: 0.5,
+                loop_points: Some(LoopPoints {
+                    intro_end: 10,
+                    loop_end: 20
+                }),
+                album: None,
+            })
+        );
+        assert_eq!(
+            manifest.resolve(&dir, Music::Route12),
+            Some(Track {
+                path: dir.join(Music::Route3.album_file()),
+                volume: 1.0,
+                loop_points: None,
+                album: Some(Music::Route3),
+            })
+        );
+        assert_eq!(manifest.resolve(&dir, Music::Route2), None);
+        assert_eq!(manifest.resolve(&dir, Music::Title), None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[ignore = "needs the album in the music directory and the game's ROM"]
+    fn test_measure_album_loop() {
+        let music = Music::Route29;
+        let path = music_dir().join(music.album_file());
+        let loop_frames = music.loop_frames().unwrap();
+
+        let points = LoopPoints::measure(&path, loop_frames).unwrap().unwrap();
+
+        // The album was recorded at 44.1 kHz, maybe a bit faster or slower than the game plays
+        let body = frames_to_samples(loop_frames.body, 44100) as f64;
+        let measured = (points.loop_end - points.intro_end) as f64;
+        assert!((measured / body - 1.0).abs() < 0.03, "{measured} vs {body}");
+    }
+}
@@ -7,6 +7,8 @@ define_u8_enum! {
         Brightpowder = 0x03,
         GreatBall = 0x04,
         PokeBall = 0x05,
+        TownMap = 0x06,
+        Bicycle = 0x07,
         MoonStone = 0x08,
         Antidote = 0x09,
         BurnHeal = 0x0a,
@@ -20,6 +22,7 @@ define_u8_enum! {
         Potion = 0x12,
         EscapeRope = 0x13,
         Repel = 0x14,
+        MaxElixer = 0x15,
         FireStone = 0x16,
         Thunderstone = 0x17,
         WaterStone = 0x18,
@@ -27,13 +30,17 @@ define_u8_enum! {
         Protein = 0x1b,
         Iron = 0x1c,
         Carbos = 0x1d,
+        LuckyPunch = 0x1e,
         Calcium = 0x1f,
+        RareCandy = 0x20,
         XAccuracy = 0x21,
         LeafStone = 0x22,
+        MetalPowder = 0x23,
         Nugget = 0x24,
         PokeDoll = 0x25,
         FullHeal = 0x26,
         Revive = 0x27,
+        MaxRevive = 0x28,
         GuardSpec = 0x29,
         SuperRepel = 0x2a,
         MaxRepel = 0x2b,
@@ -45,21 +52,57 @@ define_u8_enum! {
         XDefend = 0x33,
         XSpeed = 0x34,
         XSpecial = 0x35,
+        CoinCase = 0x36,
+        Itemfinder = 0x37,
+        PokeFlute = 0x38,
+        ExpShare = 0x39,
+        OldRod = 0x3a,
+        GoodRod = 0x3b,
+        SilverLeaf = 0x3c,
+        SuperRod = 0x3d,
+        PPUp = 0x3e,
         Ether = 0x3f,
+        MaxEther = 0x40,
+        Elixer = 0x41,
+        RedScale = 0x42,
+        Secretpotion = 0x43,
+        SSTicket = 0x44,
+        MysteryEgg = 0x45,
+        ClearBell = 0x46,
+        SilverWing = 0x47,
+        MoomooMilk = 0x48,
         QuickClaw = 0x49,
+        Psncureberry = 0x4a,
+        GoldLeaf = 0x4b,
         SoftSand = 0x4c,
         SharpBeak = 0x4d,
+        Przcureberry = 0x4e,
+        BurntBerry = 0x4f,
+        IceBerry = 0x50,
         PoisonBarb = 0x51,
         KingsRock = 0x52,
         BitterBerry = 0x53,
         MintBerry = 0x54,
+        RedApricorn = 0x55,
         Tinymushroom = 0x56,
+        BigMushroom = 0x57,
+        Silverpowder = 0x58,
+        BluApricorn = 0x59,
+        AmuletCoin = 0x5b,
+        YlwApricorn = 0x5c,
+        GrnApricorn = 0x5d,
+        CleanseTag = 0x5e,
         MysticWater = 0x5f,
         Twistedspoon = 0x60,
+        WhtApricorn = 0x61,
         Blackbelt = 0x62,
+        BlkApricorn = 0x63,
+        PnkApricorn = 0x65,
         Blackglasses = 0x66,
         Slowpoketail = 0x67,
         PinkBow = 0x68,
+        Stick = 0x69,
+        SmokeBall = 0x6a,
         Nevermeltice = 0x6b,
         Magnet = 0x6c,
         Miracleberry = 0x6d,
@@ -68,22 +111,35 @@ define_u8_enum! {
         Everstone = 0x70,
         SpellTag = 0x71,
         Ragecandybar = 0x72,
+        GSBall = 0x73,
+        BlueCard = 0x74,
         MiracleSeed = 0x75,
+        ThickClub = 0x76,
         FocusBand = 0x77,
         EnergyPowder = 0x79,
         EnergyRoot = 0x7a,
         HealPowder = 0x7b,
         RevivalHerb = 0x7c,
         HardStone = 0x7d,
+        LuckyEgg = 0x7e,
+        CardKey = 0x7f,
+        MachinePart = 0x80,
+        EggTicket = 0x81,
+        LostItem = 0x82,
         Stardust = 0x83,
         StarPiece = 0x84,
+        BasementKey = 0x85,
+        Pass = 0x86,
         Charcoal = 0x8a,
         BerryJuice = 0x8b,
         ScopeLens = 0x8c,
         MetalCoat = 0x8f,
+        DragonFang = 0x90,
         Leftovers = 0x92,
+        Mysteryberry = 0x96,
         DragonScale = 0x97,
         BerserkGene = 0x98,
+        SacredAsh = 0x9c,
         HeavyBall = 0x9d,
         FlowerMail = 0x9e,
         LevelBall = 0x9f,
@@ -93,16 +149,26 @@ define_u8_enum! {
         FriendBall = 0xa4,
         MoonBall = 0xa5,
         LoveBall = 0xa6,
+        NormalBox = 0xa7,
+        GorgeousBox = 0xa8,
         SunStone = 0xa9,
+        PolkadotBow = 0xaa,
         UpGrade = 0xac,
         GoldBerry = 0xae,
         Berry = 0xad,
+        Squirtbottle = 0xaf,
         ParkBall = 0xb1,
+        RainbowWing = 0xb2,
         BrickPiece = 0xb4,
         SurfMail = 0xb5,
         LiteBlueMail = 0xb6,
         PortraitMail = 0xb7,
         LovelyMail = 0xb8,
+        EonMail = 0xb9,
+        MorphMail = 0xba,
+        BlueskyMail = 0xbb,
+        MusicMail = 0xbc,
+        MirageMail = 0xbd,
         TmDynamicpunch = 0xbf,
         TmHeadbutt = 0xc0,
         TmCurse = 0xc1,
@@ -1,2 +1,10 @@
+// Capacities of the item lists
+pub const MAX_ITEMS: usize = 20;
+pub const MAX_BALLS: usize = 12;
+pub const MAX_KEY_ITEMS: usize = 25;
+pub const MAX_PC_ITEMS: usize = 50;
+
+pub const MAX_ITEM_STACK: u8 = 99;
+
 pub const MAIL_STRUCT_LENGTH: usize = 0x2f;
 pub const HELD_CATCH_CHANCE: u8 = 70;
@@ -6,6 +6,8 @@ use crate::{
 
 pub const EGG: u8 = 0xfd;
 
+pub const NUM_POKEMON: usize = 251;
+
 define_u8_enum! {
     pub enum PokemonSpecies {
         Bulbasaur  = 0x01,
@@ -9,6 +9,9 @@ use crate::game::{
 /// Maximum number of party pokemon
 pub const PARTY_LENGTH: usize = 6;
 
+pub const MIN_LEVEL: u8 = 2;
+pub const MAX_LEVEL: u8 = 100;
+
 pub const MONS_PER_BOX: usize = 20;
 pub const NUM_BOXES: u8 = 14;
 
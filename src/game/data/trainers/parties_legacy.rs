@@ -1,4 +1,5 @@
 use crate::{
+    error::{self, Error},
     game::constants::{
         item_constants::Item, move_constants::Move, pokemon_constants::PokemonSpecies,
         text_constants::NAME_LENGTH, trainer_constants::Trainer,
@@ -50,7 +51,7 @@ macro_rules! parties {
                             &PARTY
                         }
                     ),*
-                    Trainer::Unknown(x, y) => unimplemented!("No party defined for trainer {x}:{y}")
+                    Trainer::Unknown(x, y) => error::raise(Error::UnknownTrainer(*x, *y))
                 }
             }
         }
@@ -1,11 +1,12 @@
 use crate::{
     cpu::Cpu,
     game::{constants::battle_constants::TypeEffectiveness, macros},
+    trace,
 };
 
 /// Calculates the effectiveness of the types of the PlayerMon against the OTMon
 pub fn is_the_player_mon_types_effective_against_ot_mon(cpu: &mut Cpu) {
-    log::debug!(
+    log::debug!(target: trace::BATTLE,
         "is_the_player_mon_types_effective_against_ot_mon({:02x})",
         cpu.b
     );
@@ -42,7 +43,7 @@ pub fn is_the_player_mon_types_effective_against_ot_mon(cpu: &mut Cpu) {
         return is_the_player_mon_types_effective_against_ot_mon_super_effective(cpu);
     }
 
-    log::trace!(
+    log::trace!(target: trace::BATTLE,
         "is_the_player_mon_types_effective_against_ot_mon({:02x}) => false",
         cpu.b
     );
@@ -51,7 +52,7 @@ pub fn is_the_player_mon_types_effective_against_ot_mon(cpu: &mut Cpu) {
 }
 
 fn is_the_player_mon_types_effective_against_ot_mon_super_effective(cpu: &mut Cpu) {
-    log::trace!(
+    log::trace!(target: trace::BATTLE,
         "is_the_player_mon_types_effective_against_ot_mon({:02x}) => true",
         cpu.b
     );
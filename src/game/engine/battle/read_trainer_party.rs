@@ -11,6 +11,7 @@ use crate::{
         ram::{sram, wram},
     },
     game_state::{moveset::Moveset, party_mon::PartyMonOwned},
+    trace,
 };
 
 pub fn read_trainer_party(cpu: &mut Cpu) {
@@ -26,7 +27,7 @@ pub fn read_trainer_party(cpu: &mut Cpu) {
 
     let trainer = cpu.borrow_wram().other_trainer();
 
-    log::info!("read_trainer_party({trainer:?})");
+    log::info!(target: trace::BATTLE, "read_trainer_party({trainer:?})");
 
     cpu.write_byte(0xd280, 0); // wOTPartyCount
     cpu.write_byte(0xd281, 0xff); // wOTPartySpecies
@@ -77,7 +78,7 @@ pub fn read_trainer_party(cpu: &mut Cpu) {
                 .set_pp(pps);
         }
 
-        log::trace!(
+        log::trace!(target: trace::BATTLE,
             "read_trainer_party({trainer:?}) Added mon: {:?}",
             cpu.borrow_wram().ot_party().get(i)
         );
@@ -0,0 +1 @@
+pub mod mon_icons;
@@ -0,0 +1,21 @@
+//! The small icons that the party menu shows for each species.
+
+use crate::{cpu::Cpu, game::macros, rom::ROM};
+
+/// An icon is two frames of 2x2 tiles, of which menus that don't animate show the first.
+pub const ICON_TILES: usize = 4;
+const TILE_SIZE: usize = 16;
+
+/// The first frame of the menu icon of `species`, which is `EGG` for an egg, as 2bpp tiles in the
+/// order top left, top right, bottom left, bottom right.
+pub fn menu_icon_tiles(cpu: &mut Cpu, species: u8) -> Option<[u8; ICON_TILES * TILE_SIZE]> {
+    // Returns the icon's graphics in b:de
+    cpu.e = species;
+    macros::farcall::farcall(cpu, 0x23, 0x682b); // LoadOverworldMonIcon
+
+    let start = cpu.b as usize * 0x4000 + (cpu.de() as usize & 0x3fff);
+
+    ROM.get(start..start + ICON_TILES * TILE_SIZE)?
+        .try_into()
+        .ok()
+}
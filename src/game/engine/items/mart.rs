@@ -1,5 +1,6 @@
 use crate::{
     cpu::{Cpu, CpuFlag},
+    error::{self, Error},
     game::{
         constants::{
             item_constants::Item,
@@ -25,7 +26,7 @@ pub fn open_mart_dialog(cpu: &mut Cpu) {
         MartType::Bargain => bargain_shop(cpu),
         MartType::Pharmacy => pharmacist(cpu, mart),
         MartType::Rooftop => rooftop_sale(cpu),
-        MartType::Unknown(n) => unreachable!("Invalid mart type: {n}"),
+        MartType::Unknown(n) => error::raise(Error::UnknownMartType(n)),
     }
 
     cpu.pc = cpu.stack_pop(); // ret
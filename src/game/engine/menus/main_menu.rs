@@ -1,28 +1,50 @@
+use std::{
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
 use crate::{
-    cpu::Cpu,
+    cpu::{Cpu, CpuFlag},
     game::{
         audio::sfx::Sfx,
         constants::{
+            gfx_constants::{SCREEN_HEIGHT, SCREEN_WIDTH},
             input_constants::JoypadButtons,
-            menu_constants::{Menu2DFlags1, NAME_BOX},
+            menu_constants::NAME_BOX,
+            pokemon_constants::EGG,
             scgb_constants,
         },
+        engine::{gfx::mon_icons, menus::sound_test},
+        home::text::{place_string, tile_to_char},
         macros::{self, coords::coord},
         ram::{hram, wram},
     },
-    save_state::SaveState,
+    game_state::mon_list::MonListEntry,
+    save_state::{sav_file::RtcFooter, SaveState},
     saves,
 };
 
-const MAINMENU_NEW_GAME: u8 = 0;
-const MAINMENU_CONTINUE: u8 = 1;
+/// An entry of the main menu.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum MainMenuItem {
+    Continue,
+    NewGame,
+    Option,
+    SoundTest,
+    ImportSave,
+}
 
-const MAINMENUITEM_CONTINUE: u8 = 0;
-const MAINMENUITEM_NEW_GAME: u8 = 1;
-const MAINMENUITEM_OPTION: u8 = 2;
-const MAINMENUITEM_MYSTERY_GIFT: u8 = 3;
-const MAINMENUITEM_MOBILE: u8 = 4;
-const MAINMENUITEM_MOBILE_STUDIUM: u8 = 5;
+impl MainMenuItem {
+    fn label(self) -> &'static str {
+        match self {
+            MainMenuItem::Continue => "CONTINUE",
+            MainMenuItem::NewGame => "NEW GAME",
+            MainMenuItem::Option => "OPTION",
+            MainMenuItem::SoundTest => "SOUND TEST",
+            MainMenuItem::ImportSave => "IMPORT SAVE",
+        }
+    }
+}
 
 pub fn main_menu(cpu: &mut Cpu) {
     log::debug!("main_menu()");
@@ -39,90 +61,81 @@ pub fn main_menu(cpu: &mut Cpu) {
 
         cpu.borrow_wram_mut().set_game_timer_paused(false);
 
-        {
-            let value = main_menu_get_which_menu();
-            cpu.borrow_wram_mut().set_which_index_set(value);
-        }
-
         main_menu_print_current_time_and_day(cpu);
 
         cpu.set_hl(0x5d14); // MainMenu.MenuHeader
         cpu.call(0x1d35); // LoadMenuHeader
 
-        let should_exit_menu = main_menu_joypad_loop(cpu);
+        let items = main_menu_items();
+        let selected = main_menu_joypad_loop(cpu, &items);
 
         cpu.call(0x1c17); // CloseWindow
 
-        if should_exit_menu {
+        let Some(item) = selected else {
             cpu.pc = cpu.stack_pop(); // ret
             return;
-        }
+        };
 
         cpu.call(0x0fc8); // ClearTilemap
 
-        match cpu.borrow_wram().menu_selection() {
-            MAINMENUITEM_CONTINUE => {
+        match item {
+            MainMenuItem::Continue => {
                 main_menu_select_save(cpu);
             }
-            MAINMENUITEM_NEW_GAME => {
+            MainMenuItem::NewGame => {
                 main_menu_create_save(cpu);
             }
-            MAINMENUITEM_OPTION => {
+            MainMenuItem::Option => {
                 cpu.call(0x5ee7); // MainMenu_Option
             }
-            MAINMENUITEM_MYSTERY_GIFT => {
-                cpu.call(0x5ef5); // MainMenu_MysteryGift
-            }
-            MAINMENUITEM_MOBILE => {
-                cpu.call(0x5efc); // MainMenu_Mobile
+            MainMenuItem::SoundTest => {
+                sound_test::sound_test(cpu);
             }
-            MAINMENUITEM_MOBILE_STUDIUM => {
-                cpu.call(0x6496); // MainMenu_MobileStudium
+            MainMenuItem::ImportSave => {
+                import_save(cpu);
             }
-            n => panic!("Unknown main menu item: {n}"),
         }
     }
 }
 
-fn main_menu_get_which_menu() -> u8 {
-    match saves::list_save_files() {
-        Ok(files) => {
-            if files.is_empty() {
-                MAINMENU_NEW_GAME
-            } else {
-                MAINMENU_CONTINUE
-            }
-        }
+fn main_menu_items() -> Vec<MainMenuItem> {
+    let has_saves = match saves::list_save_files() {
+        Ok(files) => !files.is_empty(),
         Err(e) => {
             log::error!("Error listing save files: {e}");
-            MAINMENU_NEW_GAME
+            false
         }
-    }
-}
+    };
 
-fn main_menu_joypad_loop(cpu: &mut Cpu) -> bool {
-    cpu.call(0x1e70); // SetUpMenu
+    let mut items = Vec::new();
 
-    loop {
-        main_menu_print_current_time_and_day(cpu);
+    if has_saves {
+        items.push(MainMenuItem::Continue);
+    }
 
-        let mut flags = cpu.read_byte(wram::MENU_2D_FLAGS_1);
-        flags |= Menu2DFlags1::WRAP_UP_DOWN.bits();
-        cpu.write_byte(wram::MENU_2D_FLAGS_1, flags);
+    items.extend([
+        MainMenuItem::NewGame,
+        MainMenuItem::Option,
+        MainMenuItem::SoundTest,
+        MainMenuItem::ImportSave,
+    ]);
 
-        cpu.call(0x1f1a); // GetScrollingMenuJoypad
+    items
+}
 
-        let buttons = cpu.borrow_wram().menu_joypad();
+/// Returns the chosen item, or `None` if the player went back to the title screen.
+fn main_menu_joypad_loop(cpu: &mut Cpu, items: &[MainMenuItem]) -> Option<MainMenuItem> {
+    let labels: Vec<&str> = items.iter().map(|item| item.label()).collect();
 
-        if buttons.contains(JoypadButtons::B) {
-            return true;
-        }
+    let selected = run_menu(
+        cpu,
+        coord!(0, 0),
+        &labels,
+        true,
+        main_menu_print_current_time_and_day,
+    )?;
 
-        if buttons.contains(JoypadButtons::A) {
-            cpu.call(0x2009); // PlayClickSFX
-            return false;
-        }
-    }
+    Some(items[selected])
 }
 
 fn main_menu_print_current_time_and_day(cpu: &mut Cpu) {
@@ -152,42 +165,35 @@ fn clear_tilemap_etc(cpu: &mut Cpu) {
     cpu.call(0x1fbf); // ClearWindowData
 }
 
-fn main_menu_select_save(cpu: &mut Cpu) {
-    let list = match saves::list_save_files() {
-        Ok(ref files) if files.is_empty() => {
-            return;
-        }
-        Ok(files) => files,
-        Err(error) => {
-            log::error!("Error listing save files: {error}");
-            return;
-        }
-    };
+/// A save in the list, with its contents for the summary of the selected save.
+struct SaveSlot {
+    file: saves::SaveFile,
+    state: Option<SaveState>,
+}
 
-    let height = u8::min(list.len() as u8 * 2, 16);
+const VISIBLE_SAVES: usize = 7;
 
-    let width = u8::min(
-        list.iter()
-            .map(|s| s.name.chars().count() as u8)
-            .max()
-            .unwrap_or(0)
-            + 2,
-        18,
-    );
+/// What START and SELECT do in the list of saves, shown below it.
+const SAVE_LIST_HINTS: [&str; 2] = ["START: IMPORT SAVE", "SELECT: SAVE OPTIONS"];
 
-    let max_menu_item = list.len() - 1;
+fn main_menu_select_save(cpu: &mut Cpu) {
+    let Some(mut list) = list_save_slots() else {
+        return;
+    };
 
     let mut selected = 0;
     let mut scroll_pos = 0;
 
     loop {
+        let max_menu_item = list.len() - 1;
+
         cpu.set_hl(coord!(0, 0));
-        cpu.b = height;
-        cpu.c = width;
+        cpu.b = VISIBLE_SAVES as u8;
+        cpu.c = SCREEN_WIDTH - 2;
         cpu.call(0x0fe8); // Textbox
 
-        for (i, save_file) in list.iter().skip(scroll_pos).take(8).enumerate() {
-            let mut xy = coord!(1, 2 + i as u8 * 2);
+        for (i, slot) in list.iter().skip(scroll_pos).take(VISIBLE_SAVES).enumerate() {
+            let xy = coord!(1, 1 + i as u8);
 
             if i + scroll_pos == selected {
                 cpu.write_byte(xy, 0xed); // ▶
@@ -195,21 +201,18 @@ fn main_menu_select_save(cpu: &mut Cpu) {
                 cpu.write_byte(xy, 0x7f);
             }
 
-            xy += 2;
-
-            for c in save_file.name.chars() {
-                let value = match c {
-                    'A'..='Z' => 0x80 + (c as u8 - b'A'),
-                    'a'..='z' => 0xa0 + (c as u8 - b'a'),
-                    '0'..='9' => 0xf6 + (c as u8 - b'0'),
-                    ' ' => 0x7f,
-                    _ => 0xe6, // ?
-                };
-                cpu.write_byte(xy, value);
-                xy += 1;
-            }
+            let name: String = slot.file.name.chars().take(16).collect();
+            place_string(cpu, xy + 2, &name);
         }
 
+        cpu.set_hl(coord!(0, VISIBLE_SAVES as u8 + 2));
+        cpu.b = SCREEN_HEIGHT - VISIBLE_SAVES as u8 - 4 - SAVE_LIST_HINTS.len() as u8;
+        cpu.c = SCREEN_WIDTH - 2;
+        cpu.call(0x0fe8); // Textbox
+
+        place_save_summary(cpu, &list[selected], VISIBLE_SAVES as u8 + 3);
+        place_hints(cpu, &SAVE_LIST_HINTS);
+
         cpu.call(0x0a57); // JoyTextDelay
         cpu.call(0x1bdd); // GetMenuJoypad
 
@@ -226,8 +229,8 @@ fn main_menu_select_save(cpu: &mut Cpu) {
         if btns.contains(JoypadButtons::DOWN) && selected < max_menu_item {
             selected += 1;
 
-            if selected >= scroll_pos + 8 {
-                scroll_pos = selected - 7;
+            if selected >= scroll_pos + VISIBLE_SAVES {
+                scroll_pos = selected + 1 - VISIBLE_SAVES;
             }
         }
 
@@ -235,11 +238,47 @@ fn main_menu_select_save(cpu: &mut Cpu) {
             return;
         }
 
+        let changed = if btns.contains(JoypadButtons::SELECT) {
+            manage_save(cpu, &list[selected].file)
+        } else if btns.contains(JoypadButtons::START) {
+            import_save(cpu)
+        } else {
+            None
+        };
+
+        if let Some(id) = changed {
+            let Some(slots) = list_save_slots() else {
+                return;
+            };
+
+            list = slots;
+            selected = list
+                .iter()
+                .position(|s| s.file.id == id)
+                .unwrap_or(selected.min(list.len() - 1));
+            scroll_pos = (selected + 1).saturating_sub(VISIBLE_SAVES);
+            continue;
+        }
+
         if btns.contains(JoypadButtons::A) {
             cpu.call(0x2009); // PlayClickSFX
 
-            let save_file = &list[selected];
-            let sram = SaveState::from_file(&save_file.path).unwrap();
+            let save_file = &list[selected].file;
+
+            if save_file.data_set != saves::DataSet::current() {
+                log::warn!(
+                    "{} was made with the {:?} data set, not the {:?} one",
+                    save_file.name,
+                    save_file.data_set,
+                    saves::DataSet::current()
+                );
+            }
+
+            let Some(sram) = load_save(save_file) else {
+                cpu.play_sfx(Sfx::new(0x3c, 0x497d)); // Sfx_Wrong
+                continue;
+            };
+
             cpu.replace_sram(sram, save_file.path.clone());
 
             macros::farcall::farcall(cpu, 0x05, 0x4f1c); // TryLoadSaveData
@@ -252,34 +291,485 @@ fn main_menu_select_save(cpu: &mut Cpu) {
     }
 }
 
-fn main_menu_create_save(cpu: &mut Cpu) {
+/// Shows who's playing a save and how far they are, starting at row `y`.
+fn place_save_summary(cpu: &mut Cpu, slot: &SaveSlot, y: u8) {
+    let Some(state) = slot.state.as_ref().filter(|state| state.is_valid()) else {
+        place_string(cpu, coord!(1, y), "DAMAGED SAVE");
+        return;
+    };
+
+    place_poke_string(cpu, coord!(1, y), state.player_name().as_ref());
+
+    let time = state.play_time();
+    place_string_right(
+        cpu,
+        coord!(SCREEN_WIDTH - 1, y),
+        &format!("{}:{:02}", time.hours, time.minutes),
+    );
+
+    place_string(
+        cpu,
+        coord!(1, y + 1),
+        &format!("BADGES {}", state.badge_count()),
+    );
+    place_string_right(
+        cpu,
+        coord!(SCREEN_WIDTH - 1, y + 1),
+        &format!("DEX {}", state.pokedex_caught_count()),
+    );
+
+    place_string(
+        cpu,
+        coord!(1, y + 2),
+        &format!("SAVED {}", format_date(slot.file.modified)),
+    );
+
+    for i in 0..state.party().len() {
+        let species = match state.party().get(i) {
+            Some(MonListEntry::Mon(mon, ..)) => u8::from(mon.species()),
+            _ => EGG,
+        };
+
+        place_mon_icon(cpu, coord!(1 + 3 * i as u8, y + 3), i as u8, species);
+    }
+}
+
+/// Places the menu icon of `species`, which is `EGG` for an egg, at `xy`, using the BG tiles of
+/// icon `slot`.
+fn place_mon_icon(cpu: &mut Cpu, xy: u16, slot: u8, species: u8) {
+    let Some(tiles) = mon_icons::menu_icon_tiles(cpu, species) else {
+        return;
+    };
+
+    // The first BG tiles, which nothing else uses on this screen
+    let first_tile = slot * mon_icons::ICON_TILES as u8;
+
+    let vram_bank = cpu.read_byte(0xff4f); // rVBK
+    cpu.write_byte(0xff4f, 0);
+
+    for (i, &byte) in tiles.iter().enumerate() {
+        cpu.write_byte(0x9000 + first_tile as u16 * 16 + i as u16, byte); // vTiles2
+    }
+
+    cpu.write_byte(0xff4f, vram_bank);
+
+    cpu.write_byte(xy, first_tile);
+    cpu.write_byte(xy + 1, first_tile + 1);
+    cpu.write_byte(coord!(0, 1, xy), first_tile + 2);
+    cpu.write_byte(coord!(1, 1, xy), first_tile + 3);
+}
+
+/// Places a line for each of `hints` at the bottom of the screen, for actions that have no menu
+/// item.
+fn place_hints(cpu: &mut Cpu, hints: &[&str]) {
+    let top = SCREEN_HEIGHT - hints.len() as u8;
+
+    for (i, hint) in hints.iter().enumerate() {
+        let xy = coord!(0, top + i as u8);
+
+        for x in 0..SCREEN_WIDTH as u16 {
+            cpu.write_byte(xy + x, 0x7f);
+        }
+
+        place_string(cpu, xy, hint);
+    }
+}
+
+/// Places a string in the game's own encoding, up to its terminator.
+fn place_poke_string(cpu: &mut Cpu, xy: u16, text: &[u8]) {
+    for (i, &c) in text.iter().take_while(|&&c| c != 0x50).enumerate() {
+        cpu.write_byte(xy + i as u16, c);
+    }
+}
+
+/// Places a string so that it ends just before `xy`.
+fn place_string_right(cpu: &mut Cpu, xy: u16, text: &str) {
+    place_string(cpu, xy - text.chars().count() as u16, text);
+}
+
+/// The UTC date of `time`, as YYYY-MM-DD.
+fn format_date(time: SystemTime) -> String {
+    let days = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() / (24 * 60 * 60))
+        .unwrap_or(0) as i64;
+
+    // Howard Hinnant's days_from_civil, backwards
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Loads a save, offering to restore the newest good backup if neither copy of the game data in
+/// it can be loaded.
+fn load_save(save_file: &saves::SaveFile) -> Option<SaveState> {
+    let sram = match SaveState::from_file(&save_file.path) {
+        Ok(sram) if sram.is_valid() => return Some(sram),
+        Ok(sram) => {
+            log::error!("Save {} is corrupted", save_file.name);
+            Some(sram)
+        }
+        Err(error) => {
+            log::error!("Error loading save {}: {error}", save_file.name);
+            None
+        }
+    };
+
+    let backup = saves::newest_valid_backup(save_file).unwrap_or_else(|error| {
+        log::error!("Error listing backups of {}: {error}", save_file.name);
+        None
+    });
+
+    // Without a backup the game handles a corrupted save like a missing one
+    let Some((path, backup)) = backup else {
+        return sram;
+    };
+
+    let modified = path
+        .metadata()
+        .and_then(|meta| meta.modified())
+        .ok()
+        .and_then(|time| time.elapsed().ok())
+        .map(|elapsed| format!(" from {} minutes ago", elapsed.as_secs() / 60))
+        .unwrap_or_default();
+
+    let answer = tinyfiledialogs::message_box_yes_no(
+        "Corrupted save",
+        &format!(
+            "The save {} is damaged and can't be loaded. Restore the backup{modified}?",
+            save_file.name
+        ),
+        tinyfiledialogs::MessageBoxIcon::Warning,
+        tinyfiledialogs::YesNo::Yes,
+    );
+
+    if answer == tinyfiledialogs::YesNo::No {
+        return sram;
+    }
+
+    match saves::restore_backup(save_file, &backup) {
+        Ok(()) => {
+            log::info!("Restored {} from {}", save_file.name, path.display());
+            Some(backup)
+        }
+        Err(error) => {
+            log::error!("Error restoring {}: {error}", save_file.name);
+            sram
+        }
+    }
+}
+
+fn list_save_slots() -> Option<Vec<SaveSlot>> {
+    let files = list_save_files()?;
+
+    Some(
+        files
+            .into_iter()
+            .map(|file| {
+                let state = SaveState::from_file(&file.path)
+                    .inspect_err(|error| log::error!("Error reading save {}: {error}", file.name))
+                    .ok();
+
+                SaveSlot { file, state }
+            })
+            .collect(),
+    )
+}
+
+fn list_save_files() -> Option<Vec<saves::SaveFile>> {
+    match saves::list_save_files() {
+        Ok(files) if files.is_empty() => None,
+        Ok(files) => Some(files),
+        Err(error) => {
+            log::error!("Error listing save files: {error}");
+            None
+        }
+    }
+}
+
+/// Shows what can be done with a save and does it. Returns the ID of the save to select when the
+/// list of saves has changed.
+fn manage_save(cpu: &mut Cpu, save_file: &saves::SaveFile) -> Option<String> {
+    const DELETE: usize = 0;
+    const RENAME: usize = 1;
+    const DUPLICATE: usize = 2;
+    const EXPORT: usize = 3;
+
+    let choice = choose_option(cpu, &["DELETE", "RENAME", "DUPLICATE", "EXPORT"])?;
+
+    let result = match choice {
+        DELETE => {
+            if !confirm(cpu, &["Delete", &format!("{}?", save_file.name)]) {
+                return None;
+            }
+
+            saves::delete_save(save_file).map(|()| save_file.id.clone())
+        }
+        RENAME => {
+            let name = ask_save_name(cpu, &save_file.name)?;
+            saves::rename_save(save_file, &name).map(|()| save_file.id.clone())
+        }
+        DUPLICATE => saves::duplicate_save(save_file).map(|copy| copy.id),
+        EXPORT => {
+            export_save(cpu, save_file);
+            return None;
+        }
+        n => unreachable!("Unknown save option: {n}"),
+    };
+
+    match result {
+        Ok(id) => {
+            cpu.call(0x2009); // PlayClickSFX
+            Some(id)
+        }
+        Err(error) => {
+            log::error!("Error managing save {}: {error}", save_file.name);
+            cpu.play_sfx(Sfx::new(0x3c, 0x497d)); // Sfx_Wrong
+            None
+        }
+    }
+}
+
+/// A menu in the top right corner, returning the chosen option or `None` if it was cancelled.
+fn choose_option(cpu: &mut Cpu, options: &[&str]) -> Option<usize> {
+    let width = options.iter().map(|o| o.len() as u8).max().unwrap_or(0) + 2;
+
+    run_menu(
+        cpu,
+        coord!(SCREEN_WIDTH - width - 2, 0),
+        options,
+        false,
+        |_| {},
+    )
+}
+
+/// A menu in a box at `xy`, returning the chosen option or `None` if it was cancelled.
+/// `each_frame` runs while waiting for input.
+fn run_menu(
+    cpu: &mut Cpu,
+    xy: u16,
+    options: &[&str],
+    wrap: bool,
+    mut each_frame: impl FnMut(&mut Cpu),
+) -> Option<usize> {
+    let width = options.iter().map(|o| o.len() as u8).max().unwrap_or(0) + 2;
+
+    let mut selected = 0;
+
+    loop {
+        cpu.set_hl(xy);
+        cpu.b = options.len() as u8;
+        cpu.c = width;
+        cpu.call(0x0fe8); // Textbox
+
+        for (i, option) in options.iter().enumerate() {
+            let xy = coord!(1, 1 + i as u8, xy);
+            cpu.write_byte(xy, if i == selected { 0xed } else { 0x7f }); // ▶
+            place_string(cpu, xy + 2, option);
+        }
+
+        each_frame(cpu);
+
+        cpu.call(0x0a57); // JoyTextDelay
+        cpu.call(0x1bdd); // GetMenuJoypad
+
+        let btns = JoypadButtons::from_bits(cpu.a).unwrap();
+
+        if btns.contains(JoypadButtons::UP) {
+            if selected > 0 {
+                selected -= 1;
+            } else if wrap {
+                selected = options.len() - 1;
+            }
+        }
+
+        if btns.contains(JoypadButtons::DOWN) {
+            if selected < options.len() - 1 {
+                selected += 1;
+            } else if wrap {
+                selected = 0;
+            }
+        }
+
+        if btns.contains(JoypadButtons::B) {
+            return None;
+        }
+
+        if btns.contains(JoypadButtons::A) {
+            cpu.call(0x2009); // PlayClickSFX
+            return Some(selected);
+        }
+
+        cpu.call(0x045a); // DelayFrame
+    }
+}
+
+/// Asks a yes or no question, with up to two `lines` of text at the bottom of the screen.
+fn confirm(cpu: &mut Cpu, lines: &[&str]) -> bool {
+    cpu.set_hl(coord!(0, SCREEN_HEIGHT - 4));
+    cpu.b = 2;
+    cpu.c = SCREEN_WIDTH - 2;
+    cpu.call(0x0fe8); // Textbox
+
+    for (i, line) in lines.iter().take(2).enumerate() {
+        let line: String = line.chars().take(SCREEN_WIDTH as usize - 2).collect();
+        place_string(cpu, coord!(1, SCREEN_HEIGHT - 3 + i as u8), &line);
+    }
+
+    cpu.call(0x1dcf); // YesNoBox
+
+    !cpu.flag(CpuFlag::C)
+}
+
+/// Asks for a new name for a save with the naming screen. `None` if the player didn't enter a
+/// name or kept the current one.
+fn ask_save_name(cpu: &mut Cpu, current: &str) -> Option<String> {
     cpu.b = NAME_BOX;
-    cpu.set_de(0xd47d); // wPlayerName
+    cpu.set_de(wram::STRING_BUFFER_1);
 
     macros::farcall::farcall(cpu, 0x04, 0x56c1); // NamingScreen
 
+    let name = read_name(cpu, wram::STRING_BUFFER_1);
+
+    restore_menu_screen(cpu);
+
+    if name.is_empty() || name == current {
+        return None;
+    }
+
+    Some(pick_save_name(cpu, name))
+}
+
+/// Offers a free name instead of `name` if another save already has it. Saves can share a
+/// name, but they're hard to tell apart in the list.
+fn pick_save_name(cpu: &mut Cpu, name: String) -> String {
+    if saves::save_is_free(&name) {
+        return name;
+    }
+
+    let free = saves::free_save_name(&name);
+
+    if confirm(
+        cpu,
+        &[&format!("{name} is taken."), &format!("Use {free}?")],
+    ) {
+        free
+    } else {
+        name
+    }
+}
+
+/// Reads a name entered in the naming screen, as the name of a save.
+fn read_name(cpu: &mut Cpu, addr: u16) -> String {
     let mut name = String::new();
 
     for i in 0..8 {
-        let c = cpu.read_byte(0xd47d + i);
-
-        match c {
-            0x50 => break,                                       // String terminator
-            0x80..=0x99 => name.push((c - 0x80 + b'A') as char), // Uppercase letters
-            0xa0..=0xb9 => name.push((c - 0xa0 + b'a') as char), // Lowercase letters
-            0xf6..=0xff => name.push((c - 0xf6 + b'0') as char), // Digits
-            _ => name.push('_'),                                 // Unknown character
+        let c = cpu.read_byte(addr + i);
+
+        if c == 0x50 {
+            break; // String terminator
+        }
+
+        name.push(tile_to_char(c).unwrap_or('?'));
+    }
+
+    name
+}
+
+/// Sets the screen up for the menus again, after the naming screen.
+fn restore_menu_screen(cpu: &mut Cpu) {
+    clear_tilemap_etc(cpu);
+
+    cpu.b = scgb_constants::SCGB_DIPLOMA;
+    cpu.call(0x3340); // GetSGBLayout
+
+    cpu.call(0x32f9); // SetDefaultBGPAndOBP
+}
+
+/// Asks for a `.sav` file from another emulator and imports it, returning the ID of the new
+/// save.
+fn import_save(cpu: &mut Cpu) -> Option<String> {
+    let path = tinyfiledialogs::open_file_dialog(
+        "Import save",
+        "",
+        Some((&["*.sav", "*.srm"], "Save files")),
+    )?;
+
+    match saves::import_sav(Path::new(&path)) {
+        Ok(save_file) => {
+            log::info!("Imported {path} as {}", save_file.name);
+            cpu.call(0x2009); // PlayClickSFX
+            Some(save_file.id)
+        }
+        Err(error) => {
+            log::error!("Error importing {path}: {error}");
+            cpu.play_sfx(Sfx::new(0x3c, 0x497d)); // Sfx_Wrong
+            None
+        }
+    }
+}
+
+/// Exports a save as a `.sav` file with a VBA-M clock footer, which most emulators can load.
+fn export_save(cpu: &mut Cpu, save_file: &saves::SaveFile) {
+    let Some(path) = tinyfiledialogs::save_file_dialog_with_filter(
+        "Export save",
+        &format!("{}.sav", save_file.name),
+        &["*.sav"],
+        "Save files",
+    ) else {
+        return;
+    };
+
+    let result = SaveState::from_file(&save_file.path)
+        .and_then(|state| state.export_sav(Path::new(&path), RtcFooter::VbaM));
+
+    match result {
+        Ok(()) => {
+            log::info!("Exported {} to {path}", save_file.name);
+            cpu.call(0x2009); // PlayClickSFX
+        }
+        Err(error) => {
+            log::error!("Error exporting {} to {path}: {error}", save_file.name);
+            cpu.play_sfx(Sfx::new(0x3c, 0x497d)); // Sfx_Wrong
         }
     }
+}
+
+fn main_menu_create_save(cpu: &mut Cpu) {
+    cpu.b = NAME_BOX;
+    cpu.set_de(0xd47d); // wPlayerName
+
+    macros::farcall::farcall(cpu, 0x04, 0x56c1); // NamingScreen
 
-    if name.is_empty() || !saves::save_is_free(&name) {
+    let name = read_name(cpu, 0xd47d); // wPlayerName
+
+    if name.is_empty() {
         cpu.play_sfx(Sfx::new(0x3c, 0x497d)); // Sfx_Wrong
         return;
     }
 
-    saves::create_save_dir().unwrap();
+    if !saves::save_is_free(&name) {
+        restore_menu_screen(cpu);
+    }
+
+    let name = pick_save_name(cpu, name);
 
-    cpu.set_save_path(saves::get_save_path(&name));
+    match saves::create_save(&name) {
+        Ok(path) => cpu.set_save_path(path),
+        Err(error) => {
+            log::error!("Error creating save {name}: {error}");
+            cpu.play_sfx(Sfx::new(0x3c, 0x497d)); // Sfx_Wrong
+            return;
+        }
+    }
 
     cpu.call(0x5ee0); // MainMenu_NewGame
 }
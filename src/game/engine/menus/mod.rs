@@ -1,3 +1,4 @@
 pub mod intro_menu;
 pub mod main_menu;
 pub mod save;
+pub mod sound_test;
@@ -11,11 +11,13 @@ use crate::{
         macros,
         ram::{sram, wram},
     },
+    save_state::checksum::SaveCopy,
+    trace,
 };
 
 pub fn change_box_save_game(cpu: &mut Cpu) {
     let target_box_idx = cpu.e;
-    log::info!("change_box_save_game({target_box_idx})");
+    log::info!(target: trace::SAVE, "change_box_save_game({target_box_idx})");
 
     cpu.set_hl(0x52a1); // ChangeBoxSaveText
     cpu.call(0x1d4f); // MenuTextbox
@@ -49,7 +51,7 @@ pub fn change_box_save_game(cpu: &mut Cpu) {
 }
 
 pub fn add_hall_of_fame_entry(cpu: &mut Cpu) {
-    log::debug!("add_hall_of_fame_entry()");
+    log::debug!(target: trace::SAVE, "add_hall_of_fame_entry()");
 
     cpu.a = sram::HALL_OF_FAME.0;
     cpu.call(0x2fcb); // OpenSRAM
@@ -116,7 +118,7 @@ pub fn ask_overwrite_save_file(cpu: &mut Cpu) {
 
     let result = ask_overwrite_save_file(cpu);
 
-    log::info!(
+    log::info!(target: trace::SAVE,
         "ask_overwrite_save_file() -> {}",
         if result { "refuse" } else { "accept" }
     );
@@ -126,7 +128,7 @@ pub fn ask_overwrite_save_file(cpu: &mut Cpu) {
 }
 
 pub fn save_game_data(cpu: &mut Cpu) {
-    log::debug!("save_game_data()");
+    log::debug!(target: trace::SAVE, "save_game_data()");
 
     cpu.borrow_wram_mut().set_save_file_exists(true);
 
@@ -165,14 +167,14 @@ pub fn save_game_data(cpu: &mut Cpu) {
 }
 
 pub fn save_box(cpu: &mut Cpu) {
-    log::info!("save_box({})", cpu.borrow_wram().cur_box());
+    log::info!(target: trace::SAVE, "save_box({})", cpu.borrow_wram().cur_box());
     get_box_address(cpu);
     save_box_address(cpu);
     cpu.pc = cpu.stack_pop(); // ret
 }
 
 pub fn try_load_save_data(cpu: &mut Cpu) {
-    log::debug!("try_load_save_data()");
+    log::debug!(target: trace::SAVE, "try_load_save_data()");
 
     cpu.borrow_wram_mut().set_save_file_exists(false);
     check_primary_save_file(cpu);
@@ -243,8 +245,12 @@ fn check_primary_save_file(cpu: &mut Cpu) {
     cpu.a = sram::CHECK_VALUE_BANK;
     cpu.call(0x2fcb); // OpenSRAM
 
+    // Unlike the original, the checksum is verified too, so that a damaged primary copy falls
+    // back to the backup copy instead of being loaded
     if cpu.read_byte(sram::CHECK_VALUE_1) == misc_constants::SAVE_CHECK_VALUE_1
         && cpu.read_byte(sram::CHECK_VALUE_2) == misc_constants::SAVE_CHECK_VALUE_2
+        && cpu.borrow_sram().calculate_checksum(SaveCopy::Primary)
+            == cpu.borrow_sram().stored_checksum(SaveCopy::Primary)
     {
         for i in 0..DEFAULT_OPTIONS.len() as u16 {
             let byte = cpu.read_byte(sram::OPTIONS + i);
@@ -258,7 +264,7 @@ fn check_primary_save_file(cpu: &mut Cpu) {
 }
 
 pub fn load_box(cpu: &mut Cpu) {
-    log::info!("load_box({})", cpu.borrow_wram().cur_box());
+    log::info!(target: trace::SAVE, "load_box({})", cpu.borrow_wram().cur_box());
     get_box_address(cpu);
     load_box_address(cpu);
     cpu.pc = cpu.stack_pop(); // ret
@@ -268,7 +274,7 @@ fn get_box_address(cpu: &mut Cpu) {
     let mut cur_box = cpu.borrow_wram().cur_box();
 
     if cur_box >= NUM_BOXES {
-        log::warn!("cur_box out of bounds: {cur_box}");
+        log::warn!(target: trace::SAVE, "cur_box out of bounds: {cur_box}");
         cpu.borrow_wram_mut().set_cur_box(0);
         cur_box = 0;
     }
@@ -288,7 +294,7 @@ pub fn save_box_address(cpu: &mut Cpu) {
     let bank = cpu.a as usize;
     let addr = cpu.de() as usize;
 
-    log::info!("save_box_address({bank:02x}:{addr:04x})");
+    log::info!(target: trace::SAVE, "save_box_address({bank:02x}:{addr:04x})");
 
     let offset = (bank * 0x2000) | (addr & 0x1fff);
     let sram = cpu.borrow_sram_mut();
@@ -307,7 +313,7 @@ pub fn load_box_address(cpu: &mut Cpu) {
     let bank = cpu.a as usize;
     let addr = cpu.de() as usize;
 
-    log::info!("load_box_address({bank:02x}:{addr:04x})");
+    log::info!(target: trace::SAVE, "load_box_address({bank:02x}:{addr:04x})");
 
     let offset = (bank * 0x2000) | (addr & 0x1fff);
     let sram = cpu.borrow_sram_mut();
@@ -0,0 +1,134 @@
+use crate::{
+    cpu::Cpu,
+    game::{
+        audio::{cry_pointers::CRIES, music::Music},
+        constants::input_constants::JoypadButtons,
+        home::text::place_string,
+        macros::{self, coords::coord},
+    },
+    trace,
+};
+
+// SFX_TWINKLE is the last sound effect
+const NUM_SFX: u16 = 0xcf;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Row {
+    Music,
+    Sfx,
+    Cry,
+}
+
+impl Row {
+    const ALL: [Row; 3] = [Row::Music, Row::Sfx, Row::Cry];
+
+    fn y(self) -> u8 {
+        match self {
+            Row::Music => 4,
+            Row::Sfx => 8,
+            Row::Cry => 11,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Row::Music => "MUSIC",
+            Row::Sfx => "SFX",
+            Row::Cry => "CRY",
+        }
+    }
+}
+
+/// Lets the player play any music track, sound effect or cry, reached from the main menu.
+pub fn sound_test(cpu: &mut Cpu) {
+    log::debug!(target: trace::GAME, "sound_test()");
+
+    let music: Vec<Music> = (0..=u8::MAX).filter_map(Music::from_id).collect();
+
+    let mut row = 0;
+    let mut selected = [0usize; 3];
+    let counts = [music.len(), NUM_SFX as usize, CRIES.len()];
+
+    loop {
+        cpu.set_hl(coord!(0, 0));
+        cpu.b = 16;
+        cpu.c = 18;
+        cpu.call(0x0fe8); // Textbox
+
+        place_string(cpu, coord!(2, 2), "SOUND TEST");
+
+        for (i, r) in Row::ALL.into_iter().enumerate() {
+            let id = match r {
+                Row::Music => music[selected[i]] as usize,
+                _ => selected[i],
+            };
+
+            cpu.write_byte(coord!(1, r.y()), if i == row { 0xed } else { 0x7f }); // ▶
+            place_string(cpu, coord!(2, r.y()), r.label());
+            place_string(cpu, coord!(10, r.y()), &format!("{id:02X}"));
+        }
+
+        let name = format!("{:?}", music[selected[0]]);
+        place_string(
+            cpu,
+            coord!(2, Row::Music.y() + 1),
+            &name[..name.len().min(17)],
+        );
+
+        place_string(cpu, coord!(2, 14), "A PLAY  B EXIT");
+        place_string(cpu, coord!(2, 15), "SELECT STOP");
+
+        cpu.call(0x0a57); // JoyTextDelay
+        cpu.call(0x1bdd); // GetMenuJoypad
+
+        let btns = JoypadButtons::from_bits(cpu.a).unwrap();
+
+        if btns.contains(JoypadButtons::UP) {
+            row = (row + Row::ALL.len() - 1) % Row::ALL.len();
+        }
+
+        if btns.contains(JoypadButtons::DOWN) {
+            row = (row + 1) % Row::ALL.len();
+        }
+
+        if btns.contains(JoypadButtons::LEFT) {
+            selected[row] = (selected[row] + counts[row] - 1) % counts[row];
+        }
+
+        if btns.contains(JoypadButtons::RIGHT) {
+            selected[row] = (selected[row] + 1) % counts[row];
+        }
+
+        if btns.contains(JoypadButtons::SELECT) {
+            cpu.set_de(0); // MUSIC_NONE
+            cpu.call(0x3b97); // PlayMusic
+        }
+
+        if btns.contains(JoypadButtons::A) {
+            match Row::ALL[row] {
+                Row::Music => {
+                    cpu.set_de(music[selected[row]] as u16);
+                    cpu.call(0x3b97); // PlayMusic
+                }
+                Row::Sfx => {
+                    cpu.set_de(selected[row] as u16);
+                    cpu.call(0x3c23); // PlaySFX
+                }
+                Row::Cry => {
+                    cpu.borrow_wram_mut().set_cry_pitch(0);
+                    cpu.borrow_wram_mut().set_cry_length(0x100);
+                    cpu.set_de(selected[row] as u16);
+                    macros::farcall::farcall(cpu, 0x3a, 0x4b79); // _PlayCry
+                }
+            }
+        }
+
+        if btns.contains(JoypadButtons::B) {
+            cpu.set_de(Music::MainMenu as u16);
+            cpu.call(0x3b97); // PlayMusic
+            return;
+        }
+
+        cpu.call(0x045a); // DelayFrame
+    }
+}
@@ -1,4 +1,5 @@
 pub mod battle;
+pub mod gfx;
 pub mod items;
 pub mod link;
 pub mod menus;
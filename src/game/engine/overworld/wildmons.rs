@@ -21,10 +21,11 @@ use crate::{
         },
         ram::wram,
     },
+    trace,
 };
 
 pub fn load_wild_mon_data(cpu: &mut Cpu) {
-    log::debug!("load_wild_mon_data()");
+    log::debug!(target: trace::OVERWORLD, "load_wild_mon_data()");
 
     let grass = grass_wildmon_lookup(cpu).map_or([0, 0, 0], |g| g.encounter_rates);
     let water = water_wildmon_lookup(cpu).map_or(0, |w| w.encounter_rate);
@@ -41,7 +42,7 @@ pub fn find_nest(cpu: &mut Cpu) {
     let region = Region::from(cpu.e);
     let species = PokemonSpecies::from(cpu.borrow_wram().named_object_index());
 
-    log::info!("find_nest({region:?}, {species:?})");
+    log::info!(target: trace::OVERWORLD, "find_nest({region:?}, {species:?})");
 
     cpu.a = 0;
     cpu.set_hl(macros::coords::coord!(0, 0));
@@ -325,18 +326,18 @@ pub fn random_unseen_wild_mon(cpu: &mut Cpu) {
         cpu.pc = cpu.stack_pop(); // ret
     }
 
-    log::debug!("random_unseen_wild_mon()");
+    log::debug!(target: trace::OVERWORLD, "random_unseen_wild_mon()");
 
     macros::farcall::farcall(cpu, 0x24, 0x4439); // GetCallerLocation
     let map = Map::from((cpu.b, cpu.c));
 
-    log::trace!("Caller location: {map:?}");
+    log::trace!(target: trace::OVERWORLD, "Caller location: {map:?}");
 
     let wildmons = look_up_wildmons_for_map(map, JOHTO_GRASS_WILD_MONS)
         .or(look_up_wildmons_for_map(map, KANTO_GRASS_WILD_MONS));
 
     let Some(wildmons) = wildmons else {
-        log::warn!("No matching wildmons found for map {map:?}");
+        log::warn!(target: trace::OVERWORLD, "No matching wildmons found for map {map:?}");
         return return_value(cpu, 1);
     };
 
@@ -383,12 +384,12 @@ pub fn random_unseen_wild_mon(cpu: &mut Cpu) {
 }
 
 pub fn random_phone_wild_mon(cpu: &mut Cpu) {
-    log::debug!("random_phone_wild_mon()");
+    log::debug!(target: trace::OVERWORLD, "random_phone_wild_mon()");
 
     macros::farcall::farcall(cpu, 0x24, 0x4439); // GetCallerLocation
     let map = Map::from((cpu.b, cpu.c));
 
-    log::trace!("Caller location: {map:?}");
+    log::trace!(target: trace::OVERWORLD, "Caller location: {map:?}");
 
     let wildmons = look_up_wildmons_for_map(map, JOHTO_GRASS_WILD_MONS)
         .or(look_up_wildmons_for_map(map, KANTO_GRASS_WILD_MONS))
@@ -353,7 +353,7 @@ pub fn fill_moves(cpu: &mut Cpu) {
         }
 
         for c in 0..NUM_MOVES {
-            if cpu.read_byte(cpu.de() + c as u16) == learn_move.into() {
+            if cpu.read_byte(cpu.de() + c as u16) == u8::from(learn_move) {
                 continue 'learn;
             }
         }
@@ -1,7 +1,10 @@
-use crate::cpu::{Cpu, CpuFlag};
+use crate::{
+    cpu::{Cpu, CpuFlag},
+    trace,
+};
 
 pub fn terminate_exp_bar_sound(cpu: &mut Cpu) {
-    log::debug!("terminate_exp_bar_sound()");
+    log::debug!(target: trace::AUDIO, "terminate_exp_bar_sound()");
 
     cpu.mmu.sound2.stop_sfx();
 
@@ -1,2 +1,3 @@
 pub mod audio;
 pub mod init;
+pub mod text;
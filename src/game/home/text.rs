@@ -0,0 +1,77 @@
+use crate::cpu::Cpu;
+
+/// Writes `text` to the tilemap at `xy`, for the characters that the game's font has.
+pub fn place_string(cpu: &mut Cpu, xy: u16, text: &str) {
+    for (i, c) in text.chars().enumerate() {
+        cpu.write_byte(xy + i as u16, char_to_tile(c).unwrap_or(UNKNOWN_TILE));
+    }
+}
+
+/// Every character that the game's font has, in the order of its tiles.
+const FONT_CHARS: &str =
+    " ABCDEFGHIJKLMNOPQRSTUVWXYZ():;[]abcdefghijklmnopqrstuvwxyzÄÖÜäöü'-?!.&é→▶▼♂¥×/,♀0123456789";
+
+// "?", for characters that the font doesn't have
+const UNKNOWN_TILE: u8 = 0xe6;
+
+/// The font tile of `c`, or `None` if the game can't show it.
+pub fn char_to_tile(c: char) -> Option<u8> {
+    let tile = match c {
+        'A'..='Z' => 0x80 + (c as u8 - b'A'),
+        'a'..='z' => 0xa0 + (c as u8 - b'a'),
+        '0'..='9' => 0xf6 + (c as u8 - b'0'),
+        '(' => 0x9a,
+        ')' => 0x9b,
+        ':' => 0x9c,
+        ';' => 0x9d,
+        '[' => 0x9e,
+        ']' => 0x9f,
+        'Ä' => 0xc0,
+        'Ö' => 0xc1,
+        'Ü' => 0xc2,
+        'ä' => 0xc3,
+        'ö' => 0xc4,
+        'ü' => 0xc5,
+        '\'' | '’' => 0xe0,
+        '-' => 0xe3,
+        '?' => UNKNOWN_TILE,
+        '!' => 0xe7,
+        '.' => 0xe8,
+        '&' => 0xe9,
+        'é' => 0xea,
+        '→' => 0xeb,
+        '▶' => 0xed,
+        '▼' => 0xee,
+        '♂' => 0xef,
+        '¥' => 0xf0,
+        '×' => 0xf1,
+        '/' => 0xf3,
+        ',' => 0xf4,
+        '♀' => 0xf5,
+        ' ' => 0x7f,
+        _ => return None,
+    };
+
+    Some(tile)
+}
+
+/// The character of a font tile, or `None` for tiles that don't have one, like the ones for
+/// "PK" and "MN".
+pub fn tile_to_char(tile: u8) -> Option<char> {
+    FONT_CHARS.chars().find(|&c| char_to_tile(c) == Some(tile))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_tile_to_char() {
+        for c in FONT_CHARS.chars() {
+            assert_eq!(char_to_tile(c).and_then(tile_to_char), Some(c));
+        }
+
+        assert_eq!(char_to_tile('ñ'), None);
+        assert_eq!(tile_to_char(0xe1), None); // PK
+    }
+}
@@ -1,3 +1,9 @@
+/// Implemented by the enums that `define_u8_enum!` defines.
+pub trait U8Enum: Copy + Into<u8> + std::str::FromStr<Err = String> {
+    /// Whether the value is one of the named variants rather than `Unknown`.
+    fn is_known(self) -> bool;
+}
+
 macro_rules! define_u8_enum {
     (
         $vis:vis enum $name:ident {
@@ -19,6 +25,12 @@ macro_rules! define_u8_enum {
             }
         }
 
+        impl $crate::game::macros::r#enum::U8Enum for $name {
+            fn is_known(self) -> bool {
+                !matches!(self, $name::Unknown(_))
+            }
+        }
+
         impl From<u8> for $name {
             fn from(value: u8) -> Self {
                 match value {
@@ -39,6 +51,32 @@ macro_rules! define_u8_enum {
                 }
             }
         }
+
+        impl std::str::FromStr for $name {
+            type Err = String;
+
+            /// Parses the name of a variant, ignoring case, spaces and punctuation, or a number.
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                fn normalize(s: &str) -> String {
+                    s.chars()
+                        .filter(char::is_ascii_alphanumeric)
+                        .map(|c| c.to_ascii_lowercase())
+                        .collect()
+                }
+
+                let wanted = normalize(s);
+
+                $(
+                    if wanted == normalize(stringify!($variant)) {
+                        return Ok($name::$variant);
+                    }
+                )*
+
+                s.parse::<u8>()
+                    .map(Self::from)
+                    .map_err(|_| format!("unknown {}: {s}", stringify!($name)))
+            }
+        }
     };
 
     // Helper arm to turn each variant into a unit `()`
@@ -49,6 +49,12 @@ impl BoxMonOwned {
         result
     }
 
+    pub fn from_box_mon(box_mon: BoxMonRef) -> Self {
+        Self {
+            data: box_mon.data.try_into().unwrap(),
+        }
+    }
+
     pub fn from_party_mon(party_mon: PartyMonRef) -> Self {
         Self {
             // PartyMon is a superset of BoxMon, so this is safe
@@ -76,6 +82,26 @@ impl BoxMonOwned {
         BoxMonMut::new(&mut self.data).set_exp(exp);
     }
 
+    pub fn set_hp_ev(&mut self, ev: u16) {
+        BoxMonMut::new(&mut self.data).set_hp_ev(ev);
+    }
+
+    pub fn set_attack_ev(&mut self, ev: u16) {
+        BoxMonMut::new(&mut self.data).set_attack_ev(ev);
+    }
+
+    pub fn set_defense_ev(&mut self, ev: u16) {
+        BoxMonMut::new(&mut self.data).set_defense_ev(ev);
+    }
+
+    pub fn set_speed_ev(&mut self, ev: u16) {
+        BoxMonMut::new(&mut self.data).set_speed_ev(ev);
+    }
+
+    pub fn set_special_ev(&mut self, ev: u16) {
+        BoxMonMut::new(&mut self.data).set_special_ev(ev);
+    }
+
     pub fn set_dvs(&mut self, dvs: u16) {
         BoxMonMut::new(&mut self.data).set_dvs(dvs);
     }
@@ -247,6 +273,26 @@ impl<'a> BoxMonMut<'a> {
         self.data[8..=10].copy_from_slice(&exp.to_be_bytes()[1..=3]);
     }
 
+    pub fn set_hp_ev(&mut self, ev: u16) {
+        self.data[11..=12].copy_from_slice(&ev.to_be_bytes());
+    }
+
+    pub fn set_attack_ev(&mut self, ev: u16) {
+        self.data[13..=14].copy_from_slice(&ev.to_be_bytes());
+    }
+
+    pub fn set_defense_ev(&mut self, ev: u16) {
+        self.data[15..=16].copy_from_slice(&ev.to_be_bytes());
+    }
+
+    pub fn set_speed_ev(&mut self, ev: u16) {
+        self.data[17..=18].copy_from_slice(&ev.to_be_bytes());
+    }
+
+    pub fn set_special_ev(&mut self, ev: u16) {
+        self.data[19..=20].copy_from_slice(&ev.to_be_bytes());
+    }
+
     pub fn set_dvs(&mut self, dvs: u16) {
         self.data[21..=22].copy_from_slice(&dvs.to_be_bytes());
     }
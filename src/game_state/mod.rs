@@ -75,17 +75,42 @@ impl GameState {
     pub fn set_byte(&mut self, addr: usize, value: u8) {
         self.data[addr] = value;
     }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
 }
 
 impl GameState {
+    /// Bit `n` is set when sound effect channel `5 + n` of the sound engine is playing.
+    pub fn sfx_channels_on(&self) -> u8 {
+        (0..4).fold(0, |mask, n| {
+            // wChannel5Flags1 + n * CHANNEL_STRUCT_LENGTH
+            let flags = self.data[0x01cc + n * 0x32];
+            mask | ((flags & 1) << n)
+        })
+    }
+
+    pub fn music_fade(&self) -> u8 {
+        self.data[0x02a7]
+    }
+
     pub fn cry_pitch(&self) -> i16 {
         i16::from_le_bytes([self.data[0x02b0], self.data[0x02b1]])
     }
 
+    pub fn set_cry_pitch(&mut self, value: i16) {
+        self.data[0x02b0..0x02b2].copy_from_slice(&value.to_le_bytes());
+    }
+
     pub fn cry_length(&self) -> u16 {
         u16::from_le_bytes([self.data[0x02b2], self.data[0x02b3]])
     }
 
+    pub fn set_cry_length(&mut self, value: u16) {
+        self.data[0x02b2..0x02b4].copy_from_slice(&value.to_le_bytes());
+    }
+
     pub fn set_map_music(&mut self, value: Option<Music>) {
         self.data[0x02c0] = value.map_or(0, |value| value as u8);
     }
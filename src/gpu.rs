@@ -1,10 +1,18 @@
-use std::{
-    cmp::Ordering,
-    sync::mpsc::{SendError, SyncSender},
-};
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+use crate::color::{ColorCorrection, ColorTable};
+use crate::error::{self, Error};
+use crate::frame_buffer::FrameWriter;
+use crate::texture_pack::{TexturePack, Tile};
+use crate::trace::{self, Span};
+use crate::vram_viewer::{DebugView, Layer, VramView};
 
 const VRAM_SIZE: usize = 0x4000;
 const VOAM_SIZE: usize = 0xA0;
+const OAM_SPRITES: usize = 40;
+// The hardware only draws this many sprites on each line
+const LINE_SPRITES: usize = 10;
 pub const SCREEN_W: usize = 160;
 pub const SCREEN_H: usize = 144;
 
@@ -52,16 +60,20 @@ pub struct Gpu {
     csprit_inc: bool,
     csprit_ind: u8,
     csprit: [[[u8; 3]; 4]; 8],
+    colors: ColorTable,
+    unlimited_sprites: bool,
+    debug_view: Option<Arc<DebugView>>,
+    texture_pack: Option<TexturePack>,
     vrambank: usize,
-    data: Vec<u8>,
+    frame: FrameWriter,
+    frame_span: Option<Span>,
     bgprio: [PrioType; SCREEN_W],
     pub interrupt: u8,
     hblanking: bool,
-    update_screen: SyncSender<Vec<u8>>,
 }
 
 impl Gpu {
-    pub fn new_cgb(update_screen: SyncSender<Vec<u8>>) -> Gpu {
+    pub fn new_cgb(frame: FrameWriter) -> Gpu {
         Gpu {
             mode: 0,
             modeclock: 0,
@@ -93,7 +105,8 @@ impl Gpu {
             pal1: [0; 4],
             vram: [0; VRAM_SIZE],
             voam: [0; VOAM_SIZE],
-            data: vec![0; SCREEN_W * SCREEN_H * 3],
+            frame,
+            frame_span: None,
             bgprio: [PrioType::Normal; SCREEN_W],
             interrupt: 0,
             cbgpal_inc: false,
@@ -102,9 +115,12 @@ impl Gpu {
             csprit_inc: false,
             csprit_ind: 0,
             csprit: [[[0u8; 3]; 4]; 8],
+            colors: ColorTable::new(),
+            unlimited_sprites: false,
+            debug_view: None,
+            texture_pack: None,
             vrambank: 0,
             hblanking: false,
-            update_screen,
         }
     }
 
@@ -190,6 +206,18 @@ impl Gpu {
         }
     }
 
+    pub fn vram(&self) -> &[u8] {
+        &self.vram
+    }
+
+    pub fn oam(&self) -> &[u8] {
+        &self.voam
+    }
+
+    pub fn vrambank(&self) -> usize {
+        self.vrambank
+    }
+
     pub fn rb(&self, a: u16) -> u8 {
         match a {
             0x8000..=0x9FFF => self.vram[(self.vrambank * 0x2000) | (a as usize & 0x1FFF)],
@@ -363,19 +391,74 @@ impl Gpu {
     }
 
     fn clear_screen(&mut self) {
-        for v in self.data.iter_mut() {
-            *v = 255;
-        }
+        self.frame.buffer_mut().fill(255);
         self.update_screen();
     }
 
     fn update_screen(&mut self) {
-        match self.update_screen.send(self.data.clone()) {
-            Ok(_) => {}
-            Err(SendError(_)) => {
-                panic!("Screen disconnected")
-            }
+        // End the span before publishing, so that frame pacing isn't counted as frame time
+        self.frame_span = None;
+
+        if self.frame.publish().is_err() {
+            error::raise(Error::ScreenDisconnected);
+        }
+
+        self.frame_span = Span::new(trace::FRAME, "frame");
+
+        self.colors.refresh();
+
+        if self
+            .debug_view
+            .as_ref()
+            .is_some_and(|view| view.take_dump_request())
+        {
+            self.vram_view().dump_in_background();
+        }
+    }
+
+    /// Draws the replacements from `pack` into an upscaled copy of the screen, which follows the
+    /// normal one in the frame buffer.
+    pub fn set_texture_pack(&mut self, pack: TexturePack) {
+        let needed = SCREEN_W * SCREEN_H * 3 * (1 + pack.scale() * pack.scale());
+
+        if self.frame.buffer().len() < needed {
+            log::error!("The frame buffer has no room for the upscaled screen");
+            return;
         }
+
+        self.texture_pack = Some(pack);
+    }
+
+    pub fn set_debug_view(&mut self, debug_view: Arc<DebugView>) {
+        self.debug_view = Some(debug_view);
+    }
+
+    fn shows(&self, layer: Layer) -> bool {
+        self.debug_view
+            .as_ref()
+            .is_none_or(|view| view.shows(layer))
+    }
+
+    fn vram_view(&self) -> VramView {
+        VramView::new(
+            &self.vram,
+            &self.voam,
+            &self.cbgpal,
+            &self.csprit,
+            self.tilebase,
+            self.sprite_size,
+            &self.colors,
+        )
+    }
+
+    /// Draws every sprite on a line instead of the first ten, which stops sprites from
+    /// disappearing when there are many on the same line.
+    pub fn set_unlimited_sprites(&mut self, unlimited: bool) {
+        self.unlimited_sprites = unlimited;
+    }
+
+    pub fn set_color_correction(&mut self, color_correction: Arc<ColorCorrection>) {
+        self.colors.set_shared(color_correction);
     }
 
     fn update_pal(&mut self) {
@@ -405,28 +488,121 @@ impl Gpu {
     }
 
     fn setcolor(&mut self, x: usize, color: u8) {
-        self.data[self.line as usize * SCREEN_W * 3 + x * 3] = color;
-        self.data[self.line as usize * SCREEN_W * 3 + x * 3 + 1] = color;
-        self.data[self.line as usize * SCREEN_W * 3 + x * 3 + 2] = color;
+        let baseidx = self.line as usize * SCREEN_W * 3 + x * 3;
+        self.frame.buffer_mut()[baseidx..baseidx + 3].fill(color);
     }
 
     fn setrgb(&mut self, x: usize, r: u8, g: u8, b: u8) {
-        // Gameboy Color RGB correction
-        // Taken from the Gambatte emulator
+        // Gameboy Color RGB correction, using the selected colour profile
         // assume r, g and b are between 0 and 1F
         let baseidx = self.line as usize * SCREEN_W * 3 + x * 3;
 
-        let r = r as u32;
-        let g = g as u32;
-        let b = b as u32;
+        let rgb = self.colors.get(r, g, b);
+        self.frame.buffer_mut()[baseidx..baseidx + 3].copy_from_slice(&rgb);
+    }
 
-        self.data[baseidx] = ((r * 13 + g * 2 + b) >> 1) as u8;
-        self.data[baseidx + 1] = ((g * 3 + b) << 1) as u8;
-        self.data[baseidx + 2] = ((r * 3 + g * 2 + b * 11) >> 1) as u8;
+    /// Reads the tile that `address` is in, for the texture pack.
+    fn tile(&self, address: u16, vram1: bool, palette: &[[u8; 3]; 4], sprite: bool) -> Tile {
+        let start = (address as usize & 0x1ff0) + if vram1 { 0x2000 } else { 0 };
+
+        Tile {
+            data: self.vram[start..start + 16].try_into().unwrap(),
+            palette: palette.map(|[r, g, b]| r as u16 | (g as u16) << 5 | (b as u16) << 10),
+            sprite,
+        }
+    }
+
+    /// Draws pixel `col`, `row` of `tile`, whose hash is `hash`, into the upscaled screen, using
+    /// its replacement if the texture pack has one.
+    fn sethd(
+        &mut self,
+        x: usize,
+        tile: &Tile,
+        hash: u64,
+        (col, row): (usize, usize),
+        xflip: bool,
+        yflip: bool,
+    ) {
+        let Some(pack) = &mut self.texture_pack else {
+            return;
+        };
+
+        let scale = pack.scale();
+        let rgb555 = |c: u16| ((c & 0x1f) as u8, (c >> 5 & 0x1f) as u8, (c >> 10) as u8);
+
+        let (r, g, b) = rgb555(tile.palette[tile.pixel(col, row)]);
+        let fallback = self.colors.get(r, g, b);
+
+        let hd_w = SCREEN_W * scale;
+        let hd = &mut self.frame.buffer_mut()[SCREEN_W * SCREEN_H * 3..];
+
+        let Some(texture) = pack.texture(hash) else {
+            pack.missing(hash, || {
+                (0..64)
+                    .flat_map(|i| {
+                        let colnr = tile.pixel(i % 8, i / 8);
+                        let (r, g, b) = rgb555(tile.palette[colnr]);
+                        let [r, g, b] = self.colors.get(r, g, b);
+                        [r, g, b, if tile.sprite && colnr == 0 { 0 } else { 255 }]
+                    })
+                    .collect()
+            });
+
+            for j in 0..scale {
+                let idx = ((self.line as usize * scale + j) * hd_w + x * scale) * 3;
+                for i in 0..scale {
+                    hd[idx + i * 3..idx + i * 3 + 3].copy_from_slice(&fallback);
+                }
+            }
+            return;
+        };
+
+        let tex_w = 8 * scale;
+
+        for j in 0..scale {
+            let ty = row * scale + if yflip { scale - 1 - j } else { j };
+
+            for i in 0..scale {
+                let tx = col * scale + if xflip { scale - 1 - i } else { i };
+                let texel = &texture[(ty * tex_w + tx) * 4..(ty * tex_w + tx) * 4 + 4];
+                let alpha = texel[3] as u16;
+
+                let idx = ((self.line as usize * scale + j) * hd_w + x * scale + i) * 3;
+                for c in 0..3 {
+                    // Sprites are blended with what's below them, tiles with their own colour
+                    let below = if tile.sprite {
+                        hd[idx + c]
+                    } else {
+                        fallback[c]
+                    } as u16;
+                    hd[idx + c] = ((texel[c] as u16 * alpha + below * (255 - alpha)) / 255) as u8;
+                }
+            }
+        }
+    }
+
+    /// Fills a pixel of the upscaled screen with a single colour.
+    fn fill_hd(&mut self, x: usize, rgb: [u8; 3]) {
+        let Some(pack) = &self.texture_pack else {
+            return;
+        };
+
+        let scale = pack.scale();
+        let hd_w = SCREEN_W * scale;
+        let hd = &mut self.frame.buffer_mut()[SCREEN_W * SCREEN_H * 3..];
+
+        for j in 0..scale {
+            let idx = ((self.line as usize * scale + j) * hd_w + x * scale) * 3;
+            for i in 0..scale {
+                hd[idx + i * 3..idx + i * 3 + 3].copy_from_slice(&rgb);
+            }
+        }
     }
 
     fn draw_bg(&mut self) {
         let drawbg = self.lcdc0;
+        let showbg = self.shows(Layer::Background);
+        let showwin = self.shows(Layer::Window);
 
         let wx_trigger = self.winx <= 166;
         let winy = if self.win_on && self.wy_trigger && wx_trigger {
@@ -445,11 +621,15 @@ impl Gpu {
         let bgy = self.scy.wrapping_add(self.line);
         let bgtiley = (bgy as u16 >> 3) & 31;
 
+        let mut hd_tile: Option<((u16, bool, usize), Tile, u64)> = None;
+
         for x in 0..SCREEN_W {
             let winx = -((self.winx as i32) - 7) + (x as i32);
             let bgx = self.scx as u32 + x as u32;
 
-            let (tilemapbase, tiley, tilex, pixely, pixelx) = if winy >= 0 && winx >= 0 {
+            let in_window = winy >= 0 && winx >= 0 && showwin;
+
+            let (tilemapbase, tiley, tilex, pixely, pixelx) = if in_window {
                 (
                     self.win_tilemap,
                     wintiley,
@@ -469,6 +649,13 @@ impl Gpu {
                 continue;
             };
 
+            if !in_window && !showbg {
+                self.bgprio[x] = PrioType::Color0;
+                self.setrgb(x, 0x1f, 0x1f, 0x1f);
+                self.fill_hd(x, self.colors.get(0x1f, 0x1f, 0x1f));
+                continue;
+            }
+
             let tilenr: u8 = self.rbvram0(tilemapbase + tiley * 32 + tilex);
 
             let (palnr, vram1, xflip, yflip, prio) = {
@@ -518,20 +705,43 @@ impl Gpu {
             let g = self.cbgpal[palnr][colnr][1];
             let b = self.cbgpal[palnr][colnr][2];
             self.setrgb(x, r, g, b);
+
+            if self.texture_pack.is_some() {
+                // Neighbouring pixels mostly come from the same tile, so it's only read and
+                // hashed again when that changes
+                let key = (tileaddress & 0x1ff0, vram1, palnr);
+
+                if hd_tile.as_ref().is_none_or(|(cached, ..)| *cached != key) {
+                    let tile = self.tile(tileaddress, vram1, &self.cbgpal[palnr], false);
+                    let hash = tile.hash();
+                    hd_tile = Some((key, tile, hash));
+                }
+
+                let (_, tile, hash) = hd_tile.as_ref().unwrap();
+                let col = if xflip { 7 - pixelx } else { pixelx } as usize;
+                let row = if yflip { 7 - pixely } else { pixely } as usize;
+                self.sethd(x, tile, *hash, (col, row), xflip, yflip);
+            }
         }
     }
 
     fn draw_sprites(&mut self) {
-        if !self.sprite_on {
+        if !self.sprite_on || !self.shows(Layer::Sprites) {
             return;
         }
 
         let line = self.line as i32;
         let sprite_size = self.sprite_size as i32;
 
-        let mut sprites_to_draw = [(0, 0, 0); 10];
+        let max_sprites = if self.unlimited_sprites {
+            OAM_SPRITES
+        } else {
+            LINE_SPRITES
+        };
+
+        let mut sprites_to_draw = [(0, 0, 0); OAM_SPRITES];
         let mut sidx = 0;
-        for index in 0..40 {
+        for index in 0..OAM_SPRITES as u8 {
             let spriteaddr = 0xFE00 + (index as u16) * 4;
             let spritey = self.rb(spriteaddr) as u16 as i32 - 16;
             if line < spritey || line >= spritey + sprite_size {
@@ -540,7 +750,7 @@ impl Gpu {
             let spritex = self.rb(spriteaddr + 1) as u16 as i32 - 8;
             sprites_to_draw[sidx] = (spritex, spritey, index);
             sidx += 1;
-            if sidx >= 10 {
+            if sidx >= max_sprites {
                 break;
             }
         }
@@ -576,6 +786,12 @@ impl Gpu {
                 (self.rbvram0(tileaddress), self.rbvram0(tileaddress + 1))
             };
 
+            let hd_tile = self.texture_pack.is_some().then(|| {
+                let tile = self.tile(tileaddress, c_vram1, &self.csprit[c_palnr], true);
+                let hash = tile.hash();
+                (tile, hash)
+            });
+
             'xloop: for x in 0..8 {
                 if spritex + x < 0 || spritex + x >= (SCREEN_W as i32) {
                     continue;
@@ -598,6 +814,19 @@ impl Gpu {
                 let g = self.csprit[c_palnr][colnr][1];
                 let b = self.csprit[c_palnr][colnr][2];
                 self.setrgb((spritex + x) as usize, r, g, b);
+
+                if let Some((tile, hash)) = &hd_tile {
+                    let col = if xflip { 7 - x } else { x } as usize;
+                    let row = tiley as usize % 8;
+                    self.sethd(
+                        (spritex + x) as usize,
+                        tile,
+                        *hash,
+                        (col, row),
+                        xflip,
+                        yflip,
+                    );
+                }
             }
         }
     }
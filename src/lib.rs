@@ -1,13 +1,32 @@
 #![allow(clippy::new_without_default)]
+#![allow(clippy::manual_is_multiple_of)]
 #![recursion_limit = "192"]
 
+pub use crate::error::Error;
+pub use crate::frame_buffer::{FrameReader, FrameWriter};
 pub use crate::gpu::{SCREEN_H, SCREEN_W};
 pub use crate::keypad::{KeypadEvent, KeypadKey};
+pub use crate::save_state::sav_file::RtcFooter;
+pub use crate::saves::{export_sav, import_sav};
 pub use crate::sound::{AudioPlayer, Sound};
+pub use crate::sound2::AudioMode;
 
+pub mod color;
 pub mod cpu;
+pub mod crash_report;
+pub mod error;
+pub mod filter;
+pub mod frame_buffer;
 pub mod game;
+pub mod mixer;
+pub mod save_editor;
+pub mod screenshot;
+pub mod settings;
+pub mod texture_pack;
+pub mod trace;
+pub mod vram_viewer;
 
+mod apu_player;
 mod game_state;
 mod gpu;
 mod keypad;
@@ -1,13 +1,22 @@
 use glium::glutin::platform::run_return::EventLoopExtRunReturn;
+use rustic_crystal::color::ColorCorrection;
 use rustic_crystal::cpu::Cpu;
-use rustic_crystal::KeypadEvent;
-use std::sync::mpsc::{self, Receiver, SyncSender};
+use rustic_crystal::filter::{FilterChain, Image};
+use rustic_crystal::mixer::{Mixer, TransitionSettings, VolumeSettings};
+use rustic_crystal::settings::{Enhancements, Settings};
+use rustic_crystal::texture_pack::TexturePack;
+use rustic_crystal::vram_viewer::{DebugView, Layer};
+use rustic_crystal::{AudioMode, Error, FrameWriter, KeypadEvent, RtcFooter};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver};
 use std::sync::{atomic::AtomicU64, Arc};
 use std::thread;
+use std::time::Duration;
 
 #[derive(Default)]
 struct RenderOptions {
     pub linear_interpolation: bool,
+    pub screenshot_requested: bool,
 }
 
 #[cfg(target_os = "windows")]
@@ -32,15 +41,46 @@ fn create_window_builder() -> glium::glutin::window::WindowBuilder {
         .with_title("Rustic Crystal")
 }
 
+const USAGE: &str = "Usage:
+  rustic-crystal
+  rustic-crystal import <file.sav>
+  rustic-crystal export <save name> <file.sav> [--rtc vba-m|bgb|none]";
+
 fn main() -> Result<(), &'static str> {
-    env_logger::init();
+    rustic_crystal::crash_report::init_logger();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    if !args.is_empty() {
+        return run_command(&args);
+    }
+
+    let trace_path = std::env::var_os("RUSTIC_CRYSTAL_TRACE").map(std::path::PathBuf::from);
+
+    if let Some(path) = &trace_path {
+        if let Err(error) = rustic_crystal::trace::start_recording(path) {
+            log::error!("Failed to start the trace at {}: {error}", path.display());
+        }
+    }
 
     let scale = 4;
 
     let render_delay = Arc::new(AtomicU64::new(16_743));
 
+    let mut settings = Settings::load();
+
+    let texture_pack = TexturePack::load(&settings.texture_pack).unwrap_or_else(|error| {
+        log::error!("Failed to load the texture pack: {error}");
+        None
+    });
+
+    // The upscaled screen with the replacement graphics follows the normal one
+    let hd_scale = texture_pack.as_ref().map(TexturePack::scale);
+    let screen_len = rustic_crystal::SCREEN_W * rustic_crystal::SCREEN_H * 3;
+    let hd_len = hd_scale.map_or(0, |scale| screen_len * scale * scale);
+
     let (sender1, receiver1) = mpsc::channel();
-    let (sender2, receiver2) = mpsc::sync_channel(1);
+    let (frame_writer, mut frame_reader) = rustic_crystal::frame_buffer::new(screen_len + hd_len);
 
     let mut eventloop = glium::glutin::event_loop::EventLoop::new();
     let window_builder = create_window_builder();
@@ -49,19 +89,26 @@ fn main() -> Result<(), &'static str> {
         glium::backend::glutin::Display::new(window_builder, context_builder, &eventloop).unwrap();
     set_window_size(display.gl_window().window(), scale);
 
-    let mut texture = glium::texture::texture2d::Texture2d::empty_with_format(
-        &display,
-        glium::texture::UncompressedFloatFormat::U8U8U8,
-        glium::texture::MipmapsOption::NoMipmap,
-        rustic_crystal::SCREEN_W as u32,
-        rustic_crystal::SCREEN_H as u32,
-    )
-    .unwrap();
+    let mut texture = create_texture(&display, rustic_crystal::SCREEN_W, rustic_crystal::SCREEN_H);
 
     let mut renderoptions = <RenderOptions as Default>::default();
 
-    let cputhread = thread::spawn(move || run_game(sender2, receiver1));
+    let mixer = Mixer::new(settings.volume);
+    let mut filters = FilterChain::new(settings.video);
+    let color_correction = ColorCorrection::new(&settings.color);
+    let debug_view = Arc::new(DebugView::new());
+
     let periodic = timer_periodic(render_delay.clone());
+    let frame_writer = frame_writer.with_pacer(periodic);
+    let options = GameOptions {
+        mixer: mixer.clone(),
+        transition: settings.transition,
+        color_correction: color_correction.clone(),
+        enhancements: settings.enhancements,
+        debug_view: debug_view.clone(),
+        texture_pack,
+    };
+    let cputhread = thread::spawn(move || run_game(frame_writer, receiver1, options));
 
     eventloop.run_return(move |ev, _evtarget, controlflow| {
         use glium::glutin::event::ElementState::{Pressed, Released};
@@ -115,6 +162,85 @@ fn main() -> Result<(), &'static str> {
                     } => {
                         renderoptions.linear_interpolation = !renderoptions.linear_interpolation;
                     }
+                    KeyboardInput {
+                        state: Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::F),
+                        ..
+                    } => {
+                        filters.settings.filter = filters.settings.filter.next();
+                        save_video_settings(&filters, &mut settings);
+                    }
+                    KeyboardInput {
+                        state: Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::G),
+                        ..
+                    } => {
+                        filters.settings.frame_blending = !filters.settings.frame_blending;
+                        save_video_settings(&filters, &mut settings);
+                    }
+                    KeyboardInput {
+                        state: Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::P),
+                        ..
+                    } => renderoptions.screenshot_requested = true,
+                    KeyboardInput {
+                        state: Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::V),
+                        ..
+                    } => debug_view.request_dump(),
+                    KeyboardInput {
+                        state: Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::F1),
+                        ..
+                    } => toggle_layer(&debug_view, Layer::Background),
+                    KeyboardInput {
+                        state: Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::F2),
+                        ..
+                    } => toggle_layer(&debug_view, Layer::Window),
+                    KeyboardInput {
+                        state: Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::F3),
+                        ..
+                    } => toggle_layer(&debug_view, Layer::Sprites),
+                    KeyboardInput {
+                        state: Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::C),
+                        ..
+                    } => {
+                        settings.color.profile = settings.color.profile.next();
+                        color_correction.update(&settings.color);
+
+                        log::info!("Colour profile: {:?}", settings.color.profile);
+
+                        if let Err(error) = settings.save() {
+                            log::error!("Failed to save settings: {error}");
+                        }
+                    }
+                    KeyboardInput {
+                        state: Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::M),
+                        ..
+                    } => change_volume(&mixer, &mut settings, |v| v.muted = !v.muted),
+                    KeyboardInput {
+                        state: Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::N),
+                        ..
+                    } => change_volume(&mixer, &mut settings, |v| v.music_muted = !v.music_muted),
+                    KeyboardInput {
+                        state: Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::Minus),
+                        ..
+                    } => change_volume(&mixer, &mut settings, |v| {
+                        v.master = f32::max(v.master - 0.1, 0.0)
+                    }),
+                    KeyboardInput {
+                        state: Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::Equals),
+                        ..
+                    } => change_volume(&mixer, &mut settings, |v| {
+                        v.master = f32::min(v.master + 0.1, 1.0)
+                    }),
                     KeyboardInput {
                         state: Pressed,
                         virtual_keycode: Some(glutinkey),
@@ -138,12 +264,40 @@ fn main() -> Result<(), &'static str> {
                 _ => (),
             },
             Event::MainEventsCleared => {
-                periodic.recv().unwrap();
+                match frame_reader.wait_latest(Duration::from_millis(100)) {
+                    Ok(Some(frame)) => {
+                        let (screen, hd_screen) = frame.data.split_at(screen_len);
+
+                        // The upscaled screen is already smooth, so the filters are only used
+                        // without a texture pack
+                        let image = match hd_scale {
+                            Some(scale) => Image {
+                                data: hd_screen,
+                                width: rustic_crystal::SCREEN_W * scale,
+                                height: rustic_crystal::SCREEN_H * scale,
+                            },
+                            None => filters.apply(
+                                screen,
+                                &frame.previous[..screen_len],
+                                rustic_crystal::SCREEN_W,
+                                rustic_crystal::SCREEN_H,
+                            ),
+                        };
+
+                        if std::mem::take(&mut renderoptions.screenshot_requested) {
+                            match rustic_crystal::screenshot::save(
+                                image.data,
+                                image.width,
+                                image.height,
+                            ) {
+                                Ok(path) => log::info!("Saved screenshot to {}", path.display()),
+                                Err(error) => log::error!("Failed to save screenshot: {error}"),
+                            }
+                        }
 
-                match receiver2.recv() {
-                    Ok(data) => {
-                        recalculate_screen(&display, &mut texture, &data, &renderoptions);
+                        recalculate_screen(&display, &mut texture, &image, &renderoptions);
                     }
+                    Ok(None) => {} // No new frame yet, keep showing the previous one
                     Err(..) => stop = true, // Remote end has hung-up
                 }
             }
@@ -154,11 +308,97 @@ fn main() -> Result<(), &'static str> {
         }
     });
 
-    let _ = cputhread.join();
+    let crash = match cputhread.join() {
+        Ok(crash) => crash,
+        Err(_) => Some("The emulator thread stopped unexpectedly".to_owned()),
+    };
+
+    if let Some(path) = trace_path {
+        if let Err(error) = rustic_crystal::trace::stop_recording() {
+            log::error!("Failed to write trace to {}: {error}", path.display());
+        }
+    }
+
+    if let Some(message) = crash {
+        tinyfiledialogs::message_box_ok(
+            "Rustic Crystal",
+            &message,
+            tinyfiledialogs::MessageBoxIcon::Error,
+        );
+    }
 
     Ok(())
 }
 
+/// Runs a command from the command line instead of the game.
+fn run_command(args: &[String]) -> Result<(), &'static str> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    match args[..] {
+        ["import", file] => match rustic_crystal::import_sav(Path::new(file)) {
+            Ok(save) => {
+                println!("Imported {file} as {}", save.name);
+                Ok(())
+            }
+            Err(error) => {
+                eprintln!("Failed to import {file}: {error}");
+                Err("Import failed")
+            }
+        },
+        ["export", name, file, ref options @ ..] => {
+            let footer = match options {
+                [] | ["--rtc", "vba-m"] => RtcFooter::VbaM,
+                ["--rtc", "bgb"] => RtcFooter::Bgb,
+                ["--rtc", "none"] => RtcFooter::None,
+                _ => {
+                    eprintln!("{USAGE}");
+                    return Err("Invalid arguments");
+                }
+            };
+
+            match rustic_crystal::export_sav(name, Path::new(file), footer) {
+                Ok(()) => {
+                    println!("Exported {name} to {file}");
+                    Ok(())
+                }
+                Err(error) => {
+                    eprintln!("Failed to export {name}: {error}");
+                    Err("Export failed")
+                }
+            }
+        }
+        _ => {
+            eprintln!("{USAGE}");
+            Err("Invalid arguments")
+        }
+    }
+}
+
+fn change_volume(mixer: &Mixer, settings: &mut Settings, f: impl FnOnce(&mut VolumeSettings)) {
+    settings.volume = mixer.update(f);
+
+    log::info!("Volume: {:?}", settings.volume);
+
+    if let Err(error) = settings.save() {
+        log::error!("Failed to save settings: {error}");
+    }
+}
+
+fn save_video_settings(filters: &FilterChain, settings: &mut Settings) {
+    settings.video = filters.settings;
+
+    log::info!("Video: {:?}", settings.video);
+
+    if let Err(error) = settings.save() {
+        log::error!("Failed to save settings: {error}");
+    }
+}
+
+fn toggle_layer(debug_view: &DebugView, layer: Layer) {
+    let shown = debug_view.toggle_layer(layer);
+    log::info!("{layer:?}: {}", if shown { "shown" } else { "hidden" });
+}
+
 fn glutin_to_keypad(
     key: glium::glutin::event::VirtualKeyCode,
 ) -> Option<rustic_crystal::KeypadKey> {
@@ -176,14 +416,34 @@ fn glutin_to_keypad(
     }
 }
 
+fn create_texture(
+    display: &glium::Display,
+    width: usize,
+    height: usize,
+) -> glium::texture::texture2d::Texture2d {
+    glium::texture::texture2d::Texture2d::empty_with_format(
+        display,
+        glium::texture::UncompressedFloatFormat::U8U8U8,
+        glium::texture::MipmapsOption::NoMipmap,
+        width as u32,
+        height as u32,
+    )
+    .unwrap()
+}
+
 fn recalculate_screen(
     display: &glium::Display,
     texture: &mut glium::texture::texture2d::Texture2d,
-    datavec: &[u8],
+    image: &Image,
     renderoptions: &RenderOptions,
 ) {
     use glium::Surface;
 
+    // The filters change the size of the image
+    if (texture.width(), texture.height()) != (image.width as u32, image.height as u32) {
+        *texture = create_texture(display, image.width, image.height);
+    }
+
     let interpolation_type = if renderoptions.linear_interpolation {
         glium::uniforms::MagnifySamplerFilter::Linear
     } else {
@@ -191,17 +451,17 @@ fn recalculate_screen(
     };
 
     let rawimage2d = glium::texture::RawImage2d {
-        data: std::borrow::Cow::Borrowed(datavec),
-        width: rustic_crystal::SCREEN_W as u32,
-        height: rustic_crystal::SCREEN_H as u32,
+        data: std::borrow::Cow::Borrowed(image.data),
+        width: image.width as u32,
+        height: image.height as u32,
         format: glium::texture::ClientFormat::U8U8U8,
     };
     texture.write(
         glium::Rect {
             left: 0,
             bottom: 0,
-            width: rustic_crystal::SCREEN_W as u32,
-            height: rustic_crystal::SCREEN_H as u32,
+            width: image.width as u32,
+            height: image.height as u32,
         },
         rawimage2d,
     );
@@ -222,10 +482,70 @@ fn recalculate_screen(
     target.finish().unwrap();
 }
 
-fn run_game(update_screen: SyncSender<Vec<u8>>, keypad_events: Receiver<KeypadEvent>) {
-    Cpu::new_cgb(None, update_screen, keypad_events)
-        .unwrap()
-        .call(0x0100)
+/// Everything the game thread shares with the frontend, or needs from the settings.
+struct GameOptions {
+    mixer: Arc<Mixer>,
+    transition: TransitionSettings,
+    color_correction: Arc<ColorCorrection>,
+    enhancements: Enhancements,
+    debug_view: Arc<DebugView>,
+    texture_pack: Option<TexturePack>,
+}
+
+/// Runs the game, returning a message to show the player if it crashed.
+fn run_game(
+    screen: FrameWriter,
+    keypad_events: Receiver<KeypadEvent>,
+    options: GameOptions,
+) -> Option<String> {
+    let mut cpu = Cpu::new_cgb(None, screen, keypad_events).unwrap();
+    cpu.set_color_correction(options.color_correction);
+    cpu.set_unlimited_sprites(options.enhancements.unlimited_sprites);
+    cpu.set_debug_view(options.debug_view);
+
+    if let Some(pack) = options.texture_pack {
+        cpu.set_texture_pack(pack);
+    }
+
+    cpu.set_mixer(options.mixer);
+    cpu.set_music_transition(options.transition);
+    cpu.set_audio_mode(audio_mode());
+
+    let error = match cpu.run() {
+        Ok(()) | Err(Error::ScreenDisconnected) => return None,
+        Err(error) => error,
+    };
+
+    log::error!("The game crashed: {error}");
+
+    let report = cpu.crash_report(&error);
+
+    // Drop the CPU, and with it the screen, so that the window closes before the dialog is shown
+    drop(cpu);
+
+    Some(match report.write() {
+        Ok(path) => format!(
+            "The game crashed: {}\n\nA crash report has been saved to {}",
+            report.error(),
+            path.display()
+        ),
+        Err(write_error) => {
+            log::error!("Failed to write crash report: {write_error}");
+            format!("The game crashed: {}", report.error())
+        }
+    })
+}
+
+fn audio_mode() -> AudioMode {
+    match std::env::var("RUSTIC_CRYSTAL_AUDIO").as_deref() {
+        Ok("hardware") => AudioMode::Hardware,
+        Ok("soundtrack") => AudioMode::Soundtrack,
+        Ok(other) => {
+            log::warn!("Unknown audio mode {other:?}, expected \"hardware\" or \"soundtrack\"");
+            AudioMode::detect()
+        }
+        Err(_) => AudioMode::detect(),
+    }
 }
 
 fn timer_periodic(delay: Arc<AtomicU64>) -> Receiver<()> {
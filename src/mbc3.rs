@@ -1,5 +1,7 @@
+use crate::error::{self, Error};
 use crate::rom::ROM;
 use crate::save_state::SaveState;
+use crate::saves;
 
 use std::{path::PathBuf, time};
 
@@ -100,7 +102,12 @@ impl MBC3 {
 
     pub fn save_to_disk(&mut self) {
         if let Some(ref path) = self.savepath {
-            self.ram.write_to_file(path).unwrap();
+            if let Err(source) = saves::write_save(path, &self.ram) {
+                error::raise(Error::Save {
+                    path: path.clone(),
+                    source,
+                });
+            }
         }
     }
 
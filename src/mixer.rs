@@ -0,0 +1,286 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use rodio::{ChannelCount, Sample, SampleRate, Source};
+use serde::{Deserialize, Serialize};
+
+// How much the music is turned down while a cry plays
+const DUCK_GAIN: f32 = 0.4;
+
+// Bus gains are re-read every this many samples, and approached gradually to avoid clicks
+const GAIN_REFRESH: usize = 64;
+const GAIN_STEP: f32 = 1.0 / 2048.0;
+
+/// What a sound is played as, each with its own volume.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Bus {
+    Music,
+    Sfx,
+    Cries,
+    /// The emulated APU. In hardware mode it plays the music along with the sound effects, so it
+    /// follows the music volume, and otherwise it only plays jingles, like a sound effect.
+    Apu,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VolumeSettings {
+    pub master: f32,
+    pub music: f32,
+    pub sfx: f32,
+    pub cries: f32,
+    pub muted: bool,
+    pub music_muted: bool,
+}
+
+impl Default for VolumeSettings {
+    fn default() -> Self {
+        VolumeSettings {
+            master: 1.0,
+            music: 1.0,
+            sfx: 1.0,
+            cries: 1.0,
+            muted: false,
+            music_muted: false,
+        }
+    }
+}
+
+impl VolumeSettings {
+    fn gain(&self, bus: Bus) -> f32 {
+        if self.muted {
+            return 0.0;
+        }
+
+        let bus = match bus {
+            Bus::Music if self.music_muted => 0.0,
+            Bus::Music => self.music,
+            Bus::Sfx => self.sfx,
+            Bus::Cries => self.cries,
+            Bus::Apu => unreachable!(),
+        };
+
+        self.master.clamp(0.0, 1.0) * bus.clamp(0.0, 1.0)
+    }
+}
+
+/// How the music changes from one track to the next.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MusicTransition {
+    /// Stop the previous track and start the next one right away.
+    Cut,
+    /// Fade out the previous track, then start the next one.
+    FadeOut,
+    /// Fade out the previous track while fading in the next one.
+    Crossfade,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransitionSettings {
+    pub music: MusicTransition,
+    pub duration_ms: u32,
+}
+
+impl Default for TransitionSettings {
+    fn default() -> Self {
+        TransitionSettings {
+            music: MusicTransition::Crossfade,
+            duration_ms: 500,
+        }
+    }
+}
+
+impl TransitionSettings {
+    /// The duration in emulated frames, which run at about 59.7 per second.
+    pub fn frames(&self) -> u32 {
+        self.duration_ms * 597 / 10_000
+    }
+}
+
+/// Volume control shared between the game, which plays sounds, and the frontend, which changes
+/// the settings.
+pub struct Mixer {
+    settings: Mutex<VolumeSettings>,
+    // f32 bits, indexed by `Bus`
+    gains: [AtomicU32; 3],
+    playing_cries: AtomicUsize,
+    apu_plays_music: AtomicBool,
+}
+
+impl Mixer {
+    pub fn new(settings: VolumeSettings) -> Arc<Mixer> {
+        let mixer = Mixer {
+            settings: Mutex::new(settings),
+            gains: Default::default(),
+            playing_cries: AtomicUsize::new(0),
+            apu_plays_music: AtomicBool::new(false),
+        };
+
+        mixer.store_gains(&settings);
+
+        Arc::new(mixer)
+    }
+
+    pub fn settings(&self) -> VolumeSettings {
+        *self.settings.lock().unwrap()
+    }
+
+    /// Changes the settings, returning the new ones so that they can be persisted.
+    pub fn update(&self, f: impl FnOnce(&mut VolumeSettings)) -> VolumeSettings {
+        let mut settings = self.settings.lock().unwrap();
+        f(&mut settings);
+        self.store_gains(&settings);
+        *settings
+    }
+
+    fn store_gains(&self, settings: &VolumeSettings) {
+        for bus in [Bus::Music, Bus::Sfx, Bus::Cries] {
+            self.gains[bus as usize].store(settings.gain(bus).to_bits(), Ordering::Relaxed);
+        }
+    }
+
+    /// Sets whether the emulated APU plays the music, in hardware mode, or only jingles.
+    pub fn set_apu_plays_music(&self, plays_music: bool) {
+        self.apu_plays_music.store(plays_music, Ordering::Relaxed);
+    }
+
+    fn gain(&self, bus: Bus) -> f32 {
+        let bus = match bus {
+            Bus::Apu if self.apu_plays_music.load(Ordering::Relaxed) => Bus::Music,
+            Bus::Apu => Bus::Sfx,
+            bus => bus,
+        };
+
+        let gain = f32::from_bits(self.gains[bus as usize].load(Ordering::Relaxed));
+
+        if bus == Bus::Music && self.playing_cries.load(Ordering::Relaxed) > 0 {
+            gain * DUCK_GAIN
+        } else {
+            gain
+        }
+    }
+
+    /// Routes `source` through `bus`.
+    pub fn source<S: Source>(self: &Arc<Self>, bus: Bus, source: S) -> BusSource<S> {
+        if bus == Bus::Cries {
+            self.playing_cries.fetch_add(1, Ordering::Relaxed);
+        }
+
+        BusSource {
+            gain: self.gain(bus),
+            target: self.gain(bus),
+            until_refresh: GAIN_REFRESH,
+            mixer: self.clone(),
+            bus,
+            source: Some(source),
+        }
+    }
+}
+
+pub struct BusSource<S> {
+    mixer: Arc<Mixer>,
+    bus: Bus,
+    gain: f32,
+    target: f32,
+    until_refresh: usize,
+    // Taken when the source ends, so that a cry stops ducking the music right away
+    source: Option<S>,
+}
+
+impl<S> BusSource<S> {
+    fn finish(&mut self) {
+        if self.source.take().is_some() && self.bus == Bus::Cries {
+            self.mixer.playing_cries.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+impl<S: Source> Iterator for BusSource<S> {
+    type Item = Sample;
+
+    fn next(&mut self) -> Option<Sample> {
+        let Some(sample) = self.source.as_mut()?.next() else {
+            self.finish();
+            return None;
+        };
+
+        self.until_refresh -= 1;
+
+        if self.until_refresh == 0 {
+            self.until_refresh = GAIN_REFRESH;
+            self.target = self.mixer.gain(self.bus);
+        }
+
+        if self.gain < self.target {
+            self.gain = f32::min(self.gain + GAIN_STEP, self.target);
+        } else if self.gain > self.target {
+            self.gain = f32::max(self.gain - GAIN_STEP, self.target);
+        }
+
+        Some(sample * self.gain)
+    }
+}
+
+impl<S: Source> Source for BusSource<S> {
+    fn current_span_len(&self) -> Option<usize> {
+        self.source.as_ref()?.current_span_len()
+    }
+
+    fn channels(&self) -> ChannelCount {
+        self.source.as_ref().map_or(1, Source::channels)
+    }
+
+    fn sample_rate(&self) -> SampleRate {
+        self.source.as_ref().map_or(44100, Source::sample_rate)
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.as_ref()?.total_duration()
+    }
+}
+
+impl<S> Drop for BusSource<S> {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gain() {
+        let mixer = Mixer::new(VolumeSettings {
+            master: 0.5,
+            cries: 0.5,
+            ..Default::default()
+        });
+
+        assert_eq!(mixer.gain(Bus::Music), 0.5);
+        assert_eq!(mixer.gain(Bus::Cries), 0.25);
+
+        let cry = mixer.source(Bus::Cries, rodio::source::Zero::new(1, 44100));
+        assert_eq!(mixer.gain(Bus::Music), 0.5 * DUCK_GAIN);
+        drop(cry);
+        assert_eq!(mixer.gain(Bus::Music), 0.5);
+
+        mixer.update(|settings| settings.music_muted = true);
+        assert_eq!(mixer.gain(Bus::Music), 0.0);
+        assert_eq!(mixer.gain(Bus::Sfx), 0.5);
+        assert_eq!(mixer.gain(Bus::Apu), 0.5);
+
+        mixer.set_apu_plays_music(true);
+        assert_eq!(mixer.gain(Bus::Apu), 0.0);
+
+        mixer.update(|settings| settings.muted = true);
+        assert_eq!(mixer.gain(Bus::Sfx), 0.0);
+    }
+}
@@ -1,12 +1,15 @@
-use std::sync::mpsc::{Receiver, SyncSender};
+use std::sync::mpsc::Receiver;
 
+use crate::crash_report::Snapshot;
+use crate::error::{self, Error};
+use crate::frame_buffer::FrameWriter;
 use crate::game_state::GameState;
 use crate::gpu::Gpu;
 use crate::keypad::{Keypad, KeypadEvent};
 use crate::mbc3::MBC3;
 use crate::serial::{Serial, SerialCallback};
 use crate::sound::Sound;
-use crate::sound2::Sound2;
+use crate::sound2::{AudioMode, Jingle, Sound2};
 use crate::timer::Timer;
 use crate::StrResult;
 
@@ -37,6 +40,8 @@ pub struct Mmu<'a> {
     pub gpu: Gpu,
     pub sound: Option<Sound>,
     pub sound2: Sound2,
+    jingle_channels: u8,
+    music_fade: u8,
     hdma_status: DmaType,
     hdma_src: u16,
     hdma_dst: u16,
@@ -51,7 +56,7 @@ pub struct Mmu<'a> {
 impl<'a> Mmu<'a> {
     pub fn new_cgb(
         serial_callback: Option<SerialCallback<'a>>,
-        update_screen: SyncSender<Vec<u8>>,
+        screen: FrameWriter,
         keypad_events: Receiver<KeypadEvent>,
     ) -> StrResult<Mmu<'a>> {
         let serial = match serial_callback {
@@ -68,9 +73,11 @@ impl<'a> Mmu<'a> {
             serial,
             timer: Timer::new(),
             keypad: Keypad::new(keypad_events),
-            gpu: Gpu::new_cgb(update_screen),
+            gpu: Gpu::new_cgb(screen),
             sound: None,
             sound2: Sound2::new(),
+            jingle_channels: 0,
+            music_fade: 0,
             mbc: MBC3::new(),
             gbspeed: GbSpeed::Single,
             speed_switch_req: false,
@@ -140,9 +147,18 @@ impl<'a> Mmu<'a> {
         self.timer.interrupt = 0;
 
         self.gpu.do_cycle(gputicks);
+
+        if self.gpu.interrupt & 0x01 == 0x01 {
+            self.update_music();
+        }
+
         self.intf |= self.gpu.interrupt;
         self.gpu.interrupt = 0;
 
+        if self.sound2.jingle() != Jingle::None || self.jingle_channels != 0 {
+            self.update_jingle();
+        }
+
         if let Some(sound) = self.sound.as_mut() {
             sound.do_cycle(gputicks);
         }
@@ -212,6 +228,7 @@ impl<'a> Mmu<'a> {
             0xFF04..=0xFF07 => self.timer.wb(address, value),
             0xFF10..=0xFF3F => self.sound.as_mut().map_or((), |s| s.wb(address, value)),
             0xFF46 => self.oamdma(value),
+            #[allow(clippy::collapsible_match)]
             0xFF4D => {
                 if value & 0x1 == 0x1 {
                     self.speed_switch_req = true;
@@ -251,6 +268,65 @@ impl<'a> Mmu<'a> {
         self.speed_switch_req = false;
     }
 
+    pub fn set_audio_mode(&mut self, mode: AudioMode) {
+        let mut sound = Sound::new_cgb(Box::new(self.sound2.set_mode(mode)));
+
+        if mode == AudioMode::Soundtrack {
+            sound.set_channel_mask(0); // Only unmuted for jingles
+        }
+
+        self.sound = Some(sound);
+    }
+
+    /// Called every frame to follow the sound engine's music fades, and advance our own.
+    fn update_music(&mut self) {
+        let fade = self.wram.music_fade();
+
+        if fade != self.music_fade {
+            self.music_fade = fade;
+            self.sound2.rom_fade(fade);
+        }
+
+        self.sound2.update();
+    }
+
+    /// Lets the jingle's channels through the emulated APU, and resumes the music once the sound
+    /// engine has stopped them.
+    fn update_jingle(&mut self) {
+        let channels = self.wram.sfx_channels_on();
+
+        if channels != 0 {
+            self.sound2.jingle_started();
+        } else if self.sound2.jingle() == Jingle::Playing {
+            self.sound2.end_jingle();
+        }
+
+        let channels = match self.sound2.jingle() {
+            Jingle::None => 0,
+            _ => channels,
+        };
+
+        if channels != self.jingle_channels {
+            self.jingle_channels = channels;
+
+            if let Some(sound) = self.sound.as_mut() {
+                sound.set_channel_mask(channels);
+            }
+        }
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            wram: self.wram.as_bytes().to_vec(),
+            vram: self.gpu.vram().to_vec(),
+            oam: self.gpu.oam().to_vec(),
+            hram: self.zram.to_vec(),
+            rombank: self.mbc.rombank,
+            wrambank: self.wrambank,
+            vrambank: self.gpu.vrambank(),
+        }
+    }
+
     pub fn borrow_wram(&self) -> &GameState {
         &self.wram
     }
@@ -298,7 +374,7 @@ impl<'a> Mmu<'a> {
                 let src = ((self.hdma[0] as u16) << 8) | (self.hdma[1] as u16);
                 let dst = ((self.hdma[2] as u16) << 8) | (self.hdma[3] as u16) | 0x8000;
                 if !(src <= 0x7FF0 || (0xA000..=0xDFF0).contains(&src)) {
-                    panic!("HDMA transfer with illegal start address {src:04X}");
+                    error::raise(Error::IllegalHdmaSource(src));
                 }
 
                 self.hdma_src = src;
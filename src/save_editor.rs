@@ -0,0 +1,1163 @@
+//! A command-line editor for saves, run by the `rustic-crystal-save` binary. It prints what's in
+//! a save, changes it, and compares two saves, so that test fixtures can be prepared without a
+//! hex editor.
+
+use std::{
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    game::constants::{
+        item_constants::{Item, NUM_TMS},
+        item_data_constants::MAX_ITEM_STACK,
+        move_constants::Move,
+        pokemon_constants::PokemonSpecies,
+        pokemon_data_constants::{MAX_LEVEL, MIN_LEVEL, MONS_PER_BOX, NUM_BOXES, PARTY_LENGTH},
+        text_constants::{MON_NAME_LENGTH, NAME_LENGTH},
+    },
+    game_state::{
+        box_mon::{BoxMonOwned, BoxMonRef},
+        mon_list::MonListEntry,
+        moveset::Moveset,
+        party_mon::PartyMonOwned,
+    },
+    save_state::{
+        checksum::SaveCopy,
+        determinant_values::DeterminantValues,
+        game_data::{ItemList, MAX_MONEY},
+        pk2, showdown,
+        string::PokeString,
+        trade, SaveState,
+    },
+    saves::{self, PendingSave},
+};
+
+const USAGE: &str = "Usage:
+  rustic-crystal-save show <save> [--json]
+  rustic-crystal-save diff <save> <other save> [--json]
+  rustic-crystal-save set-money <save> <amount>
+  rustic-crystal-save give-item <save> <item> [quantity] [--pocket items|balls|key-items|pc]
+  rustic-crystal-save edit-mon <save> <mon> [--level <level>] [--moves <move,...>]
+                               [--dvs <attack,defense,speed,special>] [--item <item>|none]
+  rustic-crystal-save move-mon <save> <mon> party|box<n>
+  rustic-crystal-save export-team <save> party|box<n>
+  rustic-crystal-save import-team <save> <team.txt> party|box<n>
+  rustic-crystal-save export-pk2 <save> <mon> <file.pk2>
+  rustic-crystal-save import-pk2 <save> <file.pk2> box<n>[:<n>]
+  rustic-crystal-save trade <save> party:<n> <other save> party:<n>
+  rustic-crystal-save fix-checksums <save>
+
+<save> is the path to a save file, or the name of a save in the game.
+<mon> is party:<n> or box<n>:<n>, counting from 1.";
+
+pub fn run(args: &[String]) -> Result<(), String> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    // Every command gets a function of its own, since the saves are large enough that keeping
+    // all of them in one stack frame overflows the stack in debug builds.
+    match args[..] {
+        ["show", save] => show(save, false),
+        ["show", save, "--json"] => show(save, true),
+        ["diff", a, b, ref options @ ..] => diff_saves(a, b, options),
+        ["set-money", save, amount] => set_money(save, amount),
+        ["give-item", save, item, ref options @ ..] => give(save, item, options),
+        ["edit-mon", save, mon, ref options @ ..] => edit_mon(save, mon, options),
+        ["move-mon", save, mon, to] => move_mon(save, mon, to),
+        ["export-team", save, list] => {
+            let state = open(save)?.1;
+            print!(
+                "{}",
+                showdown::format_team(&export_team(&state, parse_list(list)?))?
+            );
+            Ok(())
+        }
+        ["import-team", save, team, to] => import_team(save, team, to),
+        ["export-pk2", save, mon, file] => export_pk2(save, mon, file),
+        ["import-pk2", save, file, to] => import_pk2(save, file, to),
+        ["trade", save, mon, other_save, other_mon] => trade_mons(save, mon, other_save, other_mon),
+        ["fix-checksums", save] => fix_checksums(save),
+        _ => Err(USAGE.to_owned()),
+    }
+}
+
+fn show(save: &str, json: bool) -> Result<(), String> {
+    let summary = Summary::new(&open(save)?.1);
+
+    match json {
+        true => println!("{}", to_json(&summary)?),
+        false => print!("{}", summary.to_text()),
+    }
+
+    Ok(())
+}
+
+fn diff_saves(a: &str, b: &str, options: &[&str]) -> Result<(), String> {
+    let a = serde_json::to_value(Summary::new(&open(a)?.1)).map_err(|e| e.to_string())?;
+    let b = serde_json::to_value(Summary::new(&open(b)?.1)).map_err(|e| e.to_string())?;
+
+    let mut changes = Vec::new();
+    diff("", &a, &b, &mut changes);
+
+    match options {
+        [] if changes.is_empty() => println!("The saves are the same"),
+        [] => {
+            for change in changes {
+                println!("{}: {} -> {}", change.path, change.before, change.after);
+            }
+        }
+        ["--json"] => println!("{}", to_json(&changes)?),
+        _ => return Err(USAGE.to_owned()),
+    }
+
+    Ok(())
+}
+
+fn set_money(save: &str, amount: &str) -> Result<(), String> {
+    let (path, mut state) = open(save)?;
+
+    let amount = amount
+        .parse()
+        .ok()
+        .filter(|&amount| amount <= MAX_MONEY)
+        .ok_or_else(|| format!("The amount must be between 0 and {MAX_MONEY}"))?;
+
+    state.set_money(amount);
+    write(&path, &state)
+}
+
+fn give(save: &str, item: &str, options: &[&str]) -> Result<(), String> {
+    let (path, mut state) = open(save)?;
+    let item: Item = item.parse()?;
+
+    let (quantity, pocket) = match options {
+        [] => (1, None),
+        [quantity] => (parse_quantity(quantity)?, None),
+        ["--pocket", pocket] => (1, Some(pocket)),
+        [quantity, "--pocket", pocket] => (parse_quantity(quantity)?, Some(pocket)),
+        _ => return Err(USAGE.to_owned()),
+    };
+
+    give_item(&mut state, item, quantity, pocket.copied())?;
+    write(&path, &state)
+}
+
+fn edit_mon(save: &str, mon: &str, options: &[&str]) -> Result<(), String> {
+    let (path, mut state) = open(save)?;
+    let (list, index) = parse_mon(mon)?;
+    let edit = MonEdit::parse(options)?;
+
+    let mut taken = take_mon(&mut state, list, index)?;
+    edit.apply(&mut taken.mon);
+    put_mon(&mut state, list, Some(index), taken);
+
+    write(&path, &state)
+}
+
+fn move_mon(save: &str, mon: &str, to: &str) -> Result<(), String> {
+    let (path, mut state) = open(save)?;
+    let (from, index) = parse_mon(mon)?;
+    let to = parse_list(to)?;
+
+    if list_len(&state, to) >= to.capacity() {
+        return Err(format!("{to} is full"));
+    }
+
+    if from == MonLocation::Party && to != from && state.party().len() == 1 {
+        return Err("The party can't be left empty".to_owned());
+    }
+
+    let taken = take_mon(&mut state, from, index)?;
+    put_mon(&mut state, to, None, taken);
+
+    write(&path, &state)
+}
+
+fn import_team(save: &str, team: &str, to: &str) -> Result<(), String> {
+    let (path, mut state) = open(save)?;
+    let to = parse_list(to)?;
+
+    let text =
+        fs::read_to_string(team).map_err(|error| format!("Failed to read {team}: {error}"))?;
+    let sets = showdown::parse_team(&text).map_err(|error| format!("{team}: {error}"))?;
+
+    if list_len(&state, to) + sets.len() > to.capacity() {
+        return Err(format!("{to} doesn't have room for {} mons", sets.len()));
+    }
+
+    for set in sets {
+        let taken = TakenMon {
+            mon: set.to_box_mon(state.player_id()),
+            is_egg: false,
+            ot_name: state.player_name(),
+            nickname: set.nickname(),
+        };
+
+        put_mon(&mut state, to, None, taken);
+    }
+
+    write(&path, &state)
+}
+
+fn export_pk2(save: &str, mon: &str, file: &str) -> Result<(), String> {
+    let state = open(save)?.1;
+    let (list, index) = parse_mon(mon)?;
+    let missing = || format!("{list} has no mon {}", index + 1);
+
+    let data = match list {
+        MonLocation::Party => {
+            let party = state.party();
+            pk2::write_pk2(party.get(index).ok_or_else(missing)?)
+        }
+        MonLocation::Box(n) => {
+            let r#box = state.pc_box(n);
+            let (mon, is_egg, ot_name, nickname) =
+                entry_parts(r#box.get(index).ok_or_else(missing)?);
+            let mon = PartyMonOwned::from_box_mon(mon, is_egg);
+
+            pk2::write_pk2(match is_egg {
+                true => MonListEntry::Egg(mon.as_ref(), ot_name, nickname),
+                false => MonListEntry::Mon(mon.as_ref(), ot_name, nickname),
+            })
+        }
+    };
+
+    fs::write(file, data).map_err(|error| format!("Failed to write {file}: {error}"))
+}
+
+fn import_pk2(save: &str, file: &str, to: &str) -> Result<(), String> {
+    let (path, mut state) = open(save)?;
+
+    let (to, index) = match to.contains(':') {
+        true => parse_mon(to).map(|(list, index)| (list, Some(index)))?,
+        false => (parse_list(to)?, None),
+    };
+
+    if !matches!(to, MonLocation::Box(_)) {
+        return Err("Mons can only be imported into a box".to_owned());
+    }
+
+    if list_len(&state, to) >= to.capacity() {
+        return Err(format!("{to} is full"));
+    }
+
+    if index.is_some_and(|index| index > list_len(&state, to)) {
+        return Err(format!("{to} only has {} mons", list_len(&state, to)));
+    }
+
+    let data = fs::read(file).map_err(|error| format!("Failed to read {file}: {error}"))?;
+    let (mon, is_egg, ot_name, nickname) =
+        entry_parts(pk2::read_pk2(&data).map_err(|error| format!("{file}: {error}"))?);
+
+    let taken = TakenMon {
+        mon: BoxMonOwned::from_box_mon(mon),
+        is_egg,
+        ot_name,
+        nickname,
+    };
+
+    put_mon(&mut state, to, index, taken);
+    write(&path, &state)
+}
+
+fn trade_mons(save: &str, mon: &str, other_save: &str, other_mon: &str) -> Result<(), String> {
+    let (path, mut state) = open(save)?;
+    let (other_path, mut other_state) = open(other_save)?;
+
+    if is_same_file(&path, &other_path) {
+        return Err("A save can't trade with itself".to_owned());
+    }
+
+    let party_mon = |mon| match parse_mon(mon)? {
+        (MonLocation::Party, index) => Ok(index),
+        _ => Err("Only mons in the party can be traded".to_owned()),
+    };
+
+    let index = party_mon(mon)?;
+    let other_index = party_mon(other_mon)?;
+
+    let evolutions = trade::trade(&mut state, index, &mut other_state, other_index)?;
+
+    for (save, evolution) in [(save, evolutions.0), (other_save, evolutions.1)] {
+        if let Some(species) = evolution {
+            println!("The mon received by {save} evolved into {species:?}");
+        }
+    }
+
+    // Neither save is replaced unless both could be written
+    let pending = write_pending(&path, &state)?;
+    let other_pending = write_pending(&other_path, &other_state)?;
+
+    pending
+        .commit()
+        .map_err(|error| format!("Failed to replace {}: {error}", path.display()))?;
+
+    other_pending.commit().map_err(|error| {
+        format!(
+            "Failed to replace {}: {error}. {} was already saved with the trade",
+            other_path.display(),
+            path.display()
+        )
+    })
+}
+
+fn fix_checksums(save: &str) -> Result<(), String> {
+    let (path, mut state) = open(save)?;
+
+    state.fix_checksums();
+    write(&path, &state)?;
+
+    if !state.is_valid() {
+        return Err("Neither copy of the game data has check values, so the game will still treat the save as empty".to_owned());
+    }
+
+    Ok(())
+}
+
+/// Reads `save`, either a path or the name of one of the game's saves.
+fn open(save: &str) -> Result<(PathBuf, SaveState), String> {
+    let path = PathBuf::from(save);
+
+    let path = if path.is_file() {
+        path
+    } else {
+        saves::find_save(save)
+            .map_err(|error| format!("Failed to find {save}: {error}"))?
+            .path
+    };
+
+    let state = SaveState::from_file(&path)
+        .map_err(|error| format!("Failed to read {}: {error}", path.display()))?;
+
+    if !state.is_valid() {
+        eprintln!(
+            "Warning: {} isn't intact, the game won't load it",
+            path.display()
+        );
+    }
+
+    Ok((path, state))
+}
+
+/// Whether `path` and `other_path` lead to the same file, however they're written.
+fn is_same_file(path: &Path, other_path: &Path) -> bool {
+    match (fs::canonicalize(path), fs::canonicalize(other_path)) {
+        (Ok(path), Ok(other_path)) => path == other_path,
+        _ => path == other_path,
+    }
+}
+
+/// Writes `state` to `path`, backing it up first if it's one of the game's saves.
+fn write(path: &Path, state: &SaveState) -> Result<(), String> {
+    let result = if saves::is_game_save(path) {
+        saves::write_save(path, state)
+    } else {
+        state.write_to_file(path)
+    };
+
+    result.map_err(|error| format!("Failed to write {}: {error}", path.display()))
+}
+
+/// Writes `state` next to `path` like `write`, to replace it once committed.
+fn write_pending(path: &Path, state: &SaveState) -> Result<PendingSave, String> {
+    let result = if saves::is_game_save(path) {
+        saves::write_save_pending(path, state)
+    } else {
+        state.write_pending(path).map(PendingSave::from)
+    };
+
+    result.map_err(|error| format!("Failed to write {}: {error}", path.display()))
+}
+
+fn to_json(value: &impl Serialize) -> Result<String, String> {
+    serde_json::to_string_pretty(value).map_err(|error| error.to_string())
+}
+
+fn parse_quantity(quantity: &str) -> Result<u8, String> {
+    quantity
+        .parse()
+        .ok()
+        .filter(|quantity| (1..=MAX_ITEM_STACK).contains(quantity))
+        .ok_or_else(|| format!("The quantity must be between 1 and {MAX_ITEM_STACK}"))
+}
+
+/// The position of `item` in the TM/HM pocket, skipping the two unused IDs between the TMs.
+fn tm_hm_index(item: Item) -> Option<usize> {
+    match u8::from(item) {
+        id @ 0xbf..=0xf9 if id != 0xc3 && id != 0xdc => {
+            Some((id - 0xbf) as usize - (id > 0xc3) as usize - (id > 0xdc) as usize)
+        }
+        _ => None,
+    }
+}
+
+fn tm_hm_name(index: usize) -> String {
+    if index < NUM_TMS {
+        format!("TM{:02}", index + 1)
+    } else {
+        format!("HM{:02}", index - NUM_TMS + 1)
+    }
+}
+
+/// Adds `quantity` of `item` to the bag, or to the PC. Without a pocket, the pocket is picked
+/// from the item; key items have to be given with `key-items`.
+fn give_item(
+    state: &mut SaveState,
+    item: Item,
+    quantity: u8,
+    pocket: Option<&str>,
+) -> Result<(), String> {
+    if let (Some(index), None) = (tm_hm_index(item), pocket) {
+        let mut tms_hms = state.tms_hms();
+        tms_hms[index] = tms_hms[index].saturating_add(quantity).min(MAX_ITEM_STACK);
+        state.set_tms_hms(&tms_hms);
+        return Ok(());
+    }
+
+    let list = match pocket {
+        None if is_ball(item) => ItemList::Balls,
+        None | Some("items") => ItemList::Items,
+        Some("balls") => ItemList::Balls,
+        Some("key-items") => ItemList::KeyItems,
+        Some("pc") => ItemList::Pc,
+        Some(pocket) => return Err(format!("Unknown pocket: {pocket}")),
+    };
+
+    let mut items = state.items(list);
+
+    if let Some(stack) = items.iter_mut().find(|(i, _)| *i == item) {
+        if list == ItemList::KeyItems {
+            return Err(format!("There already is a {item:?} in the key items"));
+        }
+
+        stack.1 = stack.1.saturating_add(quantity).min(MAX_ITEM_STACK);
+    } else if items.len() < list.capacity() {
+        items.push((item, quantity));
+    } else {
+        return Err(format!("The {list:?} list is full"));
+    }
+
+    state.set_items(list, &items);
+
+    Ok(())
+}
+
+fn is_ball(item: Item) -> bool {
+    matches!(
+        item,
+        Item::MasterBall
+            | Item::UltraBall
+            | Item::GreatBall
+            | Item::PokeBall
+            | Item::HeavyBall
+            | Item::LevelBall
+            | Item::LureBall
+            | Item::FastBall
+            | Item::FriendBall
+            | Item::MoonBall
+            | Item::LoveBall
+            | Item::ParkBall
+    )
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum MonLocation {
+    Party,
+    /// A box, counting from 0.
+    Box(u8),
+}
+
+impl MonLocation {
+    fn capacity(self) -> usize {
+        match self {
+            MonLocation::Party => PARTY_LENGTH,
+            MonLocation::Box(_) => MONS_PER_BOX,
+        }
+    }
+}
+
+impl std::fmt::Display for MonLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MonLocation::Party => f.write_str("The party"),
+            MonLocation::Box(n) => write!(f, "Box {}", n + 1),
+        }
+    }
+}
+
+fn parse_list(s: &str) -> Result<MonLocation, String> {
+    if s == "party" {
+        return Ok(MonLocation::Party);
+    }
+
+    s.strip_prefix("box")
+        .and_then(|n| n.parse::<u8>().ok())
+        .filter(|n| (1..=NUM_BOXES).contains(n))
+        .map(|n| MonLocation::Box(n - 1))
+        .ok_or_else(|| format!("Expected party or box1 to box{NUM_BOXES}, got {s}"))
+}
+
+fn parse_mon(s: &str) -> Result<(MonLocation, usize), String> {
+    let (list, index) = s
+        .split_once(':')
+        .ok_or_else(|| format!("Expected party:<n> or box<n>:<n>, got {s}"))?;
+
+    let list = parse_list(list)?;
+
+    let index = index
+        .parse::<usize>()
+        .ok()
+        .filter(|index| (1..=list.capacity()).contains(index))
+        .ok_or_else(|| format!("Expected a mon between 1 and {}", list.capacity()))?;
+
+    Ok((list, index - 1))
+}
+
+/// The mons in `list` as Showdown sets. Eggs are left out, since Showdown has no eggs.
+fn export_team(state: &SaveState, list: MonLocation) -> Vec<showdown::ShowdownSet> {
+    let sets = |entries: Vec<(BoxMonOwned, bool, PokeString<MON_NAME_LENGTH>)>| {
+        entries
+            .into_iter()
+            .filter(|(_, is_egg, _)| !is_egg)
+            .map(|(mon, _, nickname)| showdown::ShowdownSet::from_box_mon(mon.as_ref(), &nickname))
+            .collect()
+    };
+
+    match list {
+        MonLocation::Party => {
+            let party = state.party();
+            sets(
+                (0..party.len())
+                    .filter_map(|i| party.get(i))
+                    .map(|entry| {
+                        let (mon, is_egg, _, nickname) = entry_parts(entry);
+                        (BoxMonOwned::from_party_mon(mon), is_egg, nickname)
+                    })
+                    .collect(),
+            )
+        }
+        MonLocation::Box(n) => {
+            let r#box = state.pc_box(n);
+            sets(
+                (0..r#box.len())
+                    .filter_map(|i| r#box.get(i))
+                    .map(|entry| {
+                        let (mon, is_egg, _, nickname) = entry_parts(entry);
+                        (BoxMonOwned::from_box_mon(mon), is_egg, nickname)
+                    })
+                    .collect(),
+            )
+        }
+    }
+}
+
+fn list_len(state: &SaveState, list: MonLocation) -> usize {
+    match list {
+        MonLocation::Party => state.party().len(),
+        MonLocation::Box(n) => state.pc_box(n).len(),
+    }
+}
+
+/// A mon that was taken out of the party or a box.
+struct TakenMon {
+    mon: BoxMonOwned,
+    is_egg: bool,
+    ot_name: PokeString<NAME_LENGTH>,
+    nickname: PokeString<MON_NAME_LENGTH>,
+}
+
+fn take_mon(state: &mut SaveState, list: MonLocation, index: usize) -> Result<TakenMon, String> {
+    let missing = || format!("{list} has no mon {}", index + 1);
+
+    let taken = match list {
+        MonLocation::Party => {
+            let party = state.party();
+            let (mon, is_egg, ot_name, nickname) =
+                entry_parts(party.get(index).ok_or_else(missing)?);
+            TakenMon {
+                mon: BoxMonOwned::from_party_mon(mon),
+                is_egg,
+                ot_name,
+                nickname,
+            }
+        }
+        MonLocation::Box(n) => {
+            let r#box = state.pc_box(n);
+            let (mon, is_egg, ot_name, nickname) =
+                entry_parts(r#box.get(index).ok_or_else(missing)?);
+            TakenMon {
+                mon: BoxMonOwned::from_box_mon(mon),
+                is_egg,
+                ot_name,
+                nickname,
+            }
+        }
+    };
+
+    match list {
+        MonLocation::Party => state.party_mut().list().remove(index),
+        MonLocation::Box(n) => state.pc_box_mut(n).remove(index),
+    }
+
+    Ok(taken)
+}
+
+/// Puts `taken` at `index` in `list`, or at the end. Mons that go into the party have their stats
+/// calculated and are healed, like when they're withdrawn from a box.
+fn put_mon(state: &mut SaveState, list: MonLocation, index: Option<usize>, taken: TakenMon) {
+    let TakenMon {
+        mon,
+        is_egg,
+        ot_name,
+        nickname,
+    } = taken;
+
+    match list {
+        MonLocation::Party => {
+            let mon = PartyMonOwned::from_box_mon(mon.as_ref(), is_egg);
+            let entry = match is_egg {
+                true => MonListEntry::Egg(mon.as_ref(), ot_name, nickname),
+                false => MonListEntry::Mon(mon.as_ref(), ot_name, nickname),
+            };
+            match index {
+                Some(index) => state.party_mut().list().insert(index, entry),
+                None => state.party_mut().list().push_back(entry),
+            }
+        }
+        MonLocation::Box(n) => {
+            let entry = match is_egg {
+                true => MonListEntry::Egg(mon.as_ref(), ot_name, nickname),
+                false => MonListEntry::Mon(mon.as_ref(), ot_name, nickname),
+            };
+            match index {
+                Some(index) => state.pc_box_mut(n).insert(index, entry),
+                None => state.pc_box_mut(n).push_back(entry),
+            }
+        }
+    }
+}
+
+fn entry_parts<T>(
+    entry: MonListEntry<T>,
+) -> (
+    T,
+    bool,
+    PokeString<NAME_LENGTH>,
+    PokeString<MON_NAME_LENGTH>,
+) {
+    match entry {
+        MonListEntry::Mon(mon, ot_name, nickname) => (mon, false, ot_name, nickname),
+        MonListEntry::Egg(mon, ot_name, nickname) => (mon, true, ot_name, nickname),
+    }
+}
+
+#[derive(Default)]
+struct MonEdit {
+    level: Option<u8>,
+    moves: Option<Vec<Move>>,
+    /// Attack, defense, speed and special.
+    dvs: Option<[u8; 4]>,
+    item: Option<Option<Item>>,
+}
+
+impl MonEdit {
+    fn parse(options: &[&str]) -> Result<MonEdit, String> {
+        let mut edit = MonEdit::default();
+
+        for option in options.chunks(2) {
+            match *option {
+                ["--level", level] => {
+                    edit.level = Some(
+                        level
+                            .parse()
+                            .ok()
+                            .filter(|level| (MIN_LEVEL..=MAX_LEVEL).contains(level))
+                            .ok_or_else(|| {
+                                format!("The level must be between {MIN_LEVEL} and {MAX_LEVEL}")
+                            })?,
+                    );
+                }
+                ["--moves", moves] => {
+                    let moves = moves
+                        .split(',')
+                        .map(str::parse)
+                        .collect::<Result<Vec<Move>, _>>()?;
+
+                    if !(1..=4).contains(&moves.len()) {
+                        return Err("A mon has between 1 and 4 moves".to_owned());
+                    }
+
+                    edit.moves = Some(moves);
+                }
+                ["--dvs", dvs] => {
+                    let dvs: Vec<u8> = dvs
+                        .split(',')
+                        .map(|dv| dv.parse().ok().filter(|&dv| dv < 16))
+                        .collect::<Option<_>>()
+                        .ok_or("DVs must be between 0 and 15")?;
+
+                    edit.dvs = Some(dvs.try_into().map_err(|_| {
+                        "Expected 4 DVs: attack, defense, speed and special".to_owned()
+                    })?);
+                }
+                ["--item", "none"] => edit.item = Some(None),
+                ["--item", item] => edit.item = Some(Some(item.parse()?)),
+                _ => return Err(USAGE.to_owned()),
+            }
+        }
+
+        Ok(edit)
+    }
+
+    fn apply(&self, mon: &mut BoxMonOwned) {
+        if let Some(level) = self.level {
+            let species = mon.as_ref().species();
+            mon.set_level(level);
+            mon.set_exp(species.growth_rate().exp_at_level(level));
+        }
+
+        if let Some(moves) = &self.moves {
+            let moves = Moveset::from(&moves[..]);
+            mon.set_moves(&moves);
+            mon.set_pp(moves.pps());
+        }
+
+        if let Some([attack, defense, speed, special]) = self.dvs {
+            mon.set_dvs(DeterminantValues::new(attack, defense, speed, special).into());
+        }
+
+        if let Some(item) = self.item {
+            mon.set_item(item);
+        }
+    }
+}
+
+/// What's in a save, as it's printed.
+#[derive(Serialize)]
+struct Summary {
+    player: Player,
+    checksums: Checksums,
+    party: Vec<Mon>,
+    boxes: Vec<Vec<Mon>>,
+    current_box: u8,
+    items: Vec<ItemStack>,
+    balls: Vec<ItemStack>,
+    key_items: Vec<String>,
+    tms_hms: Vec<ItemStack>,
+    pc_items: Vec<ItemStack>,
+    pokedex_seen: Vec<String>,
+    pokedex_caught: Vec<String>,
+    event_flags: Vec<usize>,
+}
+
+#[derive(Serialize)]
+struct Player {
+    name: String,
+    id: u16,
+    money: u32,
+    coins: u16,
+    johto_badges: u8,
+    kanto_badges: u8,
+    play_time: String,
+}
+
+#[derive(Serialize)]
+struct Checksums {
+    primary: bool,
+    backup: bool,
+}
+
+#[derive(Serialize)]
+struct Mon {
+    species: String,
+    is_egg: bool,
+    nickname: String,
+    level: u8,
+    exp: u32,
+    item: Option<String>,
+    moves: Vec<String>,
+    dvs: Dvs,
+    happiness: u8,
+    ot_name: String,
+    ot_id: u16,
+}
+
+#[derive(Serialize)]
+struct Dvs {
+    attack: u8,
+    defense: u8,
+    speed: u8,
+    special: u8,
+}
+
+#[derive(Serialize)]
+struct ItemStack {
+    item: String,
+    quantity: u8,
+}
+
+impl Summary {
+    fn new(state: &SaveState) -> Summary {
+        let play_time = state.play_time();
+
+        let party = state.party();
+        let party = (0..party.len())
+            .filter_map(|i| party.get(i))
+            .map(|entry| {
+                let (mon, is_egg, ot_name, nickname) = entry_parts(entry);
+                Mon::new(
+                    BoxMonOwned::from_party_mon(mon).as_ref(),
+                    is_egg,
+                    &ot_name,
+                    &nickname,
+                )
+            })
+            .collect();
+
+        let boxes = (0..NUM_BOXES)
+            .map(|n| {
+                let r#box = state.pc_box(n);
+                (0..r#box.len())
+                    .filter_map(|i| r#box.get(i))
+                    .map(|entry| {
+                        let (mon, is_egg, ot_name, nickname) = entry_parts(entry);
+                        Mon::new(mon, is_egg, &ot_name, &nickname)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let item_stacks = |list| {
+            state
+                .items(list)
+                .into_iter()
+                .map(|(item, quantity)| ItemStack {
+                    item: format!("{item:?}"),
+                    quantity,
+                })
+                .collect()
+        };
+
+        let species = (1..=PokemonSpecies::count() as u8).map(PokemonSpecies::from);
+
+        Summary {
+            player: Player {
+                name: state.player_name().to_string(),
+                id: state.player_id(),
+                money: state.money(),
+                coins: state.coins(),
+                johto_badges: state.johto_badges(),
+                kanto_badges: state.kanto_badges(),
+                play_time: format!(
+                    "{}:{:02}:{:02}",
+                    play_time.hours, play_time.minutes, play_time.seconds
+                ),
+            },
+            checksums: Checksums {
+                primary: state.copy_is_valid(SaveCopy::Primary),
+                backup: state.copy_is_valid(SaveCopy::Backup),
+            },
+            party,
+            boxes,
+            current_box: state.current_box_index() + 1,
+            items: item_stacks(ItemList::Items),
+            balls: item_stacks(ItemList::Balls),
+            key_items: state
+                .items(ItemList::KeyItems)
+                .into_iter()
+                .map(|(item, _)| format!("{item:?}"))
+                .collect(),
+            tms_hms: state
+                .tms_hms()
+                .into_iter()
+                .enumerate()
+                .filter(|&(_, quantity)| quantity > 0)
+                .map(|(i, quantity)| ItemStack {
+                    item: tm_hm_name(i),
+                    quantity,
+                })
+                .collect(),
+            pc_items: item_stacks(ItemList::Pc),
+            pokedex_seen: species
+                .clone()
+                .filter(|&species| state.pokedex_seen(species))
+                .map(|species| format!("{species:?}"))
+                .collect(),
+            pokedex_caught: species
+                .filter(|&species| state.pokedex_caught(species))
+                .map(|species| format!("{species:?}"))
+                .collect(),
+            event_flags: state.event_flags(),
+        }
+    }
+
+    fn to_text(&self) -> String {
+        let mut text = String::new();
+        let player = &self.player;
+
+        let checksum = |valid| if valid { "intact" } else { "broken" };
+
+        writeln!(text, "Player: {} ({:05})", player.name, player.id).unwrap();
+        writeln!(text, "Play time: {}", player.play_time).unwrap();
+        writeln!(text, "Money: ¥{}, coins: {}", player.money, player.coins).unwrap();
+        writeln!(
+            text,
+            "Badges: Johto {:08b}, Kanto {:08b}",
+            player.johto_badges, player.kanto_badges
+        )
+        .unwrap();
+        writeln!(
+            text,
+            "Game data: primary {}, backup {}",
+            checksum(self.checksums.primary),
+            checksum(self.checksums.backup)
+        )
+        .unwrap();
+
+        writeln!(text, "\nParty:").unwrap();
+        write_mons(&mut text, &self.party);
+
+        for (i, mons) in self.boxes.iter().enumerate() {
+            if mons.is_empty() {
+                continue;
+            }
+
+            let current = if i + 1 == self.current_box as usize {
+                " (current)"
+            } else {
+                ""
+            };
+
+            writeln!(text, "\nBox {}{current}:", i + 1).unwrap();
+            write_mons(&mut text, mons);
+        }
+
+        writeln!(text).unwrap();
+        writeln!(text, "Items: {}", format_stacks(&self.items)).unwrap();
+        writeln!(text, "Balls: {}", format_stacks(&self.balls)).unwrap();
+        writeln!(text, "Key items: {}", format_list(&self.key_items)).unwrap();
+        writeln!(text, "TMs and HMs: {}", format_stacks(&self.tms_hms)).unwrap();
+        writeln!(text, "PC: {}", format_stacks(&self.pc_items)).unwrap();
+
+        writeln!(
+            text,
+            "\nPokédex: {} seen, {} caught",
+            self.pokedex_seen.len(),
+            self.pokedex_caught.len()
+        )
+        .unwrap();
+        writeln!(text, "Caught: {}", format_list(&self.pokedex_caught)).unwrap();
+
+        let event_flags: Vec<String> = self.event_flags.iter().map(usize::to_string).collect();
+        writeln!(text, "Event flags: {}", format_list(&event_flags)).unwrap();
+
+        text
+    }
+}
+
+impl Mon {
+    fn new(
+        mon: BoxMonRef,
+        is_egg: bool,
+        ot_name: &PokeString<NAME_LENGTH>,
+        nickname: &PokeString<MON_NAME_LENGTH>,
+    ) -> Mon {
+        let dvs = mon.dvs();
+
+        Mon {
+            species: format!("{:?}", mon.species()),
+            is_egg,
+            nickname: nickname.to_string(),
+            level: mon.level(),
+            exp: mon.exp(),
+            item: mon.item().map(|item| format!("{item:?}")),
+            moves: (0..4)
+                .filter_map(|i| mon.moves().get(i))
+                .map(|r#move| format!("{move:?}"))
+                .collect(),
+            dvs: Dvs {
+                attack: dvs.attack(),
+                defense: dvs.defense(),
+                speed: dvs.speed(),
+                special: dvs.special(),
+            },
+            happiness: mon.happiness(),
+            ot_name: ot_name.to_string(),
+            ot_id: mon.ot_id(),
+        }
+    }
+}
+
+fn write_mons(text: &mut String, mons: &[Mon]) {
+    if mons.is_empty() {
+        writeln!(text, "  (empty)").unwrap();
+    }
+
+    for (i, mon) in mons.iter().enumerate() {
+        let egg = if mon.is_egg { " (egg)" } else { "" };
+
+        writeln!(
+            text,
+            "  {}. {}{egg} \"{}\" Lv. {}, holding {}",
+            i + 1,
+            mon.species,
+            mon.nickname,
+            mon.level,
+            mon.item.as_deref().unwrap_or("nothing"),
+        )
+        .unwrap();
+        writeln!(
+            text,
+            "     Moves: {}; DVs {}/{}/{}/{}; OT {} ({:05})",
+            format_list(&mon.moves),
+            mon.dvs.attack,
+            mon.dvs.defense,
+            mon.dvs.speed,
+            mon.dvs.special,
+            mon.ot_name,
+            mon.ot_id
+        )
+        .unwrap();
+    }
+}
+
+fn format_list(list: &[String]) -> String {
+    if list.is_empty() {
+        "none".to_owned()
+    } else {
+        list.join(", ")
+    }
+}
+
+fn format_stacks(stacks: &[ItemStack]) -> String {
+    let stacks: Vec<String> = stacks
+        .iter()
+        .map(|stack| format!("{} ×{}", stack.item, stack.quantity))
+        .collect();
+
+    format_list(&stacks)
+}
+
+/// A difference between two saves. Lists of names and numbers are compared as sets, `before`
+/// and `after` then hold the entries that are only in one of the saves.
+#[derive(Serialize)]
+struct Change {
+    path: String,
+    before: Value,
+    after: Value,
+}
+
+fn diff(path: &str, a: &Value, b: &Value, changes: &mut Vec<Change>) {
+    match (a, b) {
+        (Value::Object(a), Value::Object(b)) => {
+            for (key, value) in a {
+                let path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{path}.{key}")
+                };
+
+                diff(&path, value, b.get(key).unwrap_or(&Value::Null), changes);
+            }
+        }
+        (Value::Array(a), Value::Array(b)) if is_set(a) && is_set(b) => {
+            let removed: Vec<Value> = a.iter().filter(|v| !b.contains(v)).cloned().collect();
+            let added: Vec<Value> = b.iter().filter(|v| !a.contains(v)).cloned().collect();
+
+            if !removed.is_empty() || !added.is_empty() {
+                changes.push(Change {
+                    path: path.to_owned(),
+                    before: Value::Array(removed),
+                    after: Value::Array(added),
+                });
+            }
+        }
+        (Value::Array(a), Value::Array(b)) => {
+            for i in 0..a.len().max(b.len()) {
+                diff(
+                    &format!("{path}[{i}]"),
+                    a.get(i).unwrap_or(&Value::Null),
+                    b.get(i).unwrap_or(&Value::Null),
+                    changes,
+                );
+            }
+        }
+        (a, b) if a != b => changes.push(Change {
+            path: path.to_owned(),
+            before: a.clone(),
+            after: b.clone(),
+        }),
+        _ => {}
+    }
+}
+
+fn is_set(values: &[Value]) -> bool {
+    values
+        .iter()
+        .all(|value| value.is_string() || value.is_number())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_give_item() {
+        let mut state = SaveState::new_intact();
+
+        give_item(&mut state, Item::Potion, 3, None).unwrap();
+        give_item(&mut state, Item::Potion, 99, None).unwrap();
+        give_item(&mut state, Item::GreatBall, 1, None).unwrap();
+        give_item(&mut state, Item::TmHeadbutt, 1, None).unwrap();
+
+        assert_eq!(state.items(ItemList::Items), [(Item::Potion, 99)]);
+        assert_eq!(state.items(ItemList::Balls), [(Item::GreatBall, 1)]);
+        assert_eq!(state.tms_hms()[1], 1);
+        assert!(state.is_valid());
+
+        assert_eq!(tm_hm_index(Item::TmRoar), Some(4));
+        assert_eq!(tm_hm_index(Item::Unknown(0xf3)), Some(NUM_TMS));
+    }
+
+    #[test]
+    fn test_diff() {
+        let a = serde_json::json!({ "money": 10, "flags": [1, 2], "party": [{ "level": 5 }] });
+        let b = serde_json::json!({ "money": 20, "flags": [2, 3], "party": [{ "level": 5 }, { "level": 7 }] });
+
+        let mut changes = Vec::new();
+        diff("", &a, &b, &mut changes);
+
+        let changes: Vec<String> = changes
+            .iter()
+            .map(|change| format!("{}: {} -> {}", change.path, change.before, change.after))
+            .collect();
+
+        assert_eq!(
+            changes,
+            [
+                "flags: [1] -> [3]",
+                "money: 10 -> 20",
+                "party[1]: null -> {\"level\":7}",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_is_same_file() {
+        let dir =
+            std::env::temp_dir().join(format!("rustic-crystal-same-file-{}", std::process::id()));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("a.sav"), []).unwrap();
+        fs::write(dir.join("b.sav"), []).unwrap();
+
+        assert!(is_same_file(&dir.join("a.sav"), &dir.join("sub/../a.sav")));
+        assert!(is_same_file(&dir.join("a.sav"), &dir.join("./a.sav")));
+        assert!(!is_same_file(&dir.join("a.sav"), &dir.join("b.sav")));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
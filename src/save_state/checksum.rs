@@ -0,0 +1,137 @@
+//! The checks the game uses to tell whether a save is intact.
+//!
+//! The game data is stored twice: the primary copy in SRAM bank 1 and a backup copy in bank 0.
+//! Each copy is framed by two check values, and is followed by a 16-bit sum of its bytes.
+
+use crate::{
+    game::constants::misc_constants::{SAVE_CHECK_VALUE_1, SAVE_CHECK_VALUE_2},
+    save_state::SaveState,
+};
+
+// wGameDataEnd - wGameData
+const GAME_DATA_LENGTH: usize = 0xb7a;
+
+/// One of the two copies of the game data in SRAM.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SaveCopy {
+    Primary,
+    Backup,
+}
+
+impl SaveCopy {
+    /// Offset of the first check value, followed by the game data.
+    fn check_value_1(self) -> usize {
+        match self {
+            SaveCopy::Primary => 0x2008, // sCheckValue1
+            SaveCopy::Backup => 0x1208,  // sBackupCheckValue1
+        }
+    }
+
+    pub(super) fn game_data(self) -> usize {
+        self.check_value_1() + 1
+    }
+
+    /// Offset of the checksum, followed by the second check value.
+    fn checksum(self) -> usize {
+        // The game data is followed by $18a unused bytes
+        self.game_data() + GAME_DATA_LENGTH + 0x18a
+    }
+
+    fn check_value_2(self) -> usize {
+        self.checksum() + 2
+    }
+}
+
+impl SaveState {
+    /// The sum of the game data in `copy`, as the game calculates it.
+    pub fn calculate_checksum(&self, copy: SaveCopy) -> u16 {
+        let start = copy.game_data();
+
+        self.data[start..start + GAME_DATA_LENGTH]
+            .iter()
+            .fold(0u16, |sum, &byte| sum.wrapping_add(byte as u16))
+    }
+
+    /// The checksum that was stored when `copy` was last saved.
+    pub fn stored_checksum(&self, copy: SaveCopy) -> u16 {
+        let at = copy.checksum();
+        u16::from_le_bytes([self.data[at], self.data[at + 1]])
+    }
+
+    /// Stores the checksum of `copy`, after its game data has been changed.
+    pub fn update_checksum(&mut self, copy: SaveCopy) {
+        let at = copy.checksum();
+        let checksum = self.calculate_checksum(copy);
+        self.data[at..at + 2].copy_from_slice(&checksum.to_le_bytes());
+    }
+
+    /// Makes the game accept both copies as they are, e.g. after editing them byte by byte.
+    /// Copies without check values were never written by the game and are left alone.
+    pub fn fix_checksums(&mut self) {
+        for copy in [SaveCopy::Primary, SaveCopy::Backup] {
+            if self.data[copy.check_value_1()] == SAVE_CHECK_VALUE_1
+                && self.data[copy.check_value_2()] == SAVE_CHECK_VALUE_2
+            {
+                self.update_checksum(copy);
+            }
+        }
+    }
+
+    /// Whether `copy` was completely written and hasn't been changed since.
+    pub fn copy_is_valid(&self, copy: SaveCopy) -> bool {
+        self.data[copy.check_value_1()] == SAVE_CHECK_VALUE_1
+            && self.data[copy.check_value_2()] == SAVE_CHECK_VALUE_2
+            && self.calculate_checksum(copy) == self.stored_checksum(copy)
+    }
+
+    /// Whether the game can load this save, from either copy.
+    pub fn is_valid(&self) -> bool {
+        self.copy_is_valid(SaveCopy::Primary) || self.copy_is_valid(SaveCopy::Backup)
+    }
+
+    /// An empty save that the game accepts, with both copies framed by their check values.
+    #[cfg(test)]
+    pub fn new_intact() -> SaveState {
+        let mut state = SaveState::new();
+
+        for copy in [SaveCopy::Primary, SaveCopy::Backup] {
+            state.set_byte(copy.check_value_1(), SAVE_CHECK_VALUE_1);
+            state.set_byte(copy.check_value_2(), SAVE_CHECK_VALUE_2);
+        }
+
+        state.fix_checksums();
+        state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checksum() {
+        let mut state = SaveState::new();
+        assert!(!state.is_valid());
+
+        for copy in [SaveCopy::Primary, SaveCopy::Backup] {
+            state.set_byte(copy.check_value_1(), SAVE_CHECK_VALUE_1);
+            state.set_byte(copy.check_value_2(), SAVE_CHECK_VALUE_2);
+            state.set_byte(copy.game_data() + 10, 0xff);
+            state.set_byte(copy.game_data() + 20, 0x02);
+
+            let [lo, hi] = 0x0101u16.to_le_bytes();
+            state.set_byte(copy.checksum(), lo);
+            state.set_byte(copy.checksum() + 1, hi);
+
+            assert!(state.copy_is_valid(copy));
+        }
+
+        // sCheckValue2 and sBackupCheckValue2
+        assert_eq!(SaveCopy::Primary.check_value_2(), 0x2d0f);
+        assert_eq!(SaveCopy::Backup.check_value_2(), 0x1f0f);
+
+        state.set_byte(SaveCopy::Primary.game_data(), 1);
+        assert!(!state.copy_is_valid(SaveCopy::Primary));
+        assert!(state.is_valid());
+    }
+}
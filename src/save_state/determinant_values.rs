@@ -1,7 +1,34 @@
+use crate::game::constants::type_constants::Type;
+
+// The types Hidden Power can have, in the order of HiddenPowerDamage, which skips Normal, Bird
+// and the unused types
+const HIDDEN_POWER_TYPES: [Type; 16] = [
+    Type::Fighting,
+    Type::Flying,
+    Type::Poison,
+    Type::Ground,
+    Type::Rock,
+    Type::Bug,
+    Type::Ghost,
+    Type::Steel,
+    Type::Fire,
+    Type::Water,
+    Type::Grass,
+    Type::Electric,
+    Type::Psychic,
+    Type::Ice,
+    Type::Dragon,
+    Type::Dark,
+];
+
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub struct DeterminantValues(u8, u8);
 
 impl DeterminantValues {
+    pub fn new(attack: u8, defense: u8, speed: u8, special: u8) -> Self {
+        DeterminantValues((attack << 4) | defense, (speed << 4) | special)
+    }
+
     pub fn hp(&self) -> u8 {
         ((self.attack() & 1) << 3)
             | ((self.defense() & 1) << 2)
@@ -24,6 +51,31 @@ impl DeterminantValues {
     pub fn special(&self) -> u8 {
         self.1 & 0b0000_1111
     }
+
+    /// Shininess is decided by the DVs: 10 in defense, speed and special, and an attack with
+    /// the second bit set.
+    pub fn is_shiny(&self) -> bool {
+        self.defense() == 10
+            && self.speed() == 10
+            && self.special() == 10
+            && self.attack() & 0b0010 != 0
+    }
+
+    /// The type of Hidden Power, from the two lowest bits of attack and defense.
+    pub fn hidden_power_type(&self) -> Type {
+        HIDDEN_POWER_TYPES[(((self.attack() & 0b11) << 2) | (self.defense() & 0b11)) as usize]
+    }
+
+    /// The highest DVs that give Hidden Power `r#type`, or `None` if it can't have that type.
+    pub fn max_for_hidden_power(r#type: Type) -> Option<Self> {
+        let index = HIDDEN_POWER_TYPES.iter().position(|&t| t == r#type)? as u8;
+        Some(DeterminantValues::new(
+            12 | (index >> 2),
+            12 | (index & 0b11),
+            15,
+            15,
+        ))
+    }
 }
 
 impl From<[u8; 2]> for DeterminantValues {
@@ -32,6 +84,12 @@ impl From<[u8; 2]> for DeterminantValues {
     }
 }
 
+impl From<DeterminantValues> for u16 {
+    fn from(dvs: DeterminantValues) -> Self {
+        u16::from_be_bytes([dvs.0, dvs.1])
+    }
+}
+
 impl std::fmt::Debug for DeterminantValues {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("DeterminantValues")
@@ -0,0 +1,453 @@
+//! The player's progress, read from and written to the game data in SRAM.
+//!
+//! Both copies of the game data are laid out like the game data in WRAM, from wPlayerData up to
+//! wGameDataEnd, so the accessors work with WRAM addresses. They read the copy that the game
+//! would load: the primary copy, unless only the backup copy is intact. Changes are written to
+//! that copy, and to the other one if it's intact, and their checksums are updated so that the
+//! game still accepts them.
+//!
+//! The boxes are stored outside of the game data, and aren't covered by the checksums.
+
+use crate::{
+    game::constants::{
+        item_constants::{Item, NUM_HMS, NUM_TMS},
+        item_data_constants::{MAX_BALLS, MAX_ITEMS, MAX_KEY_ITEMS, MAX_PC_ITEMS},
+        pokemon_constants::{PokemonSpecies, NUM_POKEMON},
+        pokemon_data_constants::{NUM_BOXES, PARTY_LENGTH},
+        text_constants::NAME_LENGTH,
+    },
+    game_state::{
+        mon_list::{MonList, MonListMut},
+        party_mon::{PartyMonMut, PartyMonRef},
+    },
+    save_state::{
+        checksum::SaveCopy,
+        r#box::{Box, BoxMut},
+        string::PokeString,
+        SaveState,
+    },
+};
+
+// wPlayerData
+const GAME_DATA: usize = 0xd47b;
+
+const PLAYER_ID: usize = 0xd47b;
+const PLAYER_NAME: usize = 0xd47d;
+const GAME_TIME_HOURS: usize = 0xd4c4;
+const MONEY: usize = 0xd84e;
+const COINS: usize = 0xd855;
+const JOHTO_BADGES: usize = 0xd857;
+const KANTO_BADGES: usize = 0xd858;
+const TMS_HMS: usize = 0xd859;
+const EVENT_FLAGS: usize = 0xda72;
+const CUR_BOX: usize = 0xdb72;
+const PARTY_COUNT: usize = 0xdcd7;
+const POKEDEX_CAUGHT: usize = 0xde99;
+const POKEDEX_SEEN: usize = 0xdeb9;
+// wPokemonDataEnd
+const GAME_DATA_END: usize = 0xdff5;
+
+const EVENT_FLAGS_LENGTH: usize = 0x100;
+const POKEDEX_FLAGS_LENGTH: usize = NUM_POKEMON.div_ceil(8);
+
+pub const MAX_MONEY: u32 = 999_999;
+pub const MAX_COINS: u16 = 9_999;
+
+// The boxes are stored 7 in bank 2 followed by 7 in bank 3
+const BOX_LENGTH: usize = 0x450;
+const BOXES_PER_BANK: usize = 7;
+// sBox, where the current box is kept while it's current
+const CURRENT_BOX: usize = 0x2d10;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PlayTime {
+    pub hours: u16,
+    pub minutes: u8,
+    pub seconds: u8,
+}
+
+/// The lists of items: the three item pockets of the bag and the PC. TMs and HMs are stored as
+/// a quantity for each of them instead, see [`SaveState::tms_hms`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ItemList {
+    Items,
+    Balls,
+    KeyItems,
+    Pc,
+}
+
+impl ItemList {
+    pub const ALL: [ItemList; 4] = [
+        ItemList::Items,
+        ItemList::Balls,
+        ItemList::KeyItems,
+        ItemList::Pc,
+    ];
+
+    /// The address of the count, followed by the items and a terminator.
+    fn address(self) -> usize {
+        match self {
+            ItemList::Items => 0xd892,    // wNumItems
+            ItemList::KeyItems => 0xd8bc, // wNumKeyItems
+            ItemList::Balls => 0xd8d7,    // wNumBalls
+            ItemList::Pc => 0xd8f1,       // wNumPCItems
+        }
+    }
+
+    pub fn capacity(self) -> usize {
+        match self {
+            ItemList::Items => MAX_ITEMS,
+            ItemList::Balls => MAX_BALLS,
+            ItemList::KeyItems => MAX_KEY_ITEMS,
+            ItemList::Pc => MAX_PC_ITEMS,
+        }
+    }
+
+    /// Key items can't be stacked, so they're stored without a quantity.
+    fn has_quantities(self) -> bool {
+        self != ItemList::KeyItems
+    }
+
+    fn slot_len(self) -> usize {
+        if self.has_quantities() {
+            2
+        } else {
+            1
+        }
+    }
+
+    fn len(self) -> usize {
+        1 + self.capacity() * self.slot_len() + 1
+    }
+}
+
+/// A copy of the party that's written back to both copies of the game data when it's dropped.
+pub struct PartyMut<'a> {
+    state: &'a mut SaveState,
+    data: Vec<u8>,
+}
+
+impl PartyMut<'_> {
+    pub fn list(&mut self) -> MonListMut<'_, PartyMonRef<'_>, PartyMonMut<'_>, PARTY_LENGTH> {
+        MonListMut::new(&mut self.data)
+    }
+}
+
+impl Drop for PartyMut<'_> {
+    fn drop(&mut self) {
+        self.state.set_game_data(PARTY_COUNT, &self.data);
+    }
+}
+
+impl SaveState {
+    /// The copy of the game data that the game would load.
+    pub fn loaded_copy(&self) -> SaveCopy {
+        if !self.copy_is_valid(SaveCopy::Primary) && self.copy_is_valid(SaveCopy::Backup) {
+            SaveCopy::Backup
+        } else {
+            SaveCopy::Primary
+        }
+    }
+
+    /// The game data at a WRAM address.
+    fn game_data(&self, wram: usize, len: usize) -> &[u8] {
+        let start = self.loaded_copy().game_data() + wram - GAME_DATA;
+        &self.data[start..start + len]
+    }
+
+    /// Writes game data at a WRAM address.
+    fn set_game_data(&mut self, wram: usize, bytes: &[u8]) {
+        let loaded = self.loaded_copy();
+
+        let copies: Vec<SaveCopy> = [SaveCopy::Primary, SaveCopy::Backup]
+            .into_iter()
+            .filter(|&copy| copy == loaded || self.copy_is_valid(copy))
+            .collect();
+
+        for copy in copies {
+            let start = copy.game_data() + wram - GAME_DATA;
+            self.data[start..start + bytes.len()].copy_from_slice(bytes);
+            self.update_checksum(copy);
+        }
+    }
+
+    fn flag(&self, wram: usize, n: usize) -> bool {
+        self.game_data(wram + n / 8, 1)[0] & (1 << (n % 8)) != 0
+    }
+
+    fn set_flag(&mut self, wram: usize, n: usize, value: bool) {
+        let mut byte = self.game_data(wram + n / 8, 1)[0];
+
+        if value {
+            byte |= 1 << (n % 8);
+        } else {
+            byte &= !(1 << (n % 8));
+        }
+
+        self.set_game_data(wram + n / 8, &[byte]);
+    }
+
+    pub fn player_id(&self) -> u16 {
+        let bytes = self.game_data(PLAYER_ID, 2);
+        u16::from_be_bytes([bytes[0], bytes[1]])
+    }
+
+    pub fn set_player_id(&mut self, value: u16) {
+        self.set_game_data(PLAYER_ID, &value.to_be_bytes());
+    }
+
+    pub fn player_name(&self) -> PokeString<NAME_LENGTH> {
+        PokeString::new(self.game_data(PLAYER_NAME, NAME_LENGTH).try_into().unwrap())
+    }
+
+    pub fn set_player_name(&mut self, value: &PokeString<NAME_LENGTH>) {
+        self.set_game_data(PLAYER_NAME, value.as_ref());
+    }
+
+    pub fn play_time(&self) -> PlayTime {
+        // wGameTimeHours, wGameTimeMinutes and wGameTimeSeconds
+        let bytes = self.game_data(GAME_TIME_HOURS, 4);
+
+        PlayTime {
+            hours: u16::from_be_bytes([bytes[0], bytes[1]]),
+            minutes: bytes[2],
+            seconds: bytes[3],
+        }
+    }
+
+    pub fn set_play_time(&mut self, value: PlayTime) {
+        let [hi, lo] = value.hours.to_be_bytes();
+        self.set_game_data(GAME_TIME_HOURS, &[hi, lo, value.minutes, value.seconds]);
+    }
+
+    pub fn money(&self) -> u32 {
+        let bytes = self.game_data(MONEY, 3);
+        u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]])
+    }
+
+    pub fn set_money(&mut self, value: u32) {
+        assert!(value <= MAX_MONEY);
+        self.set_game_data(MONEY, &value.to_be_bytes()[1..]);
+    }
+
+    pub fn coins(&self) -> u16 {
+        let bytes = self.game_data(COINS, 2);
+        u16::from_be_bytes([bytes[0], bytes[1]])
+    }
+
+    pub fn set_coins(&mut self, value: u16) {
+        assert!(value <= MAX_COINS);
+        self.set_game_data(COINS, &value.to_be_bytes());
+    }
+
+    pub fn johto_badges(&self) -> u8 {
+        self.game_data(JOHTO_BADGES, 1)[0]
+    }
+
+    pub fn set_johto_badges(&mut self, value: u8) {
+        self.set_game_data(JOHTO_BADGES, &[value]);
+    }
+
+    pub fn kanto_badges(&self) -> u8 {
+        self.game_data(KANTO_BADGES, 1)[0]
+    }
+
+    pub fn set_kanto_badges(&mut self, value: u8) {
+        self.set_game_data(KANTO_BADGES, &[value]);
+    }
+
+    pub fn badge_count(&self) -> u32 {
+        self.johto_badges().count_ones() + self.kanto_badges().count_ones()
+    }
+
+    pub fn items(&self, list: ItemList) -> Vec<(Item, u8)> {
+        let bytes = self.game_data(list.address(), list.len());
+        let count = (bytes[0] as usize).min(list.capacity());
+
+        bytes[1..1 + count * list.slot_len()]
+            .chunks_exact(list.slot_len())
+            .map(|slot| (Item::from(slot[0]), *slot.get(1).unwrap_or(&1)))
+            .collect()
+    }
+
+    /// Replaces the items in `list`. The quantities of key items are ignored.
+    pub fn set_items(&mut self, list: ItemList, items: &[(Item, u8)]) {
+        assert!(items.len() <= list.capacity());
+
+        let mut bytes = vec![0; list.len()];
+        bytes[0] = items.len() as u8;
+
+        for (i, &(item, quantity)) in items.iter().enumerate() {
+            let slot = 1 + i * list.slot_len();
+            bytes[slot] = item.into();
+
+            if list.has_quantities() {
+                bytes[slot + 1] = quantity;
+            }
+        }
+
+        bytes[1 + items.len() * list.slot_len()] = 0xff;
+
+        self.set_game_data(list.address(), &bytes);
+    }
+
+    /// How many of each TM and HM the player has, in order.
+    pub fn tms_hms(&self) -> [u8; NUM_TMS + NUM_HMS] {
+        self.game_data(TMS_HMS, NUM_TMS + NUM_HMS)
+            .try_into()
+            .unwrap()
+    }
+
+    pub fn set_tms_hms(&mut self, value: &[u8; NUM_TMS + NUM_HMS]) {
+        self.set_game_data(TMS_HMS, value);
+    }
+
+    pub fn event_flag(&self, n: usize) -> bool {
+        assert!(n < EVENT_FLAGS_LENGTH * 8);
+        self.flag(EVENT_FLAGS, n)
+    }
+
+    pub fn set_event_flag(&mut self, n: usize, value: bool) {
+        assert!(n < EVENT_FLAGS_LENGTH * 8);
+        self.set_flag(EVENT_FLAGS, n, value);
+    }
+
+    /// The event flags that are set.
+    pub fn event_flags(&self) -> Vec<usize> {
+        (0..EVENT_FLAGS_LENGTH * 8)
+            .filter(|&n| self.flag(EVENT_FLAGS, n))
+            .collect()
+    }
+
+    pub fn pokedex_caught(&self, species: PokemonSpecies) -> bool {
+        self.flag(POKEDEX_CAUGHT, u8::from(species) as usize - 1)
+    }
+
+    pub fn set_pokedex_caught(&mut self, species: PokemonSpecies, value: bool) {
+        self.set_flag(POKEDEX_CAUGHT, u8::from(species) as usize - 1, value);
+    }
+
+    pub fn pokedex_seen(&self, species: PokemonSpecies) -> bool {
+        self.flag(POKEDEX_SEEN, u8::from(species) as usize - 1)
+    }
+
+    pub fn set_pokedex_seen(&mut self, species: PokemonSpecies, value: bool) {
+        self.set_flag(POKEDEX_SEEN, u8::from(species) as usize - 1, value);
+    }
+
+    /// The number of species that have been caught, according to the Pokédex.
+    pub fn pokedex_caught_count(&self) -> u32 {
+        self.game_data(POKEDEX_CAUGHT, POKEDEX_FLAGS_LENGTH)
+            .iter()
+            .map(|byte| byte.count_ones())
+            .sum()
+    }
+
+    pub fn pokedex_seen_count(&self) -> u32 {
+        self.game_data(POKEDEX_SEEN, POKEDEX_FLAGS_LENGTH)
+            .iter()
+            .map(|byte| byte.count_ones())
+            .sum()
+    }
+
+    pub fn party(&self) -> MonList<'_, PartyMonRef<'_>, PARTY_LENGTH> {
+        MonList::new(self.game_data(PARTY_COUNT, GAME_DATA_END - PARTY_COUNT))
+    }
+
+    /// The party, for changing it. The changes are written when the returned [`PartyMut`] is
+    /// dropped.
+    pub fn party_mut(&mut self) -> PartyMut<'_> {
+        let data = self
+            .game_data(PARTY_COUNT, GAME_DATA_END - PARTY_COUNT)
+            .to_vec();
+
+        PartyMut { state: self, data }
+    }
+
+    /// The index of the box that's selected in the PC.
+    pub fn current_box_index(&self) -> u8 {
+        self.game_data(CUR_BOX, 1)[0]
+    }
+
+    /// Where box `index` is stored, the current box is kept apart from the others.
+    fn box_offset(&self, index: u8) -> usize {
+        assert!(index < NUM_BOXES);
+
+        if index == self.current_box_index() {
+            return CURRENT_BOX;
+        }
+
+        let index = index as usize;
+        let bank = 2 + index / BOXES_PER_BANK;
+
+        bank * 0x2000 + (index % BOXES_PER_BANK) * BOX_LENGTH
+    }
+
+    /// One of the 14 boxes, counting from 0.
+    pub fn pc_box(&self, index: u8) -> Box<'_> {
+        let offset = self.box_offset(index);
+        Box::new(&self.data[offset..offset + BOX_LENGTH])
+    }
+
+    pub fn pc_box_mut(&mut self, index: u8) -> BoxMut<'_> {
+        let offset = self.box_offset(index);
+        BoxMut::new(&mut self.data[offset..offset + BOX_LENGTH])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_game_data() {
+        let mut state = SaveState::new();
+
+        // wGameTimeHours in the primary copy, 0x2009 + 0xd4c4 - 0xd47b
+        state.set_byte(0x2052, 0x01);
+        state.set_byte(0x2053, 0x02);
+        state.set_byte(0x2054, 3);
+        state.set_byte(0x2055, 4);
+
+        assert_eq!(
+            state.play_time(),
+            PlayTime {
+                hours: 0x0102,
+                minutes: 3,
+                seconds: 4,
+            }
+        );
+
+        // wJohtoBadges and wKantoBadges
+        state.set_byte(0x23e5, 0b1011);
+        state.set_byte(0x23e6, 0b1);
+        assert_eq!(state.badge_count(), 4);
+    }
+
+    #[test]
+    fn test_set_game_data() {
+        let mut state = SaveState::new_intact();
+        assert!(state.copy_is_valid(SaveCopy::Primary));
+
+        state.set_money(123_456);
+        state.set_items(
+            ItemList::Balls,
+            &[(Item::PokeBall, 5), (Item::UltraBall, 1)],
+        );
+        state.set_items(ItemList::KeyItems, &[(Item::Bicycle, 1)]);
+        state.set_event_flag(9, true);
+
+        assert_eq!(state.money(), 123_456);
+        assert_eq!(
+            state.items(ItemList::Balls),
+            [(Item::PokeBall, 5), (Item::UltraBall, 1)]
+        );
+        assert_eq!(state.items(ItemList::KeyItems), [(Item::Bicycle, 1)]);
+        assert_eq!(state.event_flags(), [9]);
+
+        // Both copies were changed and are still accepted
+        assert!(state.copy_is_valid(SaveCopy::Primary));
+        assert!(state.copy_is_valid(SaveCopy::Backup));
+        assert_eq!(state.byte(0x23df), state.byte(0x15df));
+    }
+}
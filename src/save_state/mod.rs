@@ -1,12 +1,43 @@
 use std::{
     io::{self, Read, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
 pub mod r#box;
+pub mod checksum;
 pub mod determinant_values;
+pub mod game_data;
+pub mod pk2;
+pub mod sav_file;
+pub mod showdown;
 pub mod string;
+pub mod trade;
 
+/// A save that was written next to its path, and is removed again unless it's committed.
+pub struct PendingWrite {
+    tmp_path: PathBuf,
+    path: PathBuf,
+    committed: bool,
+}
+
+impl PendingWrite {
+    /// Moves the save into place.
+    pub fn commit(mut self) -> io::Result<()> {
+        std::fs::rename(&self.tmp_path, &self.path)?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl Drop for PendingWrite {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = std::fs::remove_file(&self.tmp_path);
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct SaveState {
     data: [u8; 0x8000],
     rtc_zero: u64,
@@ -24,10 +55,17 @@ impl SaveState {
         let mut file = std::fs::File::open(path)?;
 
         let mut rtc_bytes = [0; 8];
-        file.read_exact(&mut rtc_bytes)?;
-
         let mut data = [0; 0x8000];
-        file.read_exact(&mut data)?;
+
+        file.read_exact(&mut rtc_bytes)
+            .and_then(|_| file.read_exact(&mut data))
+            .map_err(|error| match error.kind() {
+                io::ErrorKind::UnexpectedEof => io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "the save is truncated, expected 32 KiB of SRAM",
+                ),
+                _ => error,
+            })?;
 
         Ok(SaveState {
             data,
@@ -35,13 +73,31 @@ impl SaveState {
         })
     }
 
-    pub fn write_to_file(&self, path: &PathBuf) -> io::Result<()> {
-        let mut file = std::fs::File::create(path)?;
+    /// Writes the save to a temporary file next to `path` and then moves it into place, so that
+    /// the old save is left intact if writing fails halfway.
+    pub fn write_to_file(&self, path: &Path) -> io::Result<()> {
+        self.write_pending(path)?.commit()
+    }
+
+    /// Writes the save to a temporary file next to `path`, which is only moved into place by
+    /// `PendingWrite::commit`, e.g. once another save has been written too.
+    pub fn write_pending(&self, path: &Path) -> io::Result<PendingWrite> {
+        let mut tmp_name = path.file_name().unwrap_or_default().to_owned();
+        tmp_name.push(".tmp");
+
+        let pending = PendingWrite {
+            tmp_path: path.with_file_name(tmp_name),
+            path: path.to_path_buf(),
+            committed: false,
+        };
+
+        let mut file = std::fs::File::create(&pending.tmp_path)?;
 
         file.write_all(&self.rtc_zero.to_be_bytes())?;
         file.write_all(&self.data)?;
+        file.sync_all()?;
 
-        Ok(())
+        Ok(pending)
     }
 
     pub fn byte(&self, addr: usize) -> u8 {
@@ -68,3 +124,30 @@ impl SaveState {
         r#box::BoxMut::new(&mut self.data[0x2d10..])
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_pending() {
+        let dir =
+            std::env::temp_dir().join(format!("rustic-crystal-pending-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("save.sav");
+
+        let mut state = SaveState::new();
+        state.write_to_file(&path).unwrap();
+
+        // Dropped without committing: the old save stays and the temporary file is removed
+        state.set_byte(0, 1);
+        drop(state.write_pending(&path).unwrap());
+        assert_eq!(SaveState::from_file(&path).unwrap().byte(0), 0);
+        assert!(!dir.join("save.sav.tmp").exists());
+
+        state.write_pending(&path).unwrap().commit().unwrap();
+        assert_eq!(SaveState::from_file(&path).unwrap().byte(0), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,124 @@
+//! Single mons in `.pk2` files, as used by PKHeX and other Gen 2 save tools.
+//!
+//! A `.pk2` file is a party of one, laid out like the party in SRAM: the count, the species
+//! followed by a terminator, the party struct, the OT name and the nickname. Some tools store
+//! the box struct instead of the party struct, which is read as well.
+
+use crate::{
+    game::constants::{
+        pokemon_constants::{PokemonSpecies, EGG},
+        pokemon_data_constants::MAX_LEVEL,
+        text_constants::{MON_NAME_LENGTH, NAME_LENGTH},
+    },
+    game_state::{
+        box_mon::{BoxMonOwned, BoxMonRef},
+        mon_list::{MonListEntry, MonListMut},
+        party_mon::{PartyMonMut, PartyMonOwned, PartyMonRef},
+    },
+    save_state::string::PokeString,
+};
+
+pub const PK2_LENGTH: usize =
+    PK2_HEADER_LENGTH + PartyMonOwned::LEN + NAME_LENGTH + MON_NAME_LENGTH;
+
+/// The length of a `.pk2` file with the box struct.
+const STORED_PK2_LENGTH: usize =
+    PK2_HEADER_LENGTH + BoxMonOwned::LEN + NAME_LENGTH + MON_NAME_LENGTH;
+
+// The count, the species and the terminator
+const PK2_HEADER_LENGTH: usize = 3;
+
+pub fn write_pk2(entry: MonListEntry<PartyMonRef>) -> [u8; PK2_LENGTH] {
+    let mut data = [0; PK2_LENGTH];
+
+    MonListMut::<PartyMonRef, PartyMonMut, 1>::new(&mut data).push_back(entry);
+
+    data
+}
+
+pub fn read_pk2(data: &[u8]) -> Result<MonListEntry<BoxMonRef<'_>>, String> {
+    let mon_len = match data.len() {
+        PK2_LENGTH => PartyMonOwned::LEN,
+        STORED_PK2_LENGTH => BoxMonOwned::LEN,
+        len => {
+            return Err(format!(
+                "expected {PK2_LENGTH} or {STORED_PK2_LENGTH} bytes, got {len}"
+            ))
+        }
+    };
+
+    if data[0] != 1 || data[2] != 0xff {
+        return Err("expected a single mon".to_owned());
+    }
+
+    let mon = BoxMonRef::new(&data[PK2_HEADER_LENGTH..]);
+    let is_egg = data[1] == EGG;
+
+    if !is_egg && data[1] != u8::from(mon.species()) {
+        return Err("the species in the list doesn't match the mon".to_owned());
+    }
+
+    if matches!(mon.species(), PokemonSpecies::Unknown(_))
+        || !(1..=MAX_LEVEL).contains(&mon.level())
+    {
+        return Err("the mon isn't valid".to_owned());
+    }
+
+    let ot_name = PK2_HEADER_LENGTH + mon_len;
+    let nickname = ot_name + NAME_LENGTH;
+
+    let ot_name = PokeString::new(data[ot_name..nickname].try_into().unwrap());
+    let nickname = PokeString::new(
+        data[nickname..nickname + MON_NAME_LENGTH]
+            .try_into()
+            .unwrap(),
+    );
+
+    Ok(match is_egg {
+        true => MonListEntry::Egg(mon, ot_name, nickname),
+        false => MonListEntry::Mon(mon, ot_name, nickname),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::constants::{
+        item_constants::Item, move_constants::Move, pokemon_constants::PokemonSpecies,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_pk2() {
+        let mut mon = BoxMonOwned::from_box_mon(BoxMonRef::new(&[0; BoxMonOwned::LEN]));
+        mon.set_species(PokemonSpecies::Togepi);
+        mon.set_item(Some(Item::Leftovers));
+        mon.set_moves(&[Move::Metronome][..].into());
+        mon.set_ot_id(54321);
+        mon.set_exp(125);
+        mon.set_level(5);
+
+        let party_mon = PartyMonOwned::from_box_mon(mon.as_ref(), false);
+        let ot_name = PokeString::from_text("KRIS").unwrap();
+        let nickname = PokeString::from_text("Egg-Head").unwrap();
+
+        let data = write_pk2(MonListEntry::Mon(
+            party_mon.as_ref(),
+            ot_name.clone(),
+            nickname.clone(),
+        ));
+
+        assert_eq!(data.len(), 73);
+        assert_eq!(&data[..3], &[1, 0xaf, 0xff]);
+
+        let MonListEntry::Mon(read, read_ot_name, read_nickname) = read_pk2(&data).unwrap() else {
+            panic!("expected a mon");
+        };
+
+        assert_eq!(read, mon.as_ref());
+        assert_eq!(read_ot_name, ot_name);
+        assert_eq!(read_nickname, nickname);
+
+        assert!(read_pk2(&data[..72]).is_err());
+    }
+}
@@ -0,0 +1,209 @@
+//! Conversion to and from the `.sav` files used by other emulators and cartridge dumpers.
+//!
+//! These are the raw 32 KiB of SRAM, optionally followed by the state of the MBC3 clock. The
+//! clock footer is ten little-endian 32-bit words (seconds, minutes, hours, days and the
+//! day high/flags register, first as they are and then as last latched), followed by the UNIX
+//! time at which it was saved: 8 bytes in the VBA-M format, 4 bytes in the BGB format.
+//!
+//! We only store `rtc_zero`, the UNIX time at which the clock read zero, so the registers are
+//! worked out from the time since then, and the other way around.
+
+use std::{
+    io,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::save_state::SaveState;
+
+const SRAM_SIZE: usize = 0x8000;
+const REGISTERS_SIZE: usize = 10 * 4;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+// The clock counts up to 511 days before it sets the carry flag and wraps around
+const DAYS: u64 = 512;
+
+/// What follows the SRAM in a `.sav` file.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RtcFooter {
+    None,
+    /// 48 bytes, with a 64-bit timestamp.
+    VbaM,
+    /// 44 bytes, with a 32-bit timestamp.
+    Bgb,
+}
+
+impl RtcFooter {
+    fn size(self) -> usize {
+        match self {
+            RtcFooter::None => 0,
+            RtcFooter::VbaM => REGISTERS_SIZE + 8,
+            RtcFooter::Bgb => REGISTERS_SIZE + 4,
+        }
+    }
+
+    fn from_size(size: usize) -> Option<RtcFooter> {
+        [RtcFooter::None, RtcFooter::VbaM, RtcFooter::Bgb]
+            .into_iter()
+            .find(|footer| footer.size() == size)
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// The clock registers after `elapsed` seconds.
+fn registers(elapsed: u64) -> [u8; 5] {
+    let days = elapsed / SECONDS_PER_DAY;
+    let carry = if days >= DAYS { 0x80 } else { 0 };
+    let days = days % DAYS;
+
+    [
+        (elapsed % 60) as u8,
+        (elapsed / 60 % 60) as u8,
+        (elapsed / 3600 % 24) as u8,
+        days as u8,
+        (days >> 8) as u8 | carry,
+    ]
+}
+
+/// The seconds since the clock was zero, according to the registers.
+fn elapsed(registers: &[u32]) -> u64 {
+    let days = (registers[3] & 0xff) as u64 | ((registers[4] & 0x01) as u64) << 8;
+
+    (registers[0] & 0x3f) as u64
+        + (registers[1] & 0x3f) as u64 * 60
+        + (registers[2] & 0x1f) as u64 * 3600
+        + days * SECONDS_PER_DAY
+}
+
+impl SaveState {
+    /// Reads a `.sav` file, with or without a clock footer.
+    pub fn from_sav(bytes: &[u8]) -> io::Result<SaveState> {
+        SaveState::from_sav_at(bytes, now())
+    }
+
+    fn from_sav_at(bytes: &[u8], now: u64) -> io::Result<SaveState> {
+        let footer = bytes
+            .len()
+            .checked_sub(SRAM_SIZE)
+            .and_then(RtcFooter::from_size)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "expected {SRAM_SIZE} bytes of SRAM and an optional clock footer, found {} bytes",
+                        bytes.len()
+                    ),
+                )
+            })?;
+
+        let mut state = SaveState::new();
+        state.data.copy_from_slice(&bytes[..SRAM_SIZE]);
+
+        let footer_bytes = &bytes[SRAM_SIZE..];
+
+        state.rtc_zero = match footer {
+            // The clock starts at zero now
+            RtcFooter::None => now,
+            RtcFooter::VbaM | RtcFooter::Bgb => {
+                let registers: Vec<u32> = footer_bytes[..REGISTERS_SIZE]
+                    .chunks_exact(4)
+                    .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+                    .collect();
+
+                let timestamp = match footer {
+                    RtcFooter::VbaM => {
+                        u64::from_le_bytes(footer_bytes[REGISTERS_SIZE..].try_into().unwrap())
+                    }
+                    _ => u32::from_le_bytes(footer_bytes[REGISTERS_SIZE..].try_into().unwrap())
+                        as u64,
+                };
+
+                // The clock kept running since the file was saved, unless it was halted then
+                let halted = registers[4] & 0x40 != 0;
+                let saved_at = if halted { now } else { timestamp };
+
+                saved_at.saturating_sub(elapsed(&registers))
+            }
+        };
+
+        Ok(state)
+    }
+
+    /// Writes the SRAM as a `.sav` file, followed by the clock in the format of `footer`.
+    pub fn to_sav(&self, footer: RtcFooter) -> Vec<u8> {
+        self.to_sav_at(footer, now())
+    }
+
+    fn to_sav_at(&self, footer: RtcFooter, now: u64) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(SRAM_SIZE + footer.size());
+        bytes.extend_from_slice(&self.data);
+
+        if footer == RtcFooter::None {
+            return bytes;
+        }
+
+        let registers = registers(now.saturating_sub(self.rtc_zero));
+
+        // The current registers, then the latched ones
+        for _ in 0..2 {
+            for register in registers {
+                bytes.extend_from_slice(&(register as u32).to_le_bytes());
+            }
+        }
+
+        match footer {
+            RtcFooter::VbaM => bytes.extend_from_slice(&now.to_le_bytes()),
+            _ => bytes.extend_from_slice(&(now as u32).to_le_bytes()),
+        }
+
+        bytes
+    }
+
+    pub fn import_sav(path: &Path) -> io::Result<SaveState> {
+        SaveState::from_sav(&std::fs::read(path)?)
+    }
+
+    pub fn export_sav(&self, path: &Path, footer: RtcFooter) -> io::Result<()> {
+        std::fs::write(path, self.to_sav(footer))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rtc_footer() {
+        let now = 1_700_000_000;
+        let elapsed = 300 * SECONDS_PER_DAY + 13 * 3600 + 37 * 60 + 5;
+
+        let mut state = SaveState::new();
+        state.set_byte(0x2009, 0x42);
+        state.set_rtc_zero(now - elapsed);
+
+        for footer in [RtcFooter::VbaM, RtcFooter::Bgb] {
+            let bytes = state.to_sav_at(footer, now);
+            assert_eq!(bytes.len(), SRAM_SIZE + footer.size());
+            assert_eq!(
+                &bytes[SRAM_SIZE..SRAM_SIZE + 20],
+                &[5, 0, 0, 0, 37, 0, 0, 0, 13, 0, 0, 0, 44, 0, 0, 0, 1, 0, 0, 0]
+            );
+
+            // An hour later, the clock has kept running
+            let loaded = SaveState::from_sav_at(&bytes, now + 3600).unwrap();
+            assert_eq!(loaded.rtc_zero(), now - elapsed);
+            assert_eq!(loaded.byte(0x2009), 0x42);
+        }
+
+        let raw = state.to_sav_at(RtcFooter::None, now);
+        assert_eq!(SaveState::from_sav_at(&raw, now).unwrap().rtc_zero(), now);
+
+        assert!(SaveState::from_sav_at(&raw[..0x7fff], now).is_err());
+    }
+}
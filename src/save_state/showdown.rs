@@ -0,0 +1,623 @@
+//! Teams in the text format of Pokémon Showdown and Smogon, for Gold, Silver and Crystal:
+//!
+//! ```text
+//! Sparky (Pikachu) @ Light Ball
+//! Level: 50
+//! EVs: 252 HP / 252 Atk / 252 Def / 252 SpA / 252 SpD / 252 Spe
+//! IVs: 28 Atk
+//! - Thunderbolt
+//! - Surf
+//! ```
+//!
+//! Showdown has EVs and IVs instead of stat experience and DVs. An EV is the square root of the
+//! stat experience, like in the stat formula, and an IV is twice the DV. The HP DV follows from
+//! the others, and special attack and special defense share the special DV. Like in Showdown,
+//! sets without EVs or IVs are maxed out.
+
+use std::fmt::{Display, Write};
+
+use crate::{
+    game::{
+        constants::{
+            item_constants::Item,
+            move_constants::Move,
+            pokemon_constants::PokemonSpecies,
+            pokemon_data_constants::{MAX_LEVEL, MIN_LEVEL},
+            text_constants::MON_NAME_LENGTH,
+            type_constants::Type,
+        },
+        macros::r#enum::U8Enum,
+    },
+    game_state::{
+        box_mon::{BoxMonOwned, BoxMonRef},
+        moveset::Moveset,
+    },
+    save_state::{determinant_values::DeterminantValues, string::PokeString},
+};
+
+// The EV that Showdown gives when there are none, and the highest one it takes
+const DEFAULT_EV: u16 = 252;
+const MAX_EV: u16 = 255;
+
+const DEFAULT_HAPPINESS: u8 = 255;
+
+/// Moves that Showdown knows by the names of later games.
+const MOVE_NAMES: [(Move, &str); 3] = [
+    (Move::HiJumpKick, "High Jump Kick"),
+    (Move::FaintAttack, "Feint Attack"),
+    (Move::Vicegrip, "Vise Grip"),
+];
+
+/// Items that Showdown knows by the names of later games, or spells with more spaces.
+const ITEM_NAMES: [(Item, &str); 17] = [
+    (Item::PokeBall, "Poké Ball"),
+    (Item::PokeDoll, "Poké Doll"),
+    (Item::ParlyzHeal, "Paralyze Heal"),
+    (Item::XDefend, "X Defense"),
+    (Item::Brightpowder, "Bright Powder"),
+    (Item::Silverpowder, "Silver Powder"),
+    (Item::Twistedspoon, "Twisted Spoon"),
+    (Item::Blackbelt, "Black Belt"),
+    (Item::Blackglasses, "Black Glasses"),
+    (Item::Nevermeltice, "Never-Melt Ice"),
+    (Item::KingsRock, "King's Rock"),
+    (Item::Psncureberry, "PSN Cure Berry"),
+    (Item::Przcureberry, "PRZ Cure Berry"),
+    (Item::Miracleberry, "Miracle Berry"),
+    (Item::Mysteryberry, "Mystery Berry"),
+    (Item::ExpShare, "Exp. Share"),
+    (Item::UpGrade, "Up-Grade"),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShowdownSet {
+    pub species: PokemonSpecies,
+    /// `None` when the mon has the species name.
+    pub nickname: Option<String>,
+    pub item: Option<Item>,
+    pub level: u8,
+    pub happiness: u8,
+    /// The stat experience of HP, attack, defense, speed and special.
+    pub stat_exp: [u16; 5],
+    pub dvs: DeterminantValues,
+    pub moves: Vec<Move>,
+}
+
+impl ShowdownSet {
+    pub fn from_box_mon(mon: BoxMonRef, nickname: &PokeString<MON_NAME_LENGTH>) -> Self {
+        let species = mon.species();
+        let nickname = nickname.to_string();
+
+        ShowdownSet {
+            species,
+            nickname: (nickname != species.name().to_string()).then_some(nickname),
+            item: mon.item(),
+            level: mon.level(),
+            happiness: mon.happiness(),
+            stat_exp: [
+                mon.hp_ev(),
+                mon.attack_ev(),
+                mon.defense_ev(),
+                mon.speed_ev(),
+                mon.special_ev(),
+            ],
+            dvs: mon.dvs(),
+            moves: (0..4).filter_map(|i| mon.moves().get(i)).collect(),
+        }
+    }
+
+    /// The mon, as if it was caught by the trainer with `ot_id`.
+    pub fn to_box_mon(&self, ot_id: u16) -> BoxMonOwned {
+        let mut mon = BoxMonOwned::from_box_mon(BoxMonRef::new(&[0; BoxMonOwned::LEN]));
+        let moves = Moveset::from(&self.moves[..]);
+
+        mon.set_species(self.species);
+        mon.set_item(self.item);
+        mon.set_moves(&moves);
+        mon.set_ot_id(ot_id);
+        mon.set_exp(self.species.growth_rate().exp_at_level(self.level));
+        mon.set_hp_ev(self.stat_exp[0]);
+        mon.set_attack_ev(self.stat_exp[1]);
+        mon.set_defense_ev(self.stat_exp[2]);
+        mon.set_speed_ev(self.stat_exp[3]);
+        mon.set_special_ev(self.stat_exp[4]);
+        mon.set_dvs(self.dvs.clone().into());
+        mon.set_pp(moves.pps());
+        mon.set_happiness(self.happiness);
+        mon.set_level(self.level);
+
+        mon
+    }
+
+    pub fn nickname(&self) -> PokeString<MON_NAME_LENGTH> {
+        self.nickname
+            .as_deref()
+            .and_then(PokeString::from_text)
+            .unwrap_or_else(|| self.species.name())
+    }
+
+    fn parse(lines: &[(usize, &str)]) -> Result<Self, String> {
+        let (header_line, header) = lines[0];
+        let error = |line: usize, message: String| format!("Line {line}: {message}");
+
+        let (name, item) = match header.split_once(" @ ") {
+            Some((name, item)) => (name.trim(), Some(parse_renamed(item.trim(), &ITEM_NAMES))),
+            None => (header.trim(), None),
+        };
+
+        // The gender follows from the DVs
+        let name = name
+            .strip_suffix(" (M)")
+            .or_else(|| name.strip_suffix(" (F)"))
+            .unwrap_or(name);
+
+        let (nickname, species) = match name.strip_suffix(')').and_then(|n| n.rsplit_once(" (")) {
+            Some((nickname, species)) => (Some(nickname.to_owned()), species),
+            None => (None, name),
+        };
+
+        let species = parse_known::<PokemonSpecies>(species).map_err(|e| error(header_line, e))?;
+        let item = item.transpose().map_err(|e| error(header_line, e))?;
+
+        if let Some(nickname) = &nickname {
+            if PokeString::<MON_NAME_LENGTH>::from_text(nickname).is_none() {
+                return Err(error(
+                    header_line,
+                    format!("{nickname} can't be used as a nickname"),
+                ));
+            }
+        }
+
+        let mut set = ShowdownSet {
+            species,
+            nickname,
+            item,
+            level: MAX_LEVEL,
+            happiness: DEFAULT_HAPPINESS,
+            stat_exp: [DEFAULT_EV * DEFAULT_EV; 5],
+            dvs: DeterminantValues::new(15, 15, 15, 15),
+            moves: Vec::new(),
+        };
+
+        let mut shiny = false;
+        let mut has_dvs = false;
+        let mut hidden_power = None;
+
+        for &(n, line) in &lines[1..] {
+            let (key, value) = line.split_once(':').unwrap_or((line, ""));
+            let value = value.trim();
+
+            match key.trim() {
+                _ if line.starts_with('-') || line.starts_with('~') => {
+                    // Showdown names the type of Hidden Power, which follows from the DVs
+                    let (name, r#type) = match line[1..].split_once(" [") {
+                        Some((name, r#type)) => (name, Some(r#type.trim_end_matches(']'))),
+                        None => (&line[1..], None),
+                    };
+                    let r#move =
+                        parse_renamed(name.trim(), &MOVE_NAMES).map_err(|e| error(n, e))?;
+
+                    if let (Move::HiddenPower, Some(r#type)) = (r#move, r#type) {
+                        let r#type = parse_known::<Type>(r#type)
+                            .ok()
+                            .filter(|&t| DeterminantValues::max_for_hidden_power(t).is_some())
+                            .ok_or_else(|| error(n, format!("Hidden Power can't be {}", r#type)))?;
+
+                        hidden_power = Some((n, r#type));
+                    }
+
+                    if set.moves.len() == 4 {
+                        return Err(error(n, "a mon can't have more than 4 moves".to_owned()));
+                    }
+
+                    set.moves.push(r#move);
+                }
+                "Level" => {
+                    set.level = value
+                        .parse()
+                        .ok()
+                        .filter(|level| (MIN_LEVEL..=MAX_LEVEL).contains(level))
+                        .ok_or_else(|| {
+                            error(
+                                n,
+                                format!("the level must be between {MIN_LEVEL} and {MAX_LEVEL}"),
+                            )
+                        })?;
+                }
+                "Happiness" => {
+                    set.happiness = value
+                        .parse()
+                        .map_err(|_| error(n, format!("invalid happiness: {value}")))?;
+                }
+                "Shiny" => shiny = value.eq_ignore_ascii_case("yes"),
+                "EVs" => {
+                    let evs = parse_stats(value, 0, MAX_EV).map_err(|e| error(n, e))?;
+
+                    set.stat_exp = [evs.hp, evs.attack, evs.defense, evs.speed, evs.special]
+                        .map(|ev| (ev as u32 * ev as u32).min(u16::MAX as u32) as u16);
+                }
+                "IVs" | "DVs" => {
+                    let (default, max) = if key == "IVs" { (30, 31) } else { (15, 15) };
+                    let values = parse_stats(value, default, max).map_err(|e| error(n, e))?;
+                    let dv = |value: u16| if key == "IVs" { value / 2 } else { value } as u8;
+
+                    set.dvs = DeterminantValues::new(
+                        dv(values.attack),
+                        dv(values.defense),
+                        dv(values.speed),
+                        dv(values.special),
+                    );
+                    has_dvs = true;
+                }
+                // Not in Gold, Silver and Crystal, but Showdown sometimes writes them anyway
+                "Ability" | "Nature" => {}
+                _ => return Err(error(n, format!("unexpected line: {line}"))),
+            }
+        }
+
+        if set.moves.is_empty() {
+            return Err(error(
+                header_line,
+                "a mon needs at least one move".to_owned(),
+            ));
+        }
+
+        if shiny && !has_dvs {
+            set.dvs = DeterminantValues::new(15, 10, 10, 10);
+        }
+
+        if let Some((n, r#type)) = hidden_power {
+            if set.dvs.hidden_power_type() != r#type {
+                if has_dvs || shiny {
+                    return Err(error(
+                        n,
+                        format!(
+                            "the DVs give Hidden Power {:?}, not {:?}",
+                            set.dvs.hidden_power_type(),
+                            r#type
+                        ),
+                    ));
+                }
+
+                set.dvs = DeterminantValues::max_for_hidden_power(r#type).unwrap();
+            }
+        }
+
+        Ok(set)
+    }
+}
+
+impl Display for ShowdownSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.nickname {
+            Some(nickname) => write!(f, "{nickname} ({})", species_name(self.species))?,
+            None => f.write_str(&species_name(self.species))?,
+        }
+
+        // Showdown can't read an item that's not in the game
+        if let Some(item) = self.item {
+            if !item.is_known() {
+                return Err(std::fmt::Error);
+            }
+
+            match showdown_name(item, &ITEM_NAMES) {
+                Some(name) => write!(f, " @ {name}")?,
+                None => write!(f, " @ {}", split_words(&format!("{item:?}")))?,
+            }
+        }
+
+        writeln!(f)?;
+
+        if self.level != MAX_LEVEL {
+            writeln!(f, "Level: {}", self.level)?;
+        }
+
+        if self.dvs.is_shiny() {
+            writeln!(f, "Shiny: Yes")?;
+        }
+
+        if self.happiness != DEFAULT_HAPPINESS {
+            writeln!(f, "Happiness: {}", self.happiness)?;
+        }
+
+        let evs = self
+            .stat_exp
+            .map(|stat_exp| ((stat_exp as f64).sqrt().ceil() as u16).min(MAX_EV));
+
+        if evs != [DEFAULT_EV; 5] {
+            let [hp, attack, defense, speed, special] = evs;
+            let evs = format_stats([hp, attack, defense, special, special, speed], |ev| ev > 0);
+            writeln!(f, "EVs: {}", evs.as_deref().unwrap_or("0 HP"))?;
+        }
+
+        let dvs = &self.dvs;
+        let ivs = [
+            dvs.hp(),
+            dvs.attack(),
+            dvs.defense(),
+            dvs.special(),
+            dvs.special(),
+            dvs.speed(),
+        ]
+        .map(|dv| dv as u16 * 2);
+
+        if let Some(ivs) = format_stats(ivs, |iv| iv != 30) {
+            writeln!(f, "IVs: {ivs}")?;
+        }
+
+        for &r#move in &self.moves {
+            match showdown_name(r#move, &MOVE_NAMES) {
+                Some(name) => writeln!(f, "- {name}")?,
+                None if r#move == Move::HiddenPower => {
+                    writeln!(f, "- Hidden Power [{:?}]", self.dvs.hidden_power_type())?
+                }
+                None => writeln!(f, "- {}", title_case(&r#move.name().to_string()))?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses a team, one set per paragraph. The `=== [gen2] Team ===` headers of exported
+/// teambuilder backups are skipped.
+pub fn parse_team(text: &str) -> Result<Vec<ShowdownSet>, String> {
+    let mut sets = Vec::new();
+    let mut lines = Vec::new();
+
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+
+        if !line.is_empty() && !line.starts_with("===") {
+            lines.push((n + 1, line));
+            continue;
+        }
+
+        if !lines.is_empty() {
+            sets.push(ShowdownSet::parse(&lines)?);
+            lines.clear();
+        }
+    }
+
+    if !lines.is_empty() {
+        sets.push(ShowdownSet::parse(&lines)?);
+    }
+
+    Ok(sets)
+}
+
+/// Writes a team, with an empty line after each set.
+/// The sets in Showdown's format, or an error naming a mon that holds an item Showdown can't
+/// read.
+pub fn format_team(sets: &[ShowdownSet]) -> Result<String, String> {
+    let mut team = String::new();
+
+    for set in sets {
+        writeln!(team, "{set}").map_err(|_| {
+            let item = set.item.map_or(0, u8::from);
+            format!(
+                "{} holds an unknown item: {item}",
+                species_name(set.species)
+            )
+        })?;
+    }
+
+    Ok(team)
+}
+
+/// Parses a name with the enum's `FromStr`, which also takes numbers, and rejects the ones that
+/// aren't known.
+fn parse_known<T: U8Enum>(name: &str) -> Result<T, String> {
+    let value = name.parse::<T>()?;
+
+    if !value.is_known() || value.into() == 0 {
+        return Err(format!("unknown name: {name}"));
+    }
+
+    Ok(value)
+}
+
+/// Parses a name like `parse_known`, or the name Showdown uses instead, from `names`.
+fn parse_renamed<T: U8Enum>(name: &str, names: &[(T, &str)]) -> Result<T, String> {
+    match names
+        .iter()
+        .find(|(_, showdown)| showdown.eq_ignore_ascii_case(name))
+    {
+        Some(&(value, _)) => Ok(value),
+        None => parse_known(name),
+    }
+}
+
+/// The name Showdown uses for `value`, if it's in `names`.
+fn showdown_name<T: PartialEq>(value: T, names: &[(T, &'static str)]) -> Option<&'static str> {
+    names
+        .iter()
+        .find(|(known, _)| *known == value)
+        .map(|&(_, name)| name)
+}
+
+struct Stats {
+    hp: u16,
+    attack: u16,
+    defense: u16,
+    speed: u16,
+    special: u16,
+}
+
+/// Parses `252 HP / 252 Atk / ...`. The HP DV follows from the others, so it's ignored for IVs
+/// and DVs, and special attack and special defense share a value.
+fn parse_stats(text: &str, default: u16, max: u16) -> Result<Stats, String> {
+    let mut stats = Stats {
+        hp: default,
+        attack: default,
+        defense: default,
+        speed: default,
+        special: default,
+    };
+
+    for stat in text.split('/') {
+        let (value, name) = stat
+            .trim()
+            .split_once(' ')
+            .ok_or_else(|| format!("invalid stat: {stat}"))?;
+
+        let value = value
+            .parse()
+            .ok()
+            .filter(|&value| value <= max)
+            .ok_or_else(|| format!("stats must be between 0 and {max}, got {value}"))?;
+
+        match name.trim().to_ascii_lowercase().as_str() {
+            "hp" => stats.hp = value,
+            "atk" => stats.attack = value,
+            "def" => stats.defense = value,
+            "spe" => stats.speed = value,
+            "spa" | "spd" | "spc" => stats.special = value,
+            _ => return Err(format!("unknown stat: {name}")),
+        }
+    }
+
+    Ok(stats)
+}
+
+/// Formats the stats that `include`, in Showdown's order, or `None` if there are none.
+fn format_stats(values: [u16; 6], include: impl Fn(u16) -> bool) -> Option<String> {
+    let stats: Vec<String> = ["HP", "Atk", "Def", "SpA", "SpD", "Spe"]
+        .into_iter()
+        .zip(values)
+        .filter(|&(_, value)| include(value))
+        .map(|(name, value)| format!("{value} {name}"))
+        .collect();
+
+    (!stats.is_empty()).then(|| stats.join(" / "))
+}
+
+/// The name Showdown uses for `species`, where it differs from the name of the variant.
+fn species_name(species: PokemonSpecies) -> String {
+    match species {
+        PokemonSpecies::NidoranF => "Nidoran-F".to_owned(),
+        PokemonSpecies::NidoranM => "Nidoran-M".to_owned(),
+        PokemonSpecies::Farfetchd => "Farfetch'd".to_owned(),
+        PokemonSpecies::MrMime => "Mr. Mime".to_owned(),
+        PokemonSpecies::HoOh => "Ho-Oh".to_owned(),
+        species => format!("{species:?}"),
+    }
+}
+
+/// `MiracleBerry` to `Miracle Berry`.
+fn split_words(name: &str) -> String {
+    let mut result = String::new();
+
+    for (i, ch) in name.chars().enumerate() {
+        if i > 0 && ch.is_ascii_uppercase() {
+            result.push(' ');
+        }
+
+        result.push(ch);
+    }
+
+    result
+}
+
+/// `DOUBLE-EDGE` to `Double-Edge`.
+fn title_case(name: &str) -> String {
+    let mut result = String::new();
+    let mut word_start = true;
+
+    for ch in name.chars() {
+        if word_start {
+            result.extend(ch.to_uppercase());
+        } else {
+            result.extend(ch.to_lowercase());
+        }
+
+        word_start = !ch.is_alphanumeric();
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_team() {
+        let team = "=== [gen2] Test ===
+
+Sparky (Pikachu) (M) @ Light Ball
+Level: 50
+EVs: 252 HP / 100 Atk
+IVs: 28 Atk / 20 SpA / 20 SpD
+- Thunderbolt
+- Hidden Power [Electric]
+
+Mr. Mime @ Leftovers
+Shiny: Yes
+- Psychic
+";
+
+        let sets = parse_team(team).unwrap();
+        assert_eq!(sets.len(), 2);
+
+        let sparky = &sets[0];
+        assert_eq!(sparky.species, PokemonSpecies::Pikachu);
+        assert_eq!(sparky.nickname.as_deref(), Some("Sparky"));
+        assert_eq!(sparky.item, Some(Item::LightBall));
+        assert_eq!(sparky.level, 50);
+        assert_eq!(sparky.stat_exp, [63504, 10000, 0, 0, 0]);
+        assert_eq!(sparky.dvs, DeterminantValues::new(14, 15, 15, 10));
+        assert_eq!(sparky.moves, [Move::Thunderbolt, Move::HiddenPower]);
+
+        let mr_mime = &sets[1];
+        assert_eq!(mr_mime.species, PokemonSpecies::MrMime);
+        assert_eq!(mr_mime.nickname, None);
+        assert!(mr_mime.dvs.is_shiny());
+
+        // The move names come from the ROM
+        let mut mr_mime = mr_mime.clone();
+        mr_mime.moves.clear();
+        assert_eq!(
+            mr_mime.to_string(),
+            "Mr. Mime @ Leftovers\nShiny: Yes\nIVs: 16 HP / 20 Def / 20 SpA / 20 SpD / 20 Spe\n"
+        );
+
+        // Showdown's names for renamed moves, and Hidden Power that decides the DVs
+        let hitmonlee =
+            &parse_team("Hitmonlee\n- High Jump Kick\n- Hidden Power [Ice]").unwrap()[0];
+        assert_eq!(hitmonlee.moves, [Move::HiJumpKick, Move::HiddenPower]);
+        assert_eq!(hitmonlee.dvs, DeterminantValues::new(15, 13, 15, 15));
+        assert_eq!(hitmonlee.dvs.hidden_power_type(), Type::Ice);
+
+        assert!(parse_team("Pikachu\nIVs: 28 Atk\n- Hidden Power [Ice]").is_err());
+        assert!(parse_team("Pikachu\n- Hidden Power [Normal]").is_err());
+        assert!(parse_team("Pikachu\n- Splash Attack").is_err());
+        assert!(parse_team("Pikachu @ Nothing\n- Thunderbolt").is_err());
+    }
+
+    #[test]
+    fn test_format_team() {
+        let mut set = parse_team("Cubone @ Thick Club\n- Bone Club")
+            .unwrap()
+            .remove(0);
+        assert_eq!(set.item, Some(Item::ThickClub));
+
+        // The move names come from the ROM
+        set.moves.clear();
+        assert_eq!(
+            format_team(&[set.clone()]).unwrap(),
+            "Cubone @ Thick Club\n\n"
+        );
+
+        set.item = Some(Item::Przcureberry);
+        assert!(format_team(&[set.clone()])
+            .unwrap()
+            .starts_with("Cubone @ PRZ Cure Berry\n"));
+
+        set.item = Some(Item::Unknown(0x19));
+        assert_eq!(
+            format_team(&[set]),
+            Err("Cubone holds an unknown item: 25".to_owned())
+        );
+    }
+}
@@ -3,7 +3,7 @@ use std::{
     ops::Index,
 };
 
-use crate::game::constants::text_constants::PrintNum;
+use crate::game::{constants::text_constants::PrintNum, home::text::char_to_tile};
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub struct PokeString<const N: usize>([u8; N]);
@@ -74,6 +74,22 @@ impl<const N: usize> PokeString<N> {
         }
         Self(arr)
     }
+
+    /// Encodes `s`, or returns `None` if it has characters the game can't show or doesn't leave
+    /// room for the terminator.
+    pub fn from_text(s: &str) -> Option<Self> {
+        let mut arr = [0x50; N];
+
+        for (i, ch) in s.chars().enumerate() {
+            if i + 1 >= N {
+                return None;
+            }
+
+            arr[i] = char_to_tile(ch)?;
+        }
+
+        Some(Self(arr))
+    }
 }
 
 impl<const N: usize> AsRef<[u8]> for PokeString<N> {
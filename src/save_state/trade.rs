@@ -0,0 +1,296 @@
+//! Trades between two saves, without a link cable.
+//!
+//! A trade swaps a party mon of each save the way a link trade does: the mons keep their original
+//! trainer, their happiness is reset, their mail goes with them, they're registered in the
+//! Pokédex of the save that receives them, and they evolve if their species evolves by trade.
+//! Like in the game, the received mon is added at the end of the party.
+
+use crate::{
+    game::{
+        constants::{
+            battle_constants::NUM_MOVES,
+            item_constants::Item,
+            item_data_constants::MAIL_STRUCT_LENGTH,
+            pokemon_constants::PokemonSpecies,
+            pokemon_data_constants::{Evolution, BASE_HAPPINESS, PARTY_LENGTH},
+            text_constants::{MON_NAME_LENGTH, NAME_LENGTH},
+        },
+        data::pokemon::evos_attacks::EVOS_ATTACKS,
+    },
+    game_state::{
+        box_mon::BoxMonRef,
+        mon_list::MonListEntry,
+        party_mon::{PartyMonMut, PartyMonOwned, PartyMonRef},
+    },
+    save_state::{string::PokeString, SaveState},
+};
+
+// sPartyMail, followed by sPartyMailBackup
+const PARTY_MAIL: usize = 0x600;
+const PARTY_MAIL_BACKUP: usize = PARTY_MAIL + PARTY_LENGTH * MAIL_STRUCT_LENGTH;
+
+/// A mon on its way to the other save.
+struct Offer {
+    mon: [u8; PartyMonOwned::LEN],
+    is_egg: bool,
+    ot_name: PokeString<NAME_LENGTH>,
+    nickname: PokeString<MON_NAME_LENGTH>,
+    mail: [u8; MAIL_STRUCT_LENGTH],
+}
+
+/// Trades the party mon at `index` in `a` for the party mon at `other_index` in `b`.
+///
+/// Returns the species that the mon received by `a`, and the one received by `b`, evolved into.
+pub fn trade(
+    a: &mut SaveState,
+    index: usize,
+    b: &mut SaveState,
+    other_index: usize,
+) -> Result<(Option<PokemonSpecies>, Option<PokemonSpecies>), String> {
+    let offer_a = offer(a, index)?;
+    let offer_b = offer(b, other_index)?;
+
+    if !can_battle_after_trade(a, index, &offer_b)
+        || !can_battle_after_trade(b, other_index, &offer_a)
+    {
+        return Err("The trade would leave a party without a mon that can battle".to_owned());
+    }
+
+    Ok((receive(a, index, offer_b), receive(b, other_index, offer_a)))
+}
+
+fn offer(state: &SaveState, index: usize) -> Result<Offer, String> {
+    let party = state.party();
+
+    let (mon, is_egg, ot_name, nickname) = match party.get(index) {
+        Some(MonListEntry::Mon(mon, ot_name, nickname)) => (mon, false, ot_name, nickname),
+        Some(MonListEntry::Egg(mon, ot_name, nickname)) => (mon, true, ot_name, nickname),
+        None => return Err(format!("The party has no mon {}", index + 1)),
+    };
+
+    let mail = PARTY_MAIL + index * MAIL_STRUCT_LENGTH;
+
+    Ok(Offer {
+        mon: mon.to_vec().try_into().unwrap(),
+        is_egg,
+        ot_name,
+        nickname,
+        mail: state.data[mail..mail + MAIL_STRUCT_LENGTH]
+            .try_into()
+            .unwrap(),
+    })
+}
+
+/// Whether the party still has a mon that isn't an egg or fainted, like the game checks before a
+/// trade.
+fn can_battle_after_trade(state: &SaveState, index: usize, received: &Offer) -> bool {
+    let party = state.party();
+
+    let can_battle = |i| matches!(party.get(i), Some(MonListEntry::Mon(mon, ..)) if mon.hp() > 0);
+
+    (0..party.len()).any(|i| i != index && can_battle(i))
+        || (!received.is_egg && PartyMonRef::new(&received.mon).hp() > 0)
+}
+
+fn receive(state: &mut SaveState, index: usize, mut offer: Offer) -> Option<PokemonSpecies> {
+    let species = PartyMonRef::new(&offer.mon).species();
+
+    // Eggs keep their happiness, since it counts the cycles until they hatch
+    let evolution = match offer.is_egg {
+        true => None,
+        false => {
+            PartyMonMut::new(&mut offer.mon).set_happiness(BASE_HAPPINESS);
+            evolve(&mut offer)
+        }
+    };
+
+    {
+        let mon = PartyMonRef::new(&offer.mon);
+        let mut party = state.party_mut();
+        let mut list = party.list();
+
+        list.remove(index);
+        list.push_back(match offer.is_egg {
+            true => MonListEntry::Egg(mon, offer.ot_name, offer.nickname),
+            false => MonListEntry::Mon(mon, offer.ot_name, offer.nickname),
+        });
+    }
+
+    // The mail of the mons after the traded one moves up with them
+    let len = state.party().len();
+
+    for mail in [PARTY_MAIL, PARTY_MAIL_BACKUP] {
+        state.data.copy_within(
+            mail + (index + 1) * MAIL_STRUCT_LENGTH..mail + len * MAIL_STRUCT_LENGTH,
+            mail + index * MAIL_STRUCT_LENGTH,
+        );
+
+        let last = mail + (len - 1) * MAIL_STRUCT_LENGTH;
+        state.data[last..last + MAIL_STRUCT_LENGTH].copy_from_slice(&offer.mail);
+    }
+
+    if !offer.is_egg {
+        for species in [Some(species), evolution].into_iter().flatten() {
+            state.set_pokedex_seen(species, true);
+            state.set_pokedex_caught(species, true);
+        }
+    }
+
+    evolution
+}
+
+/// Evolves a traded mon if its species evolves by trade, which is stopped by an Everstone.
+///
+/// The moves that the new species learns at the mon's level are learnt when there's room for
+/// them, since there's no one to ask which move to forget.
+fn evolve(offer: &mut Offer) -> Option<PokemonSpecies> {
+    let mon = PartyMonRef::new(&offer.mon);
+    let item = mon.item();
+    let old_species = mon.species();
+    let old_max_hp = mon.max_hp();
+
+    if item == Some(Item::Everstone) {
+        return None;
+    }
+
+    let (new_species, uses_item) = (u8::from(old_species) as usize)
+        .checked_sub(1)
+        .and_then(|index| EVOS_ATTACKS.get(index))?
+        .evos
+        .iter()
+        .find_map(|evolution| match *evolution {
+            Evolution::Trade(None, species) => Some((species, false)),
+            Evolution::Trade(Some(trade_item), species) if item == Some(trade_item) => {
+                Some((species, true))
+            }
+            _ => None,
+        })?;
+
+    let mut mon = PartyMonMut::new(&mut offer.mon);
+
+    mon.set_species(new_species);
+
+    if uses_item {
+        mon.set_item(None);
+    }
+
+    let stats = PartyMonOwned::from_box_mon(BoxMonRef::new(&offer.mon), false);
+    let stats = stats.as_ref();
+    let mut mon = PartyMonMut::new(&mut offer.mon);
+
+    mon.set_max_hp(stats.max_hp());
+    mon.set_attack(stats.attack());
+    mon.set_defense(stats.defense());
+    mon.set_speed(stats.speed());
+    mon.set_special_attack(stats.spcl_atk());
+    mon.set_special_defense(stats.spcl_def());
+
+    let mon = PartyMonRef::new(&offer.mon);
+    let hp = mon.hp() + stats.max_hp().saturating_sub(old_max_hp);
+    let level = mon.level();
+    let known_moves = mon.moves();
+    let mut moves: Vec<_> = (0..known_moves.len())
+        .filter_map(|i| known_moves.get(i))
+        .collect();
+    let mut pp = mon.pp();
+
+    for &(learn_level, learn_move) in EVOS_ATTACKS[u8::from(new_species) as usize - 1].level_up {
+        if learn_level == level && moves.len() < NUM_MOVES && !moves.contains(&learn_move) {
+            pp[moves.len()] = learn_move.pp();
+            moves.push(learn_move);
+        }
+    }
+
+    let mut mon = PartyMonMut::new(&mut offer.mon);
+
+    mon.set_hp(hp);
+    mon.set_moves(&moves[..].into());
+    mon.set_pp(pp);
+
+    if offer.nickname == old_species.name() {
+        offer.nickname = new_species.name();
+    }
+
+    Some(new_species)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_save(party: &[(PokemonSpecies, Option<Item>)]) -> SaveState {
+        let mut state = SaveState::new_intact();
+
+        for (i, &(species, item)) in party.iter().enumerate() {
+            let mut mon = [0; PartyMonOwned::LEN];
+            let mut mon_mut = PartyMonMut::new(&mut mon);
+
+            mon_mut.set_species(species);
+            mon_mut.set_item(item);
+            mon_mut.set_level(40);
+            mon_mut.set_hp(100);
+            mon_mut.set_happiness(255);
+
+            let ot_name = PokeString::from_text("GOLD").unwrap();
+            let nickname = PokeString::from_text("SPOOKY").unwrap();
+
+            state.party_mut().list().push_back(MonListEntry::Mon(
+                PartyMonRef::new(&mon),
+                ot_name,
+                nickname,
+            ));
+
+            state.data[PARTY_MAIL + i * MAIL_STRUCT_LENGTH] = i as u8 + 1;
+        }
+
+        state
+    }
+
+    #[test]
+    fn test_trade() {
+        let mut a = new_save(&[
+            (PokemonSpecies::Haunter, None),
+            (PokemonSpecies::Pidgey, None),
+        ]);
+        let mut b = new_save(&[(PokemonSpecies::Onix, Some(Item::Everstone))]);
+
+        let (a_evolution, b_evolution) = trade(&mut a, 0, &mut b, 0).unwrap();
+
+        assert_eq!(a_evolution, None);
+        assert_eq!(
+            b_evolution,
+            (!cfg!(feature = "legacy")).then_some(PokemonSpecies::Gengar)
+        );
+
+        let party = a.party();
+        let Some(MonListEntry::Mon(onix, ot_name, _)) = party.get(1) else {
+            panic!("expected the Onix at the end of the party");
+        };
+
+        assert_eq!(onix.species(), PokemonSpecies::Onix);
+        assert_eq!(onix.happiness(), BASE_HAPPINESS);
+        assert_eq!(ot_name, PokeString::from_text("GOLD").unwrap());
+        assert_eq!(a.data[PARTY_MAIL], 2);
+        assert_eq!(a.data[PARTY_MAIL + MAIL_STRUCT_LENGTH], 1);
+        assert_eq!(a.data[PARTY_MAIL_BACKUP + MAIL_STRUCT_LENGTH], 1);
+        assert!(a.pokedex_caught(PokemonSpecies::Onix));
+        assert!(a.is_valid());
+
+        assert!(b.pokedex_caught(PokemonSpecies::Haunter));
+        assert_eq!(b.data[PARTY_MAIL], 1);
+
+        // Trading away the only mon that can battle for an egg isn't allowed
+        let mut c = new_save(&[(PokemonSpecies::Togepi, None)]);
+        {
+            let mut party = c.party_mut();
+            let mut list = party.list();
+            let mon = list.get(0).unwrap().mon().to_vec();
+            let ot_name = PokeString::from_text("GOLD").unwrap();
+            let nickname = PokeString::from_text("EGG").unwrap();
+            list.remove(0);
+            list.push_back(MonListEntry::Egg(PartyMonRef::new(&mon), ot_name, nickname));
+        }
+
+        assert!(trade(&mut b, 0, &mut c, 0).is_err());
+    }
+}
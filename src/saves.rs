@@ -2,7 +2,17 @@
 // https://savelocation.net/factorio
 // https://savelocation.net/prison-architect
 
-use std::{env, ffi::OsString, fs, io::Result, path::PathBuf};
+use std::{
+    collections::HashSet,
+    env, fs,
+    io::{Error, ErrorKind, Result},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::save_state::{sav_file::RtcFooter, PendingWrite, SaveState};
 
 #[cfg(target_os = "macos")]
 fn get_save_dir() -> PathBuf {
@@ -20,59 +30,518 @@ fn get_save_dir() -> PathBuf {
     PathBuf::from(env::var("appdata").unwrap()).join("Rustic Crystal\\saves")
 }
 
+pub fn get_crash_reports_dir() -> PathBuf {
+    get_save_dir().with_file_name("crash-reports")
+}
+
+pub fn get_screenshots_dir() -> PathBuf {
+    get_save_dir().with_file_name("screenshots")
+}
+
+pub fn get_settings_path() -> PathBuf {
+    get_save_dir().with_file_name("settings.toml")
+}
+
+/// How many backups are kept of each save, older ones are deleted.
+const MAX_BACKUPS: usize = 5;
+
+fn get_backups_dir(id: &str) -> PathBuf {
+    get_save_dir().join("backups").join(id)
+}
+
+/// The version of the game data a save was made with. Saves load with either, but trainers and
+/// the like won't match what the game expects with the other one.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DataSet {
+    Vanilla,
+    /// The changes from Pokémon Crystal Legacy, with the `legacy` feature.
+    Legacy,
+}
+
+impl DataSet {
+    pub fn current() -> DataSet {
+        if cfg!(feature = "legacy") {
+            DataSet::Legacy
+        } else {
+            DataSet::Vanilla
+        }
+    }
+}
+
+/// What's known about a save besides its SRAM, stored next to it as `<id>.toml`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SaveMeta {
+    name: String,
+    /// UNIX times, in seconds.
+    created: u64,
+    modified: u64,
+    data_set: DataSet,
+}
+
+impl SaveMeta {
+    fn new(name: &str) -> SaveMeta {
+        SaveMeta {
+            name: name.to_owned(),
+            created: now(),
+            modified: now(),
+            data_set: DataSet::current(),
+        }
+    }
+}
+
+/// A save, stored as `<id>.sav`. The ID never changes, so that saves can share a name and be
+/// renamed freely.
 pub struct SaveFile {
+    pub id: String,
     pub path: PathBuf,
+    /// The name shown in the list of saves.
     pub name: String,
+    /// When the game was last saved.
+    pub modified: SystemTime,
+    pub data_set: DataSet,
+}
+
+impl SaveFile {
+    fn new(id: String, meta: SaveMeta) -> SaveFile {
+        SaveFile {
+            path: get_save_path(&id),
+            id,
+            name: meta.name,
+            modified: UNIX_EPOCH + Duration::from_secs(meta.modified),
+            data_set: meta.data_set,
+        }
+    }
+
+    fn open(path: &Path) -> Result<SaveFile> {
+        let id = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "invalid save path"))?;
+
+        Ok(SaveFile::new(id.to_owned(), read_meta(id)?))
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
 }
 
 pub fn create_save_dir() -> Result<()> {
     fs::create_dir_all(get_save_dir())
 }
 
-pub fn get_save_path(name: &str) -> PathBuf {
-    let ext: OsString = OsString::from("sav");
-    get_save_dir().join(name).with_extension(ext)
+fn get_save_path(id: &str) -> PathBuf {
+    get_save_dir().join(id).with_extension("sav")
+}
+
+fn get_meta_path(id: &str) -> PathBuf {
+    get_save_dir().join(id).with_extension("toml")
+}
+
+/// A new ID for a save, based on the current time.
+fn new_id() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0);
+
+    (nanos..)
+        .map(|n| format!("{n:016x}"))
+        .find(|id| !get_save_path(id).exists() && !get_meta_path(id).exists())
+        .unwrap()
+}
+
+fn read_meta(id: &str) -> Result<SaveMeta> {
+    let text = fs::read_to_string(get_meta_path(id))?;
+    toml::from_str(&text).map_err(|error| Error::new(ErrorKind::InvalidData, error))
+}
+
+fn write_meta(id: &str, meta: &SaveMeta) -> Result<()> {
+    let path = get_meta_path(id);
+    let tmp_path = path.with_extension("toml.tmp");
+
+    fs::write(&tmp_path, toml::to_string(meta).map_err(Error::other)?)?;
+    fs::rename(tmp_path, path)
+}
+
+/// Starts a new save called `name`, returning where the game should save it.
+pub fn create_save(name: &str) -> Result<PathBuf> {
+    create_save_dir()?;
+
+    if let Err(error) = remove_unsaved_metas() {
+        log::warn!("Failed to clean up the saves that were never saved: {error}");
+    }
+
+    let id = new_id();
+    write_meta(&id, &SaveMeta::new(name))?;
+
+    Ok(get_save_path(&id))
+}
+
+/// Deletes the metadata left behind by new games that were never saved, which is only done when
+/// another one starts so that a game in progress keeps its name.
+fn remove_unsaved_metas() -> Result<()> {
+    for entry in fs::read_dir(get_save_dir())? {
+        let path = entry?.path();
+
+        if path.extension() == Some("toml".as_ref()) && !path.with_extension("sav").exists() {
+            fs::remove_file(path)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes the save at `path`, after backing up the previous version.
+pub fn write_save(path: &Path, state: &SaveState) -> Result<()> {
+    // A failed backup shouldn't stop the game from saving
+    if let Err(error) = backup_save(path) {
+        log::error!("Failed to back up {}: {error}", path.display());
+    }
+
+    state.write_to_file(path)?;
+    update_modified(path, &state.player_name().to_string());
+
+    Ok(())
+}
+
+/// A save that was written next to its path, and replaces it once it's committed.
+pub struct PendingSave {
+    write: PendingWrite,
+    /// The path and player name of one of the game's saves, whose metadata is updated with it.
+    game_save: Option<(PathBuf, String)>,
+}
+
+impl PendingSave {
+    pub fn commit(self) -> Result<()> {
+        self.write.commit()?;
+
+        if let Some((path, player_name)) = self.game_save {
+            update_modified(&path, &player_name);
+        }
+
+        Ok(())
+    }
+}
+
+impl From<PendingWrite> for PendingSave {
+    fn from(write: PendingWrite) -> PendingSave {
+        PendingSave {
+            write,
+            game_save: None,
+        }
+    }
+}
+
+/// Writes the save at `path` like `write_save`, but only replaces it once the result is
+/// committed, so that several saves can be replaced together.
+pub fn write_save_pending(path: &Path, state: &SaveState) -> Result<PendingSave> {
+    if let Err(error) = backup_save(path) {
+        log::error!("Failed to back up {}: {error}", path.display());
+    }
+
+    Ok(PendingSave {
+        write: state.write_pending(path)?,
+        game_save: Some((path.to_owned(), state.player_name().to_string())),
+    })
+}
+
+/// Whether `path` is one of the game's saves, rather than a file elsewhere.
+pub fn is_game_save(path: &Path) -> bool {
+    let Ok(path) = fs::canonicalize(path) else {
+        return false;
+    };
+
+    path.extension() == Some("sav".as_ref())
+        && fs::canonicalize(get_save_dir()).is_ok_and(|dir| path.parent() == Some(&dir))
+}
+
+/// Marks the save at `path` as saved now, recreating its metadata if it's missing.
+fn update_modified(path: &Path, player_name: &str) {
+    let Some(id) = path.file_stem().and_then(|stem| stem.to_str()) else {
+        return;
+    };
+
+    let mut meta = read_meta(id).unwrap_or_else(|error| {
+        log::warn!("Recreating the metadata of {id}: {error}");
+        SaveMeta::new(player_name)
+    });
+
+    meta.modified = now();
+
+    if let Err(error) = write_meta(id, &meta) {
+        log::error!("Failed to update the metadata of {id}: {error}");
+    }
 }
 
 pub fn save_is_free(name: &str) -> bool {
-    !get_save_path(name).exists()
+    list_save_files().map_or(true, |files| files.iter().all(|save| save.name != name))
 }
 
-pub fn list_save_files() -> Result<Vec<SaveFile>> {
-    let ext: OsString = OsString::from("sav");
+/// Picks a save name that isn't used yet based on `name`, by adding a number to it if it's
+/// taken.
+pub fn free_save_name(name: &str) -> String {
+    let taken: HashSet<String> = list_save_files()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|save| save.name)
+        .collect();
+
+    if !taken.contains(name) {
+        return name.to_owned();
+    }
+
+    (2..)
+        .map(|n| format!("{name} {n}"))
+        .find(|name| !taken.contains(name))
+        .unwrap()
+}
+
+/// The most recently saved save called `name`.
+pub fn find_save(name: &str) -> Result<SaveFile> {
+    list_save_files()?
+        .into_iter()
+        .find(|save| save.name == name)
+        .ok_or_else(|| {
+            Error::new(
+                ErrorKind::NotFound,
+                format!("there is no save called {name}"),
+            )
+        })
+}
+
+/// Imports a `.sav` file from another emulator or a cartridge as a new save, named after the
+/// file.
+pub fn import_sav(path: &Path) -> Result<SaveFile> {
+    let state = SaveState::import_sav(path)?;
+
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .filter(|stem| !stem.is_empty())
+        .unwrap_or("Imported");
+
+    let path = create_save(&free_save_name(stem))?;
+    state.write_to_file(&path)?;
+
+    SaveFile::open(&path)
+}
+
+/// Exports the save called `name` as a `.sav` file that other emulators can load.
+pub fn export_sav(name: &str, to: &Path, footer: RtcFooter) -> Result<()> {
+    SaveState::from_file(&find_save(name)?.path)?.export_sav(to, footer)
+}
+
+/// Copies the save at `path` to its backups before it's overwritten, named after the time of the
+/// backup. Saves that the game couldn't load are skipped, so that they don't push out the last
+/// good backups.
+fn backup_save(path: &Path) -> Result<()> {
+    let Some(id) = path.file_stem().and_then(|stem| stem.to_str()) else {
+        return Ok(());
+    };
+
+    let state = match SaveState::from_file(&path.to_path_buf()) {
+        Ok(state) => state,
+        Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+
+    if !state.is_valid() {
+        log::warn!("Not backing up {id}, it's corrupted");
+        return Ok(());
+    }
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+
+    let dir = get_backups_dir(id);
+    fs::create_dir_all(&dir)?;
+    state.write_to_file(&dir.join(format!("{timestamp}.sav")))?;
+
+    for old in list_backups(id)?.into_iter().skip(MAX_BACKUPS) {
+        fs::remove_file(old)?;
+    }
+
+    Ok(())
+}
+
+/// The backups of the save with `id`, newest first.
+fn list_backups(id: &str) -> Result<Vec<PathBuf>> {
+    let dir = match fs::read_dir(get_backups_dir(id)) {
+        Ok(dir) => dir,
+        Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut backups = Vec::new();
+
+    for entry in dir {
+        let path = entry?.path();
+
+        if path.extension() != Some("sav".as_ref()) {
+            continue;
+        }
+
+        let timestamp = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.parse::<u128>().ok());
+
+        if let Some(timestamp) = timestamp {
+            backups.push((timestamp, path));
+        }
+    }
+
+    backups.sort_by_key(|&(timestamp, _)| std::cmp::Reverse(timestamp));
+
+    Ok(backups.into_iter().map(|(_, path)| path).collect())
+}
 
+/// The newest backup of `save` that the game can load.
+pub fn newest_valid_backup(save: &SaveFile) -> Result<Option<(PathBuf, SaveState)>> {
+    for path in list_backups(&save.id)? {
+        match SaveState::from_file(&path) {
+            Ok(state) if state.is_valid() => return Ok(Some((path, state))),
+            Ok(_) => log::warn!("Skipping corrupted backup {}", path.display()),
+            Err(e) => log::warn!("Skipping backup {}: {e}", path.display()),
+        }
+    }
+
+    Ok(None)
+}
+
+/// Replaces `save` with `backup`. The save that's replaced is kept next to it with a `.corrupt`
+/// extension, in case there's anything left to rescue.
+pub fn restore_backup(save: &SaveFile, backup: &SaveState) -> Result<()> {
+    if save.path.exists() {
+        fs::copy(&save.path, save.path.with_extension("corrupt"))?;
+    }
+
+    backup.write_to_file(&save.path)
+}
+
+/// Deletes `save`, along with its backups.
+pub fn delete_save(save: &SaveFile) -> Result<()> {
+    fs::remove_file(&save.path)?;
+
+    for result in [
+        fs::remove_file(get_meta_path(&save.id)),
+        fs::remove_dir_all(get_backups_dir(&save.id)),
+    ] {
+        match result {
+            Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Changes the name that `save` is shown with.
+pub fn rename_save(save: &SaveFile, to: &str) -> Result<()> {
+    let mut meta = read_meta(&save.id)?;
+    meta.name = to.to_owned();
+    write_meta(&save.id, &meta)
+}
+
+/// Copies `save` to a new save, with a free name based on its name.
+pub fn duplicate_save(save: &SaveFile) -> Result<SaveFile> {
+    let path = create_save(&free_save_name(&save.name))?;
+    fs::copy(&save.path, &path)?;
+
+    SaveFile::open(&path)
+}
+
+/// Moves a save from before saves had IDs, `<name>.sav`, to a new ID.
+fn migrate_save(name: &str, modified: SystemTime) -> Result<(String, SaveMeta)> {
+    let id = new_id();
+
+    let modified = modified
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    let meta = SaveMeta {
+        name: name.to_owned(),
+        created: modified,
+        modified,
+        data_set: DataSet::current(),
+    };
+
+    write_meta(&id, &meta)?;
+
+    if let Err(error) = fs::rename(get_save_path(name), get_save_path(&id)) {
+        let _ = fs::remove_file(get_meta_path(&id));
+        return Err(error);
+    }
+
+    if get_backups_dir(name).is_dir() {
+        fs::rename(get_backups_dir(name), get_backups_dir(&id))?;
+    }
+
+    log::info!("Moved save {name} to {id}");
+
+    Ok((id, meta))
+}
+
+/// Lists the saves, newest first. Saves from before saves had IDs are moved to one on the way.
+pub fn list_save_files() -> Result<Vec<SaveFile>> {
     let dir = match fs::read_dir(get_save_dir()) {
         Ok(dir) => dir,
         Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
         Err(e) => return Err(e),
     };
 
+    // Migrating a save renames it and writes its metadata, which a `read_dir` in progress may or
+    // may not return again
+    let entries = dir.collect::<Result<Vec<_>>>()?;
+
     let mut files = Vec::new();
 
-    for entry in dir {
-        let entry = entry?;
+    for entry in entries {
         let path = entry.path();
 
-        if path.extension() == Some(&ext) && path.is_file() {
-            if let Some(stem) = path.file_stem() {
-                if let Some(name) = stem.to_str() {
-                    let name = name.to_owned();
-                    files.push(SaveFile { path, name });
+        let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+
+        if path.extension() != Some("sav".as_ref()) || !path.is_file() {
+            continue;
+        }
+
+        let (id, meta) = match read_meta(stem) {
+            Ok(meta) => (stem.to_owned(), meta),
+            Err(ref e) if e.kind() == ErrorKind::NotFound => {
+                let modified = entry
+                    .metadata()
+                    .and_then(|meta| meta.modified())
+                    .unwrap_or(UNIX_EPOCH);
+
+                match migrate_save(stem, modified) {
+                    Ok(migrated) => migrated,
+                    Err(error) => {
+                        log::error!("Failed to move save {stem} to an ID: {error}");
+                        continue;
+                    }
                 }
             }
-        }
+            Err(error) => {
+                log::error!("Skipping save {stem}: {error}");
+                continue;
+            }
+        };
+
+        files.push(SaveFile::new(id, meta));
     }
 
     // Sort by last modified
-    files.sort_by_cached_key(|save| {
-        std::cmp::Reverse(
-            save.path
-                .metadata()
-                .and_then(|meta| meta.modified())
-                .ok()
-                .unwrap_or(std::time::SystemTime::UNIX_EPOCH),
-        )
-    });
+    files.sort_by_key(|save| std::cmp::Reverse(save.modified));
 
     Ok(files)
 }
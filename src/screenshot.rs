@@ -0,0 +1,52 @@
+use std::{
+    fs,
+    io::{self, BufWriter},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::saves;
+
+/// Writes an RGB image, with 3 bytes per pixel, as a PNG file.
+pub fn write_png(path: &Path, data: &[u8], width: usize, height: usize) -> io::Result<()> {
+    write(path, data, width, height, png::ColorType::Rgb)
+}
+
+/// Writes an RGBA image, with 4 bytes per pixel, as a PNG file.
+pub fn write_rgba_png(path: &Path, data: &[u8], width: usize, height: usize) -> io::Result<()> {
+    write(path, data, width, height, png::ColorType::Rgba)
+}
+
+fn write(
+    path: &Path,
+    data: &[u8],
+    width: usize,
+    height: usize,
+    color: png::ColorType,
+) -> io::Result<()> {
+    let file = BufWriter::new(fs::File::create(path)?);
+
+    let mut encoder = png::Encoder::new(file, width as u32, height as u32);
+    encoder.set_color(color);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer.write_image_data(data).map_err(io::Error::other)?;
+    writer.finish().map_err(io::Error::other)
+}
+
+/// Saves a screenshot in the screenshots directory, returning where it was written.
+pub fn save(data: &[u8], width: usize, height: usize) -> io::Result<PathBuf> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+
+    let dir = saves::get_screenshots_dir();
+    fs::create_dir_all(&dir)?;
+
+    let path = dir.join(format!("screenshot-{timestamp}.png"));
+    write_png(&path, data, width, height)?;
+
+    Ok(path)
+}
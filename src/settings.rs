@@ -0,0 +1,60 @@
+use std::{fs, io};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    color::ColorSettings,
+    filter::FilterSettings,
+    mixer::{TransitionSettings, VolumeSettings},
+    saves,
+    texture_pack::TexturePackSettings,
+};
+
+/// Player preferences that aren't part of a save, stored in `settings.toml` next to the saves.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub volume: VolumeSettings,
+    pub transition: TransitionSettings,
+    pub video: FilterSettings,
+    pub color: ColorSettings,
+    pub enhancements: Enhancements,
+    pub texture_pack: TexturePackSettings,
+}
+
+/// Changes to how the game behaves on real hardware, all off by default.
+#[derive(Debug, Default, Copy, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Enhancements {
+    /// Draw every sprite on a line, instead of only the first ten like the hardware does.
+    pub unlimited_sprites: bool,
+}
+
+impl Settings {
+    /// Reads the settings, using the defaults if they are missing or invalid.
+    pub fn load() -> Settings {
+        let path = saves::get_settings_path();
+
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Settings::default(),
+            Err(error) => {
+                log::error!("Failed to read {}: {error}", path.display());
+                return Settings::default();
+            }
+        };
+
+        toml::from_str(&text).unwrap_or_else(|error| {
+            log::error!("Ignoring invalid {}: {error}", path.display());
+            Settings::default()
+        })
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = saves::get_settings_path();
+        let text = toml::to_string(self).map_err(io::Error::other)?;
+
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(path, text)
+    }
+}
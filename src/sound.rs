@@ -677,6 +677,7 @@ pub struct Sound {
     reg_ff25: u8,
     need_sync: bool,
     dmg_mode: bool,
+    channel_mask: u8,
     player: Box<dyn AudioPlayer>,
 }
 
@@ -715,6 +716,7 @@ impl Sound {
             reg_ff25: 0x00,
             need_sync: false,
             dmg_mode,
+            channel_mask: 0x0f,
             player,
         }
     }
@@ -802,6 +804,12 @@ impl Sound {
         }
     }
 
+    /// Selects which of the four channels are heard, bit 0 being channel 1. The channels keep
+    /// running while muted.
+    pub fn set_channel_mask(&mut self, mask: u8) {
+        self.channel_mask = mask & 0x0f;
+    }
+
     pub fn sync(&mut self) {
         self.need_sync = true;
     }
@@ -875,6 +883,9 @@ impl Sound {
         let left_vol = (self.volume_left as f32 / 7.0) * (1.0 / 15.0) * 0.25;
         let right_vol = (self.volume_right as f32 / 7.0) * (1.0 / 15.0) * 0.25;
 
+        // Muted channels are removed from both the left and the right output
+        let routing = self.reg_ff25 & (self.channel_mask * 0x11);
+
         while outputted < sample_count {
             let buf_left = &mut [0f32; OUTPUT_SAMPLE_COUNT + 10];
             let buf_right = &mut [0f32; OUTPUT_SAMPLE_COUNT + 10];
@@ -882,20 +893,20 @@ impl Sound {
 
             let count1 = self.channel1.blip.read_samples(buf, false);
             for (i, v) in buf[..count1].iter().enumerate() {
-                if self.reg_ff25 & 0x01 == 0x01 {
+                if routing & 0x01 == 0x01 {
                     buf_left[i] += *v as f32 * left_vol;
                 }
-                if self.reg_ff25 & 0x10 == 0x10 {
+                if routing & 0x10 == 0x10 {
                     buf_right[i] += *v as f32 * right_vol;
                 }
             }
 
             let count2 = self.channel2.blip.read_samples(buf, false);
             for (i, v) in buf[..count2].iter().enumerate() {
-                if self.reg_ff25 & 0x02 == 0x02 {
+                if routing & 0x02 == 0x02 {
                     buf_left[i] += *v as f32 * left_vol;
                 }
-                if self.reg_ff25 & 0x20 == 0x20 {
+                if routing & 0x20 == 0x20 {
                     buf_right[i] += *v as f32 * right_vol;
                 }
             }
@@ -904,20 +915,20 @@ impl Sound {
             // increase in amplitude in order to avoid a loss of precision.
             let count3 = self.channel3.blip.read_samples(buf, false);
             for (i, v) in buf[..count3].iter().enumerate() {
-                if self.reg_ff25 & 0x04 == 0x04 {
+                if routing & 0x04 == 0x04 {
                     buf_left[i] += ((*v as f32) / 4.0) * left_vol;
                 }
-                if self.reg_ff25 & 0x40 == 0x40 {
+                if routing & 0x40 == 0x40 {
                     buf_right[i] += ((*v as f32) / 4.0) * right_vol;
                 }
             }
 
             let count4 = self.channel4.blip.read_samples(buf, false);
             for (i, v) in buf[..count4].iter().enumerate() {
-                if self.reg_ff25 & 0x08 == 0x08 {
+                if routing & 0x08 == 0x08 {
                     buf_left[i] += *v as f32 * left_vol;
                 }
-                if self.reg_ff25 & 0x80 == 0x80 {
+                if routing & 0x80 == 0x80 {
                     buf_right[i] += *v as f32 * right_vol;
                 }
             }
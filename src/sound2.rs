@@ -1,5 +1,12 @@
+use std::sync::Arc;
+
 use rodio::{stream::OutputStream, OutputStreamBuilder, Sink};
 
+use crate::{
+    apu_player::ApuPlayer,
+    mixer::{Bus, Mixer, MusicTransition, TransitionSettings},
+};
+
 pub trait Sfx<TSource> {
     fn open(self) -> TSource;
 }
@@ -8,24 +15,194 @@ pub trait Music<TSource>: Sfx<TSource> {
     fn id(&self) -> u32;
 }
 
+/// Where the game audio comes from.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AudioMode {
+    /// Music from the soundtrack, with sound effects and cries synthesized from the ROM.
+    Soundtrack,
+    /// The ROM's own sound engine, played through the emulated APU like on the original hardware.
+    Hardware,
+}
+
+impl AudioMode {
+    /// Uses the soundtrack when it's installed, and falls back to the original hardware otherwise.
+    pub fn detect() -> AudioMode {
+        if crate::game::audio::soundtrack::soundtrack_installed() {
+            AudioMode::Soundtrack
+        } else {
+            AudioMode::Hardware
+        }
+    }
+}
+
+/// Progress of a jingle, or of another sound that the synthesizer can't play, which is played by
+/// the ROM's sound engine through the emulated APU.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Jingle {
+    None,
+    /// Requested, but the sound engine hasn't started any of its channels yet. Given up on once
+    /// `frames_left` runs out, so that the music doesn't stay paused.
+    Starting {
+        frames_left: u32,
+    },
+    Playing,
+}
+
+/// How long the sound engine gets to start a jingle's channels.
+const JINGLE_START_FRAMES: u32 = 30;
+
+/// A music track whose volume can be faded over a number of frames.
+struct MusicSink {
+    id: u32,
+    sink: Sink,
+    volume: f32,
+    target: f32,
+    /// Volume change per frame.
+    step: f32,
+    /// Frames left before the track starts playing.
+    delay: u32,
+}
+
+impl MusicSink {
+    fn new(id: u32, sink: Sink) -> MusicSink {
+        MusicSink {
+            id,
+            sink,
+            volume: 1.0,
+            target: 1.0,
+            step: 0.0,
+            delay: 0,
+        }
+    }
+
+    fn set_volume(&mut self, volume: f32) {
+        self.volume = volume;
+        self.target = volume;
+        self.sink.set_volume(volume);
+    }
+
+    fn fade_to(&mut self, target: f32, frames: u32) {
+        if frames == 0 {
+            self.set_volume(target);
+        } else {
+            self.target = target;
+            self.step = (target - self.volume).abs() / frames as f32;
+        }
+    }
+
+    fn is_silent(&self) -> bool {
+        self.volume <= 0.0 && self.target <= 0.0
+    }
+
+    fn tick(&mut self, paused: bool) {
+        if self.delay > 0 {
+            self.delay -= 1;
+
+            if self.delay == 0 && !paused {
+                self.sink.play();
+            }
+
+            return;
+        }
+
+        if self.volume < self.target {
+            self.volume = f32::min(self.volume + self.step, self.target);
+            self.sink.set_volume(self.volume);
+        } else if self.volume > self.target {
+            self.volume = f32::max(self.volume - self.step, self.target);
+            self.sink.set_volume(self.volume);
+        }
+    }
+}
+
 pub struct Sound2 {
-    music: Option<(u32, Sink)>,
+    mode: AudioMode,
+    music: Option<MusicSink>,
+    /// Previous tracks that are fading out.
+    fading_music: Vec<MusicSink>,
+    transition: TransitionSettings,
+    /// Whether the ROM's sound engine is fading out the music, and will fade in the next track.
+    rom_fading_out: bool,
     sfx: Option<Sink>,
+    cry: Option<Sink>,
+    jingle: Jingle,
+    /// Whether the music is paused for the jingle, which is only done for actual jingles.
+    jingle_pauses_music: bool,
+    mixer: Arc<Mixer>,
+    apu: Option<ApuPlayer>,
     stream: OutputStream,
 }
 
 impl Sound2 {
     pub fn new() -> Self {
         Sound2 {
+            mode: AudioMode::Soundtrack,
             music: None,
+            fading_music: Vec::new(),
+            transition: TransitionSettings::default(),
+            rom_fading_out: false,
             sfx: None,
+            cry: None,
+            jingle: Jingle::None,
+            jingle_pauses_music: false,
+            mixer: Mixer::new(Default::default()),
+            apu: None,
             stream: OutputStreamBuilder::open_default_stream().unwrap(),
         }
     }
 
+    pub fn mode(&self) -> AudioMode {
+        self.mode
+    }
+
+    /// Uses the volume settings of `mixer`, for sounds played from now on.
+    pub fn set_mixer(&mut self, mixer: Arc<Mixer>) {
+        self.mixer = mixer;
+    }
+
+    pub fn set_transition(&mut self, transition: TransitionSettings) {
+        self.transition = transition;
+    }
+
+    /// Switches audio mode, returning a player for the emulated APU. With the soundtrack, the APU
+    /// is only heard while a jingle plays.
+    pub fn set_mode(&mut self, mode: AudioMode) -> ApuPlayer {
+        self.mode = mode;
+
+        if mode == AudioMode::Hardware {
+            self.stop_music();
+            self.stop_sfx();
+            self.stop_cry();
+        }
+
+        self.mixer.set_apu_plays_music(mode == AudioMode::Hardware);
+
+        // The APU's source stays in the output stream, and is shared by the players
+        self.apu
+            .get_or_insert_with(|| {
+                let (player, source) = ApuPlayer::new(self.stream.config().sample_rate());
+                self.stream.mixer().add(self.mixer.source(Bus::Apu, source));
+                player
+            })
+            .clone()
+    }
+
+    fn play<S>(&self, bus: Bus, source: S) -> Sink
+    where
+        S: rodio::Source + Send + 'static,
+    {
+        let sink = Sink::connect_new(self.stream.mixer());
+        sink.append(self.mixer.source(bus, source));
+        sink
+    }
+
     pub fn stop_music(&mut self) {
-        if let Some((_, sink)) = self.music.take() {
-            sink.stop();
+        if let Some(music) = self.music.take() {
+            music.sink.stop();
+        }
+
+        for music in self.fading_music.drain(..) {
+            music.sink.stop();
         }
     }
 
@@ -35,9 +212,15 @@ impl Sound2 {
         }
     }
 
+    pub fn stop_cry(&mut self) {
+        if let Some(sink) = self.cry.take() {
+            sink.stop();
+        }
+    }
+
     fn is_playing_music(&self, id: u32) -> bool {
-        if let Some((playing, _)) = self.music.as_ref() {
-            *playing == id
+        if let Some(music) = self.music.as_ref() {
+            music.id == id
         } else {
             false
         }
@@ -49,17 +232,100 @@ impl Sound2 {
         TSource: rodio::Source + Send + 'static,
         f32: cpal::FromSample<TSource::Item>,
     {
+        if self.mode == AudioMode::Hardware {
+            return; // The ROM plays the music itself
+        }
+
         let id = music.id();
 
         if self.is_playing_music(id) {
             return; // Allready playing this music
         }
 
-        self.stop_music();
+        let frames = self.transition.frames();
+        let previous = self.music.take();
 
-        let sink = Sink::connect_new(self.stream.mixer());
-        sink.append(music.open());
-        self.music = Some((id, sink));
+        let mut next = MusicSink::new(id, self.play(Bus::Music, music.open()));
+
+        match previous {
+            // The sound engine has faded out the previous track, and fades in the next one itself
+            Some(previous) if self.rom_fading_out => {
+                previous.sink.stop();
+                next.set_volume(0.0);
+            }
+            Some(previous) if frames == 0 || self.transition.music == MusicTransition::Cut => {
+                previous.sink.stop();
+            }
+            Some(mut previous) => {
+                previous.fade_to(0.0, frames);
+                self.fading_music.push(previous);
+
+                match self.transition.music {
+                    MusicTransition::FadeOut => {
+                        next.sink.pause();
+                        next.delay = frames;
+                    }
+                    _ => {
+                        next.set_volume(0.0);
+                        next.fade_to(1.0, frames);
+                    }
+                }
+            }
+            None => {}
+        }
+
+        if self.jingle != Jingle::None && self.jingle_pauses_music {
+            next.sink.pause(); // Resumed when the jingle is done
+        }
+
+        self.music = Some(next);
+    }
+
+    /// Follows a fade requested through `wMusicFade`, whose low bits are the number of frames per
+    /// volume level, and whose top bit is set when fading in.
+    pub fn rom_fade(&mut self, fade: u8) {
+        if fade == 0 {
+            self.rom_fading_out = false;
+            return;
+        }
+
+        // The sound engine steps through 8 volume levels
+        let frames = (fade & 0x3f) as u32 * 8;
+        let fade_in = fade & 0x80 != 0;
+
+        self.rom_fading_out = !fade_in;
+
+        if let Some(music) = self.music.as_mut() {
+            music.fade_to(if fade_in { 1.0 } else { 0.0 }, frames);
+        }
+    }
+
+    /// Advances fades by one frame.
+    pub fn update(&mut self) {
+        if let Jingle::Starting { frames_left } = &mut self.jingle {
+            if *frames_left == 0 {
+                log::warn!("The sound engine didn't start the jingle");
+                self.end_jingle();
+            } else {
+                *frames_left -= 1;
+            }
+        }
+
+        let paused = self.jingle != Jingle::None && self.jingle_pauses_music;
+
+        if let Some(music) = self.music.as_mut() {
+            music.tick(paused);
+        }
+
+        self.fading_music.retain_mut(|music| {
+            music.tick(false);
+
+            if music.is_silent() {
+                music.sink.stop();
+            }
+
+            !music.is_silent()
+        });
     }
 
     pub fn play_sfx<T, TSource>(&mut self, sound: T)
@@ -68,12 +334,83 @@ impl Sound2 {
         TSource: rodio::Source + Send + 'static,
         f32: cpal::FromSample<TSource::Item>,
     {
-        if let Some(sink) = self.sfx.take() {
-            sink.stop();
+        if self.mode == AudioMode::Hardware {
+            return; // The ROM plays the sound itself
         }
 
-        let sink = Sink::connect_new(self.stream.mixer());
-        sink.append(sound.open());
-        self.sfx = Some(sink);
+        self.stop_sfx();
+
+        // The sound engine cuts the jingle short to play the new sound
+        self.end_jingle();
+
+        self.sfx = Some(self.play(Bus::Sfx, sound.open()));
+    }
+
+    /// Plays a cry, which doesn't interrupt sound effects and ducks the music while playing.
+    pub fn play_cry<T, TSource>(&mut self, cry: T)
+    where
+        T: Sfx<TSource>,
+        TSource: rodio::Source + Send + 'static,
+        f32: cpal::FromSample<TSource::Item>,
+    {
+        if self.mode == AudioMode::Hardware {
+            return; // The ROM plays the cry itself
+        }
+
+        self.stop_cry();
+        self.end_jingle();
+
+        self.cry = Some(self.play(Bus::Cries, cry.open()));
+    }
+
+    pub fn jingle(&self) -> Jingle {
+        self.jingle
+    }
+
+    /// Lets the ROM's sound engine play a sound through the emulated APU, pausing the music if
+    /// it's a jingle.
+    pub fn start_jingle(&mut self, pause_music: bool) {
+        if self.mode == AudioMode::Hardware {
+            return; // The ROM pauses the music itself
+        }
+
+        self.stop_sfx();
+        self.end_jingle();
+
+        if pause_music {
+            if let Some(music) = self.music.as_ref() {
+                music.sink.pause();
+            }
+        }
+
+        self.jingle = Jingle::Starting {
+            frames_left: JINGLE_START_FRAMES,
+        };
+        self.jingle_pauses_music = pause_music;
+    }
+
+    /// Called once the sound engine has started the jingle's channels.
+    pub fn jingle_started(&mut self) {
+        if matches!(self.jingle, Jingle::Starting { .. }) {
+            self.jingle = Jingle::Playing;
+        }
+    }
+
+    /// Resumes the music after a jingle.
+    pub fn end_jingle(&mut self) {
+        if self.jingle == Jingle::None {
+            return;
+        }
+
+        let paused_music = self
+            .music
+            .as_ref()
+            .filter(|music| self.jingle_pauses_music && music.delay == 0);
+
+        if let Some(music) = paused_music {
+            music.sink.play();
+        }
+
+        self.jingle = Jingle::None;
     }
 }
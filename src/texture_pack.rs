@@ -0,0 +1,281 @@
+//! Replacement graphics for the tiles drawn by the GPU.
+//!
+//! A texture pack is a folder with a `pack.toml` and PNG files named after the hash of the tile
+//! they replace, e.g. `0123456789abcdef.png`. The hash covers both the pixels and the palette
+//! of the tile, so the same tile in different colours can be replaced separately.
+//!
+//! ```toml
+//! # How much larger the replacements are than the original 8×8 tiles
+//! scale = 4
+//! ```
+//!
+//! The replacements are drawn into an upscaled copy of the screen, which is shown instead of the
+//! normal one. In dump mode, every tile that is drawn without a replacement is written to the
+//! `dump` folder of the pack at its original size, as a starting point for new replacements.
+
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fs,
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Sender},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::screenshot::write_rgba_png;
+
+const MANIFEST_NAME: &str = "pack.toml";
+const MAX_SCALE: usize = 16;
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TexturePackSettings {
+    /// The folder of the pack, no pack is used if this isn't set.
+    pub path: Option<PathBuf>,
+    /// Write tiles without a replacement to the `dump` folder of the pack.
+    pub dump: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    #[serde(default = "default_scale")]
+    scale: usize,
+}
+
+fn default_scale() -> usize {
+    4
+}
+
+/// The 8×8 pixels of a tile, with its palette, as drawn by the GPU.
+pub(crate) struct Tile {
+    /// Two bytes per row, like in VRAM.
+    pub data: [u8; 16],
+    /// RGB555 values of the four colours.
+    pub palette: [u16; 4],
+    /// Colour 0 is transparent.
+    pub sprite: bool,
+}
+
+impl Tile {
+    /// FNV-1a over the pixels and the palette.
+    pub fn hash(&self) -> u64 {
+        let palette = self.palette.iter().flat_map(|c| c.to_le_bytes());
+
+        self.data
+            .iter()
+            .copied()
+            .chain(palette)
+            .chain(std::iter::once(self.sprite as u8))
+            .fold(0xcbf29ce484222325, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+            })
+    }
+
+    /// The colour number of a pixel.
+    pub fn pixel(&self, x: usize, y: usize) -> usize {
+        let bit = 7 - x;
+        ((self.data[y * 2] >> bit) & 1) as usize
+            | (((self.data[y * 2 + 1] >> bit) & 1) << 1) as usize
+    }
+}
+
+/// A loaded texture pack.
+pub struct TexturePack {
+    scale: usize,
+    // RGBA, (8 * scale)² pixels each
+    textures: HashMap<u64, Vec<u8>>,
+    dumper: Option<Dumper>,
+    seen: HashSet<u64>,
+}
+
+/// Writes dumped tiles on a background thread, so that drawing doesn't wait for PNG encoding.
+struct Dumper {
+    dir: PathBuf,
+    sender: Sender<(PathBuf, Vec<u8>)>,
+}
+
+impl Dumper {
+    fn new(dir: PathBuf) -> std::io::Result<Dumper> {
+        fs::create_dir_all(&dir)?;
+
+        let (sender, receiver) = mpsc::channel::<(PathBuf, Vec<u8>)>();
+
+        std::thread::spawn(move || {
+            for (path, tile) in receiver {
+                if let Err(error) = write_rgba_png(&path, &tile, 8, 8) {
+                    log::error!("Failed to dump tile to {}: {error}", path.display());
+                }
+            }
+        });
+
+        Ok(Dumper { dir, sender })
+    }
+}
+
+impl TexturePack {
+    pub fn load(settings: &TexturePackSettings) -> Result<Option<TexturePack>, Box<dyn Error>> {
+        let Some(dir) = &settings.path else {
+            return Ok(None);
+        };
+
+        let manifest: Manifest = match fs::read_to_string(dir.join(MANIFEST_NAME)) {
+            Ok(text) => toml::from_str(&text)?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound && settings.dump => {
+                // Dumping is how new packs are started
+                Manifest {
+                    scale: default_scale(),
+                }
+            }
+            Err(error) => return Err(format!("failed to read {MANIFEST_NAME}: {error}").into()),
+        };
+
+        if manifest.scale == 0 || manifest.scale > MAX_SCALE {
+            return Err(format!("scale must be between 1 and {MAX_SCALE}").into());
+        }
+
+        let dumper = match settings.dump {
+            true => Some(Dumper::new(dir.join("dump"))?),
+            false => None,
+        };
+
+        let mut pack = TexturePack {
+            scale: manifest.scale,
+            textures: HashMap::new(),
+            dumper,
+            seen: HashSet::new(),
+        };
+
+        pack.load_textures(dir)?;
+
+        log::info!(
+            "Loaded {} textures from {}",
+            pack.textures.len(),
+            dir.display()
+        );
+
+        Ok(Some(pack))
+    }
+
+    fn load_textures(&mut self, dir: &Path) -> Result<(), Box<dyn Error>> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+
+            if path.is_dir() {
+                if path.file_name().is_some_and(|name| name != "dump") {
+                    self.load_textures(&path)?;
+                }
+                continue;
+            }
+
+            if path.extension().is_none_or(|ext| ext != "png") {
+                continue;
+            }
+
+            let Some(hash) = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| u64::from_str_radix(stem, 16).ok())
+            else {
+                log::warn!("Skipping {}, not named after a hash", path.display());
+                continue;
+            };
+
+            match read_rgba_png(&path, 8 * self.scale) {
+                Ok(texture) => {
+                    self.textures.insert(hash, texture);
+                }
+                Err(error) => log::warn!("Skipping {}: {error}", path.display()),
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn scale(&self) -> usize {
+        self.scale
+    }
+
+    /// The replacement for the tile with `hash`, as RGBA.
+    pub(crate) fn texture(&self, hash: u64) -> Option<&[u8]> {
+        self.textures.get(&hash).map(Vec::as_slice)
+    }
+
+    /// Notes that a tile was drawn without a replacement, dumping it the first time in dump mode.
+    pub(crate) fn missing(&mut self, hash: u64, tile: impl FnOnce() -> Vec<u8>) {
+        let Some(dumper) = &self.dumper else {
+            return;
+        };
+
+        if !self.seen.insert(hash) {
+            return;
+        }
+
+        let path = dumper.dir.join(format!("{hash:016x}.png"));
+        let _ = dumper.sender.send((path, tile()));
+    }
+}
+
+/// Reads a PNG file as RGBA, which must be `size` pixels wide and high.
+fn read_rgba_png(path: &Path, size: usize) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut decoder = png::Decoder::new(BufReader::new(fs::File::open(path)?));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+
+    let mut reader = decoder.read_info()?;
+    let mut data = vec![0; reader.output_buffer_size().ok_or("image too large")?];
+    let info = reader.next_frame(&mut data)?;
+    data.truncate(info.buffer_size());
+
+    if (info.width as usize, info.height as usize) != (size, size) {
+        return Err(format!(
+            "expected {size}×{size} pixels, found {}×{}",
+            info.width, info.height
+        )
+        .into());
+    }
+
+    let rgba = match info.color_type {
+        png::ColorType::Rgba => data,
+        png::ColorType::Rgb => data
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => data
+            .chunks_exact(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        png::ColorType::Grayscale => data.iter().flat_map(|&v| [v, v, v, 255]).collect(),
+        png::ColorType::Indexed => return Err("unsupported colour type".into()),
+    };
+
+    Ok(rgba)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tile() {
+        let mut tile = Tile {
+            data: [0; 16],
+            palette: [0x7fff, 0x56b5, 0x294a, 0x0000],
+            sprite: false,
+        };
+
+        // Colour 3 in the top left corner, colour 1 in the bottom right corner
+        tile.data[0] = 0x80;
+        tile.data[1] = 0x80;
+        tile.data[14] = 0x01;
+
+        assert_eq!(tile.pixel(0, 0), 3);
+        assert_eq!(tile.pixel(7, 7), 1);
+        assert_eq!(tile.pixel(1, 0), 0);
+
+        let hash = tile.hash();
+        tile.palette[1] = 0x001f;
+        assert_ne!(tile.hash(), hash);
+    }
+}
@@ -0,0 +1,250 @@
+//! Lightweight spans on top of `log`, with an optional recorder that exports Chrome trace-event
+//! JSON (open it in `chrome://tracing` or https://ui.perfetto.dev).
+//!
+//! Spans and log messages use one of the subsystem targets below, so they can be filtered with
+//! e.g. `RUST_LOG=battle=debug,save=trace`.
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+pub const AUDIO: &str = "audio";
+pub const BATTLE: &str = "battle";
+pub const FRAME: &str = "frame";
+pub const GAME: &str = "game";
+pub const OVERWORLD: &str = "overworld";
+pub const SAVE: &str = "save";
+
+// Hooks run inside frames, but a hook like `main_menu` can also run for many frames, so they
+// don't nest properly and are shown as two separate tracks
+const HOOK_TRACK: u32 = 1;
+const FRAME_TRACK: u32 = 2;
+
+static RECORDING: AtomicBool = AtomicBool::new(false);
+static RECORDER: Mutex<Option<Recorder>> = Mutex::new(None);
+
+/// Writes finished spans to the trace file as they come, so that long sessions don't pile them up
+/// in memory.
+struct Recorder {
+    epoch: Instant,
+    out: BufWriter<File>,
+}
+
+/// A trace event with a start and a duration, in microseconds.
+#[derive(Serialize)]
+struct CompleteEvent {
+    name: &'static str,
+    cat: &'static str,
+    ph: &'static str,
+    ts: u64,
+    dur: u64,
+    pid: u32,
+    tid: u32,
+}
+
+/// A trace event that names a track.
+#[derive(Serialize)]
+struct ThreadNameEvent {
+    name: &'static str,
+    ph: &'static str,
+    pid: u32,
+    tid: u32,
+    args: ThreadName,
+}
+
+#[derive(Serialize)]
+struct ThreadName {
+    name: &'static str,
+}
+
+impl Recorder {
+    fn write_event(&mut self, event: &impl Serialize) -> io::Result<()> {
+        self.out.write_all(b",\n")?;
+        serde_json::to_writer(&mut self.out, event).map_err(io::Error::from)
+    }
+}
+
+/// A timed region of work, which ends when dropped.
+#[must_use]
+pub struct Span {
+    target: &'static str,
+    name: &'static str,
+    start: Instant,
+}
+
+impl Span {
+    pub fn new(target: &'static str, name: &'static str) -> Option<Span> {
+        if !RECORDING.load(Ordering::Relaxed)
+            && !log::log_enabled!(target: target, log::Level::Trace)
+        {
+            return None;
+        }
+
+        log::trace!(target: target, "> {name}");
+
+        Some(Span {
+            target,
+            name,
+            start: Instant::now(),
+        })
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let duration = self.start.elapsed();
+
+        log::trace!(target: self.target, "< {} ({duration:?})", self.name);
+
+        if RECORDING.load(Ordering::Relaxed) {
+            if let Some(recorder) = RECORDER.lock().unwrap().as_mut() {
+                let start = self.start.saturating_duration_since(recorder.epoch);
+
+                let event = CompleteEvent {
+                    name: self.name,
+                    cat: self.target,
+                    ph: "X",
+                    ts: micros(start),
+                    dur: micros(duration),
+                    pid: 1,
+                    tid: if self.target == FRAME {
+                        FRAME_TRACK
+                    } else {
+                        HOOK_TRACK
+                    },
+                };
+
+                if let Err(error) = recorder.write_event(&event) {
+                    log::error!("Failed to write to the trace, stopping it: {error}");
+                    RECORDING.store(false, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+}
+
+/// Starts a span for the Rust hook `F`, e.g. `rustic_crystal::game::engine::menus::save::save_box`.
+pub fn hook_span<F>() -> Option<Span> {
+    let path = std::any::type_name::<F>();
+
+    if !RECORDING.load(Ordering::Relaxed) && log::max_level() < log::Level::Trace {
+        return None;
+    }
+
+    let name = path.rsplit("::").next().unwrap_or(path);
+
+    Span::new(subsystem(path), name)
+}
+
+/// Maps the module path of a hook to its subsystem target.
+fn subsystem(path: &str) -> &'static str {
+    if path.contains("::battle::") {
+        BATTLE
+    } else if path.contains("::audio::") {
+        AUDIO
+    } else if path.contains("::overworld::") {
+        OVERWORLD
+    } else if path.contains("::menus::save::") {
+        SAVE
+    } else {
+        GAME
+    }
+}
+
+/// Starts writing finished spans to `path` as Chrome trace-event JSON, until `stop_recording`.
+pub fn start_recording(path: &Path) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+
+    // The track names go first, so that every span can be written with a comma before it
+    out.write_all(b"{\"traceEvents\":[\n")?;
+    serde_json::to_writer(&mut out, &thread_name_event(HOOK_TRACK, "Hooks"))?;
+
+    let mut recorder = Recorder {
+        epoch: Instant::now(),
+        out,
+    };
+    recorder.write_event(&thread_name_event(FRAME_TRACK, "Frames"))?;
+
+    *RECORDER.lock().unwrap() = Some(recorder);
+    RECORDING.store(true, Ordering::Relaxed);
+
+    Ok(())
+}
+
+/// Stops recording and finishes the trace file.
+pub fn stop_recording() -> io::Result<()> {
+    RECORDING.store(false, Ordering::Relaxed);
+
+    let Some(mut recorder) = RECORDER.lock().unwrap().take() else {
+        return Ok(());
+    };
+
+    recorder.out.write_all(b"\n]}\n")?;
+    recorder.out.flush()
+}
+
+fn thread_name_event(tid: u32, name: &'static str) -> ThreadNameEvent {
+    ThreadNameEvent {
+        name: "thread_name",
+        ph: "M",
+        pid: 1,
+        tid,
+        args: ThreadName { name },
+    }
+}
+
+fn micros(duration: Duration) -> u64 {
+    duration.as_micros().try_into().unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subsystem() {
+        assert_eq!(
+            subsystem("rustic_crystal::game::engine::battle::read_trainer_party::get_trainer_name"),
+            BATTLE
+        );
+        assert_eq!(
+            subsystem("rustic_crystal::game::engine::menus::save::save_box"),
+            SAVE
+        );
+        assert_eq!(
+            subsystem("rustic_crystal::game::engine::menus::main_menu::main_menu"),
+            GAME
+        );
+    }
+
+    #[test]
+    fn test_recording() {
+        let path =
+            std::env::temp_dir().join(format!("rustic-crystal-trace-{}.json", std::process::id()));
+
+        start_recording(&path).unwrap();
+        drop(Span::new(FRAME, "frame"));
+        drop(Span::new(GAME, "main_menu"));
+        stop_recording().unwrap();
+
+        let trace: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        let events = trace["traceEvents"].as_array().unwrap();
+
+        assert_eq!(events[1]["args"]["name"], "Frames");
+        assert_eq!(events[2]["tid"], FRAME_TRACK);
+        assert_eq!(events[3]["name"], "main_menu");
+        assert_eq!(events[3]["tid"], HOOK_TRACK);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,378 @@
+//! Debug views of the graphics memory, for tracking down glitches in the drawing code.
+//!
+//! A dump writes the following to a new folder in the screenshots directory:
+//!
+//! - `tiles.png`: all tiles of VRAM bank 0 (left) and bank 1 (right), in greyscale
+//! - `map_9800.png` and `map_9c00.png`: both tilemaps, drawn with their attributes
+//! - `maps.txt`: the tile number and attributes of each tilemap entry
+//! - `oam.png` and `oam.txt`: all 40 sprites, with their positions and flags
+//! - `palettes.png` and `palettes.txt`: the 8 BG palettes and the 8 sprite palettes
+
+use std::{
+    fmt::Write as _,
+    fs, io,
+    path::PathBuf,
+    sync::atomic::{AtomicBool, AtomicU8, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{color::ColorTable, saves, screenshot::write_png};
+
+const GREYS: [u8; 4] = [255, 170, 85, 0];
+
+/// A layer of the main output, that can be hidden to see what's drawn where.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Layer {
+    Background = 0x01,
+    Window = 0x02,
+    Sprites = 0x04,
+}
+
+/// Requests from the frontend, which are picked up by the GPU between frames.
+pub struct DebugView {
+    hidden_layers: AtomicU8,
+    dump_requested: AtomicBool,
+}
+
+impl DebugView {
+    pub fn new() -> DebugView {
+        DebugView {
+            hidden_layers: AtomicU8::new(0),
+            dump_requested: AtomicBool::new(false),
+        }
+    }
+
+    /// Shows or hides `layer`, returning whether it is now shown.
+    pub fn toggle_layer(&self, layer: Layer) -> bool {
+        let hidden = self.hidden_layers.fetch_xor(layer as u8, Ordering::Relaxed);
+        hidden & layer as u8 != 0
+    }
+
+    pub fn request_dump(&self) {
+        self.dump_requested.store(true, Ordering::Relaxed);
+    }
+
+    pub(crate) fn take_dump_request(&self) -> bool {
+        self.dump_requested.swap(false, Ordering::Relaxed)
+    }
+
+    pub(crate) fn shows(&self, layer: Layer) -> bool {
+        self.hidden_layers.load(Ordering::Relaxed) & layer as u8 == 0
+    }
+}
+
+type Palettes = [[[u8; 3]; 4]; 8];
+
+/// A copy of the state of the GPU that's needed to draw the debug views, so that they can be
+/// drawn and written away from the emulator thread.
+pub(crate) struct VramView {
+    /// Both banks, bank 0 first.
+    vram: Vec<u8>,
+    oam: Vec<u8>,
+    /// The palettes as stored by the GPU, with 5 bits per channel.
+    bg_palettes: Palettes,
+    sprite_palettes: Palettes,
+    /// The same palettes, converted to the colours shown on screen.
+    bg_colors: Palettes,
+    sprite_colors: Palettes,
+    /// 0x8000 or 0x8800, as selected by LCDC.
+    tilebase: u16,
+    sprite_size: u32,
+}
+
+/// An RGB image that's drawn into.
+struct Canvas {
+    data: Vec<u8>,
+    width: usize,
+    height: usize,
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize) -> Canvas {
+        Canvas {
+            data: vec![0; width * height * 3],
+            width,
+            height,
+        }
+    }
+
+    fn set(&mut self, x: usize, y: usize, rgb: [u8; 3]) {
+        let idx = (y * self.width + x) * 3;
+        self.data[idx..idx + 3].copy_from_slice(&rgb);
+    }
+
+    fn fill(&mut self, x: usize, y: usize, w: usize, h: usize, rgb: [u8; 3]) {
+        for py in y..y + h {
+            for px in x..x + w {
+                self.set(px, py, rgb);
+            }
+        }
+    }
+
+    fn write(&self, path: PathBuf) -> io::Result<()> {
+        write_png(&path, &self.data, self.width, self.height)
+    }
+}
+
+impl VramView {
+    pub fn new(
+        vram: &[u8],
+        oam: &[u8],
+        bg_palettes: &Palettes,
+        sprite_palettes: &Palettes,
+        tilebase: u16,
+        sprite_size: u32,
+        colors: &ColorTable,
+    ) -> VramView {
+        let convert = |palettes: &Palettes| {
+            palettes.map(|palette| palette.map(|[r, g, b]| colors.get(r, g, b)))
+        };
+
+        VramView {
+            vram: vram.to_vec(),
+            oam: oam.to_vec(),
+            bg_palettes: *bg_palettes,
+            sprite_palettes: *sprite_palettes,
+            bg_colors: convert(bg_palettes),
+            sprite_colors: convert(sprite_palettes),
+            tilebase,
+            sprite_size,
+        }
+    }
+
+    /// The colour number of a pixel in a tile, `tile` counting from 0x8000 in `bank`.
+    fn tile_pixel(&self, bank: usize, tile: usize, x: usize, y: usize) -> usize {
+        let addr = bank * 0x2000 + tile * 16 + y * 2;
+        let bit = 7 - x;
+
+        ((self.vram[addr] >> bit) & 1) as usize | (((self.vram[addr + 1] >> bit) & 1) << 1) as usize
+    }
+
+    fn tiles(&self) -> Canvas {
+        // 384 tiles per bank, 16 per row
+        let mut canvas = Canvas::new(2 * 16 * 8, 24 * 8);
+
+        for bank in 0..2 {
+            for tile in 0..384 {
+                let ox = bank * 128 + (tile % 16) * 8;
+                let oy = (tile / 16) * 8;
+
+                for y in 0..8 {
+                    for x in 0..8 {
+                        let grey = GREYS[self.tile_pixel(bank, tile, x, y)];
+                        canvas.set(ox + x, oy + y, [grey; 3]);
+                    }
+                }
+            }
+        }
+
+        canvas
+    }
+
+    /// The tile number and attributes of an entry in the tilemap at `base`.
+    fn map_entry(&self, base: u16, index: usize) -> (u8, u8) {
+        let addr = (base as usize & 0x1fff) + index;
+        (self.vram[addr], self.vram[0x2000 + addr])
+    }
+
+    fn map(&self, base: u16) -> Canvas {
+        let mut canvas = Canvas::new(256, 256);
+
+        for index in 0..32 * 32 {
+            let (tilenr, attrs) = self.map_entry(base, index);
+
+            let tile = if self.tilebase == 0x8000 {
+                tilenr as usize
+            } else {
+                (tilenr as i8 as i16 + 256) as usize
+            };
+
+            let bank = (attrs as usize >> 3) & 1;
+            let palette = &self.bg_colors[attrs as usize & 0x07];
+            let xflip = attrs & (1 << 5) != 0;
+            let yflip = attrs & (1 << 6) != 0;
+
+            for y in 0..8 {
+                for x in 0..8 {
+                    let tx = if xflip { 7 - x } else { x };
+                    let ty = if yflip { 7 - y } else { y };
+                    let colnr = self.tile_pixel(bank, tile, tx, ty);
+
+                    canvas.set((index % 32) * 8 + x, (index / 32) * 8 + y, palette[colnr]);
+                }
+            }
+        }
+
+        canvas
+    }
+
+    fn maps_text(&self) -> String {
+        let mut out = String::new();
+
+        let _ = writeln!(out, "Entries are tile number/attributes, in hex");
+        let _ = writeln!(
+            out,
+            "Attributes: 0-2 palette, 3 bank, 5 x flip, 6 y flip, 7 priority"
+        );
+
+        for base in [0x9800, 0x9c00] {
+            let _ = writeln!(out);
+            let _ = writeln!(out, "Tilemap {base:04X}:");
+
+            for row in 0..32 {
+                let entries: Vec<String> = (0..32)
+                    .map(|col| {
+                        let (tilenr, attrs) = self.map_entry(base, row * 32 + col);
+                        format!("{tilenr:02X}/{attrs:02X}")
+                    })
+                    .collect();
+
+                let _ = writeln!(out, "{}", entries.join(" "));
+            }
+        }
+
+        out
+    }
+
+    fn sprite(&self, index: usize) -> (u8, u8, u8, u8) {
+        let oam = &self.oam[index * 4..index * 4 + 4];
+        (oam[0], oam[1], oam[2], oam[3])
+    }
+
+    fn oam(&self) -> Canvas {
+        // 8 sprites per row, each in a 16×24 cell
+        let mut canvas = Canvas::new(8 * 16, 5 * 24);
+        canvas.fill(0, 0, canvas.width, canvas.height, [64, 64, 64]);
+
+        let height = self.sprite_size as usize;
+
+        for index in 0..40 {
+            let (_, _, tilenr, flags) = self.sprite(index);
+
+            let tile = if height == 16 { tilenr & 0xfe } else { tilenr } as usize;
+            let bank = (flags as usize >> 3) & 1;
+            let palette = &self.sprite_colors[flags as usize & 0x07];
+            let xflip = flags & (1 << 5) != 0;
+            let yflip = flags & (1 << 6) != 0;
+
+            let ox = (index % 8) * 16 + 4;
+            let oy = (index / 8) * 24 + 4;
+
+            for y in 0..height {
+                for x in 0..8 {
+                    let tx = if xflip { 7 - x } else { x };
+                    let ty = if yflip { height - 1 - y } else { y };
+                    let colnr = self.tile_pixel(bank, tile + ty / 8, tx, ty % 8);
+
+                    let rgb = if colnr == 0 {
+                        // Transparent
+                        [255, 0, 255]
+                    } else {
+                        palette[colnr]
+                    };
+
+                    canvas.set(ox + x, oy + y, rgb);
+                }
+            }
+        }
+
+        canvas
+    }
+
+    fn oam_text(&self) -> String {
+        let mut out = String::new();
+
+        let _ = writeln!(out, "Sprites are {} pixels high", self.sprite_size);
+        let _ = writeln!(out);
+        let _ = writeln!(out, "#   Y   X   tile flags palette bank flip prio");
+
+        for index in 0..40 {
+            let (y, x, tilenr, flags) = self.sprite(index);
+
+            let flip = match (flags & (1 << 5) != 0, flags & (1 << 6) != 0) {
+                (false, false) => "-",
+                (true, false) => "x",
+                (false, true) => "y",
+                (true, true) => "xy",
+            };
+
+            let _ =
+                writeln!(
+                out,
+                "{index:<3} {:<3} {:<3} {tilenr:02X}   {flags:02X}    {}       {}    {flip:<4} {}",
+                y as i32 - 16,
+                x as i32 - 8,
+                flags & 0x07,
+                (flags >> 3) & 1,
+                if flags & (1 << 7) != 0 { "below bg" } else { "-" },
+            );
+        }
+
+        out
+    }
+
+    fn palettes(&self) -> Canvas {
+        // BG palettes on the left, sprite palettes on the right, 8×8 per colour
+        let mut canvas = Canvas::new(2 * 4 * 8 + 8, 8 * 8);
+
+        for (side, palettes) in [self.bg_colors, self.sprite_colors].iter().enumerate() {
+            for (nr, palette) in palettes.iter().enumerate() {
+                for (colnr, &rgb) in palette.iter().enumerate() {
+                    canvas.fill(side * 40 + colnr * 8, nr * 8, 8, 8, rgb);
+                }
+            }
+        }
+
+        canvas
+    }
+
+    fn palettes_text(&self) -> String {
+        let mut out = String::new();
+
+        for (name, palettes) in [("BG", self.bg_palettes), ("OBJ", self.sprite_palettes)] {
+            for (nr, palette) in palettes.iter().enumerate() {
+                let colors: Vec<String> = palette
+                    .iter()
+                    .map(|[r, g, b]| {
+                        let rgb555 = *r as u16 | (*g as u16) << 5 | (*b as u16) << 10;
+                        format!("{rgb555:04X}")
+                    })
+                    .collect();
+
+                let _ = writeln!(out, "{name} {nr}: {}", colors.join(" "));
+            }
+        }
+
+        out
+    }
+
+    /// Writes all views to a new folder on a background thread, logging where it was written.
+    pub fn dump_in_background(self) {
+        std::thread::spawn(move || match self.dump() {
+            Ok(path) => log::info!("Dumped VRAM to {}", path.display()),
+            Err(error) => log::error!("Failed to dump VRAM: {error}"),
+        });
+    }
+
+    /// Writes all views to a new folder, returning where it was written.
+    fn dump(&self) -> io::Result<PathBuf> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or(0);
+
+        let dir = saves::get_screenshots_dir().join(format!("vram-{timestamp}"));
+        fs::create_dir_all(&dir)?;
+
+        self.tiles().write(dir.join("tiles.png"))?;
+        self.map(0x9800).write(dir.join("map_9800.png"))?;
+        self.map(0x9c00).write(dir.join("map_9c00.png"))?;
+        fs::write(dir.join("maps.txt"), self.maps_text())?;
+        self.oam().write(dir.join("oam.png"))?;
+        fs::write(dir.join("oam.txt"), self.oam_text())?;
+        self.palettes().write(dir.join("palettes.png"))?;
+        fs::write(dir.join("palettes.txt"), self.palettes_text())?;
+
+        Ok(dir)
+    }
+}